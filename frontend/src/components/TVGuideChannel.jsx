@@ -3,13 +3,13 @@ import { useState, useEffect } from 'react'
 const TVGuideChannel = () => {
   const [currentTime, setCurrentTime] = useState(new Date())
   const [selectedChannel, setSelectedChannel] = useState(null)
+  const [tuned, setTuned] = useState(null)
 
   const channels = [
     {
       id: 1,
       number: "3",
       network: "WKYC",
-      video: "/videos/WKYC 6pm News, June 2001.ia.mp4",
       schedule: [
         { time: "6:00 PM", show: "Evening News", duration: 30 },
         { time: "6:30 PM", show: "NBC Nightly News", duration: 30 },
@@ -21,7 +21,6 @@ const TVGuideChannel = () => {
       id: 2,
       number: "4",
       network: "WUAB",
-      video: "/videos/CaptureA_3483.mp4",
       schedule: [
         { time: "6:00 PM", show: "Movie: Back to the Future", duration: 150 },
         { time: "8:30 PM", show: "Local Programming", duration: 30 }
@@ -31,7 +30,6 @@ const TVGuideChannel = () => {
       id: 3,
       number: "5",
       network: "WEWS",
-      video: "/videos/sample.mp4",
       schedule: [
         { time: "6:00 PM", show: "ABC World News", duration: 30 },
         { time: "6:30 PM", show: "Wheel of Fortune", duration: 30 },
@@ -43,7 +41,6 @@ const TVGuideChannel = () => {
       id: 4,
       number: "8",
       network: "WJW",
-      video: "/videos/sample.mp4",
       schedule: [
         { time: "6:00 PM", show: "FOX 8 News", duration: 60 },
         { time: "7:00 PM", show: "The Simpsons", duration: 30 },
@@ -54,7 +51,6 @@ const TVGuideChannel = () => {
       id: 5,
       number: "19",
       network: "WOIO",
-      video: "/videos/sample.mp4",
       schedule: [
         { time: "6:00 PM", show: "CBS Evening News", duration: 30 },
         { time: "6:30 PM", show: "Access Hollywood", duration: 30 },
@@ -65,7 +61,6 @@ const TVGuideChannel = () => {
       id: 6,
       number: "25",
       network: "WVIZ",
-      video: "/videos/sample.mp4",
       schedule: [
         { time: "6:00 PM", show: "PBS NewsHour", duration: 60 },
         { time: "7:00 PM", show: "Nature: Arctic Wildlife", duration: 90 },
@@ -89,6 +84,25 @@ const TVGuideChannel = () => {
     })
   }
 
+  // Ask the server what the channel is airing and stream it by library ID
+  const tuneChannel = async (channel) => {
+    setSelectedChannel(channel)
+    setTuned(null)
+    try {
+      const response = await fetch(`/live/${channel.number}`)
+      const status = await response.json()
+      if (!response.ok) {
+        setTuned({ message: status.message || 'No signal' })
+      } else if (status.video_id) {
+        setTuned({ src: `/videos/${encodeURIComponent(status.video_id)}#t=${status.offset_seconds}` })
+      } else {
+        setTuned({ message: status.status === 'OFF AIR' ? 'OFF AIR' : status.segment_title })
+      }
+    } catch {
+      setTuned({ message: 'No signal' })
+    }
+  }
+
   const getWidthFromDuration = (duration) => {
     // Base width is 180px for a 30-minute show
     return (duration / 30) * 180 + 'px'
@@ -97,12 +111,16 @@ const TVGuideChannel = () => {
   if (selectedChannel) {
     return (
       <div className="video-player">
-        <video
-          src={selectedChannel.video}
-          autoPlay
-          controls
-          className="full-video"
-        />
+        {tuned?.src ? (
+          <video
+            src={tuned.src}
+            autoPlay
+            controls
+            className="full-video"
+          />
+        ) : (
+          <div className="full-video">{tuned ? tuned.message : `Tuning to CH ${selectedChannel.number}...`}</div>
+        )}
         <button
           onClick={() => setSelectedChannel(null)}
           className="back-button"
@@ -133,7 +151,7 @@ const TVGuideChannel = () => {
             
             <div 
               className="program-list"
-              onClick={() => setSelectedChannel(channel)}
+              onClick={() => tuneChannel(channel)}
             >
               {channel.schedule.map((program, idx) => (
                 <div 
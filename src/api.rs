@@ -0,0 +1,238 @@
+use serde::Serialize;
+use std::convert::Infallible;
+use std::fmt;
+use warp::http::StatusCode;
+use warp::{Rejection, Reply};
+
+use crate::index::LookupError;
+
+// Everything the server's routes can fail with, each with its own status and code
+#[derive(Debug)]
+pub enum ApiError {
+    MissingId,
+    InvalidFilename,
+    VideoNotFound,
+    OutsideLibrary,
+    ChannelNotFound(u8),
+    InvalidBody(String),
+    InvalidChannelMap(String),
+    // The video has no sidecar, so it can't be starred or put on a channel
+    NoMetadata,
+    ChannelTaken(u8),
+    NoFreeChannel,
+    // No credentials, or a token or session that is not valid
+    Unauthorized,
+    InvalidCredentials,
+    // Signed in, but the role does not allow this
+    Forbidden,
+    // Personal channels can only be removed by the viewer who made them
+    NotOwner,
+    InvalidPin,
+    // Too many wrong passwords or PINs; seconds until the next try is checked
+    TooManyAttempts(u64),
+    // Behind a parental lock that this client has not unlocked
+    Locked,
+    InvalidSubscription(String),
+    SubscriptionExists(String),
+    SubscriptionNotFound(String),
+    PlayerUnavailable(String),
+    Internal(String),
+}
+
+// JSON body sent with every error response
+#[derive(Serialize)]
+pub struct ErrorBody {
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ApiError::MissingId
+            | ApiError::InvalidFilename
+            | ApiError::InvalidBody(_)
+            | ApiError::InvalidChannelMap(_)
+            | ApiError::NoMetadata
+            | ApiError::InvalidSubscription(_) => StatusCode::BAD_REQUEST,
+            ApiError::ChannelTaken(_) | ApiError::NoFreeChannel | ApiError::SubscriptionExists(_) => StatusCode::CONFLICT,
+            ApiError::Unauthorized | ApiError::InvalidCredentials => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden | ApiError::NotOwner | ApiError::InvalidPin | ApiError::Locked => StatusCode::FORBIDDEN,
+            ApiError::VideoNotFound | ApiError::ChannelNotFound(_) | ApiError::SubscriptionNotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::OutsideLibrary => StatusCode::FORBIDDEN,
+            ApiError::TooManyAttempts(_) => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::PlayerUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            ApiError::MissingId => "missing_id",
+            ApiError::InvalidFilename => "invalid_filename",
+            ApiError::VideoNotFound => "video_not_found",
+            ApiError::OutsideLibrary => "outside_library",
+            ApiError::ChannelNotFound(_) => "channel_not_found",
+            ApiError::InvalidBody(_) => "invalid_body",
+            ApiError::InvalidChannelMap(_) => "invalid_channel_map",
+            ApiError::NoMetadata => "no_metadata",
+            ApiError::ChannelTaken(_) => "channel_taken",
+            ApiError::NoFreeChannel => "no_free_channel",
+            ApiError::Unauthorized => "unauthorized",
+            ApiError::InvalidCredentials => "invalid_credentials",
+            ApiError::Forbidden => "forbidden",
+            ApiError::NotOwner => "not_owner",
+            ApiError::InvalidPin => "invalid_pin",
+            ApiError::TooManyAttempts(_) => "too_many_attempts",
+            ApiError::Locked => "locked",
+            ApiError::InvalidSubscription(_) => "invalid_subscription",
+            ApiError::SubscriptionExists(_) => "subscription_exists",
+            ApiError::SubscriptionNotFound(_) => "subscription_not_found",
+            ApiError::PlayerUnavailable(_) => "player_unavailable",
+            ApiError::Internal(_) => "internal",
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiError::MissingId => write!(f, "request must include the video's \"id\""),
+            ApiError::InvalidFilename => write!(f, "{}", LookupError::InvalidFilename),
+            ApiError::VideoNotFound => write!(f, "{}", LookupError::NotFound),
+            ApiError::OutsideLibrary => write!(f, "{}", LookupError::OutsideLibrary),
+            ApiError::ChannelNotFound(channel) => write!(f, "channel {} has nothing scheduled", channel),
+            ApiError::InvalidBody(e) => write!(f, "invalid request body: {}", e),
+            ApiError::InvalidChannelMap(e) => write!(f, "invalid channel map: {}", e),
+            ApiError::NoMetadata => write!(f, "video has no sidecar metadata"),
+            ApiError::ChannelTaken(channel) => write!(f, "channel {} is already in use", channel),
+            ApiError::NoFreeChannel => write!(f, "no channel numbers are left for personal channels"),
+            ApiError::Unauthorized => write!(f, "sign in or send an API token"),
+            ApiError::InvalidCredentials => write!(f, "wrong password"),
+            ApiError::Forbidden => write!(f, "an admin account is required"),
+            ApiError::NotOwner => write!(f, "only the viewer who made this channel can remove it"),
+            ApiError::InvalidPin => write!(f, "wrong PIN"),
+            ApiError::TooManyAttempts(seconds) => write!(f, "too many wrong attempts; try again in {} seconds", seconds),
+            ApiError::Locked => write!(f, "locked by parental controls; unlock with the PIN first"),
+            ApiError::InvalidSubscription(e) => write!(f, "invalid subscription: {}", e),
+            ApiError::SubscriptionExists(name) => write!(f, "a subscription named '{}' already exists", name),
+            ApiError::SubscriptionNotFound(name) => write!(f, "no subscription named '{}'", name),
+            ApiError::PlayerUnavailable(e) => write!(f, "could not start the player: {}", e),
+            ApiError::Internal(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+// warp turns any Reject type into a Rejection, so handlers can use `?`
+impl warp::reject::Reject for ApiError {}
+
+impl From<LookupError> for ApiError {
+    fn from(error: LookupError) -> Self {
+        match error {
+            LookupError::InvalidFilename => ApiError::InvalidFilename,
+            LookupError::NotFound => ApiError::VideoNotFound,
+            LookupError::OutsideLibrary => ApiError::OutsideLibrary,
+        }
+    }
+}
+
+fn reply(status: StatusCode, code: &'static str, message: String) -> warp::reply::Response {
+    warp::reply::with_status(warp::reply::json(&ErrorBody { code, message }), status).into_response()
+}
+
+// Turn every rejection, ours or warp's, into a JSON error response
+pub async fn recover(rejection: Rejection) -> Result<warp::reply::Response, Infallible> {
+    if let Some(error) = rejection.find::<ApiError>() {
+        return Ok(reply(error.status(), error.code(), error.to_string()));
+    }
+    if let Some(error) = rejection.find::<warp::filters::body::BodyDeserializeError>() {
+        let error = ApiError::InvalidBody(std::error::Error::source(error).map(|e| e.to_string()).unwrap_or_default());
+        return Ok(reply(error.status(), error.code(), error.to_string()));
+    }
+    let (status, code, message) = if rejection.is_not_found() {
+        (StatusCode::NOT_FOUND, "not_found", "no such route".to_string())
+    } else if rejection.find::<warp::reject::MethodNotAllowed>().is_some() {
+        (StatusCode::METHOD_NOT_ALLOWED, "method_not_allowed", "method not allowed for this route".to_string())
+    } else if rejection.find::<warp::reject::UnsupportedMediaType>().is_some() {
+        (StatusCode::UNSUPPORTED_MEDIA_TYPE, "unsupported_media_type", "request body must be JSON".to_string())
+    } else if rejection.find::<warp::reject::PayloadTooLarge>().is_some() {
+        (StatusCode::PAYLOAD_TOO_LARGE, "payload_too_large", "request body is too large".to_string())
+    } else if let Some(error) = rejection.find::<warp::reject::InvalidQuery>() {
+        (StatusCode::BAD_REQUEST, "invalid_query", error.to_string())
+    } else if let Some(error) = rejection.find::<warp::reject::MissingHeader>() {
+        (StatusCode::BAD_REQUEST, "missing_header", error.to_string())
+    } else if let Some(error) = rejection.find::<warp::reject::InvalidHeader>() {
+        (StatusCode::BAD_REQUEST, "invalid_header", error.to_string())
+    } else {
+        eprintln!("Unhandled rejection: {:?}", rejection);
+        (StatusCode::INTERNAL_SERVER_ERROR, "internal", "internal server error".to_string())
+    };
+    Ok(reply(status, code, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use warp::Filter;
+
+    async fn body(response: warp::reply::Response) -> Value {
+        let bytes = warp::hyper::body::to_bytes(response.into_body()).await.unwrap();
+        serde_json::from_slice(&bytes).unwrap()
+    }
+
+    #[test]
+    fn errors_map_to_their_status_and_code() {
+        for (error, status, code) in [
+            (ApiError::MissingId, StatusCode::BAD_REQUEST, "missing_id"),
+            (ApiError::InvalidFilename, StatusCode::BAD_REQUEST, "invalid_filename"),
+            (ApiError::InvalidBody("x".to_string()), StatusCode::BAD_REQUEST, "invalid_body"),
+            (ApiError::ChannelTaken(3), StatusCode::CONFLICT, "channel_taken"),
+            (ApiError::Unauthorized, StatusCode::UNAUTHORIZED, "unauthorized"),
+            (ApiError::InvalidCredentials, StatusCode::UNAUTHORIZED, "invalid_credentials"),
+            (ApiError::Forbidden, StatusCode::FORBIDDEN, "forbidden"),
+            (ApiError::InvalidPin, StatusCode::FORBIDDEN, "invalid_pin"),
+            (ApiError::Locked, StatusCode::FORBIDDEN, "locked"),
+            (ApiError::OutsideLibrary, StatusCode::FORBIDDEN, "outside_library"),
+            (ApiError::TooManyAttempts(30), StatusCode::TOO_MANY_REQUESTS, "too_many_attempts"),
+            (ApiError::VideoNotFound, StatusCode::NOT_FOUND, "video_not_found"),
+            (ApiError::ChannelNotFound(7), StatusCode::NOT_FOUND, "channel_not_found"),
+            (ApiError::SubscriptionNotFound("news".to_string()), StatusCode::NOT_FOUND, "subscription_not_found"),
+            (ApiError::PlayerUnavailable("gone".to_string()), StatusCode::SERVICE_UNAVAILABLE, "player_unavailable"),
+            (ApiError::Internal("disk".to_string()), StatusCode::INTERNAL_SERVER_ERROR, "internal"),
+        ] {
+            assert_eq!((error.status(), error.code()), (status, code), "{:?}", error);
+        }
+        assert_eq!(ApiError::from(LookupError::InvalidFilename).code(), "invalid_filename");
+        assert_eq!(ApiError::from(LookupError::NotFound).code(), "video_not_found");
+        assert_eq!(ApiError::from(LookupError::OutsideLibrary).code(), "outside_library");
+    }
+
+    #[tokio::test]
+    async fn rejections_become_json_error_bodies() {
+        let response = recover(warp::reject::custom(ApiError::ChannelNotFound(7))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let error = body(response).await;
+        assert_eq!(error["code"], "channel_not_found");
+        assert_eq!(error["message"], "channel 7 has nothing scheduled");
+
+        let route = warp::path!("channels")
+            .and(warp::put())
+            .and(warp::body::json())
+            .map(|_: Value| "ok")
+            .recover(recover);
+        for (request, status, code) in [
+            (warp::test::request().method("PUT").path("/channels").body("{"), StatusCode::BAD_REQUEST, "invalid_body"),
+            (warp::test::request().method("GET").path("/channels"), StatusCode::METHOD_NOT_ALLOWED, "method_not_allowed"),
+            (warp::test::request().method("PUT").path("/nowhere"), StatusCode::NOT_FOUND, "not_found"),
+        ] {
+            let response = request.reply(&route).await;
+            assert_eq!(response.status(), status, "{}", code);
+            let error: Value = serde_json::from_slice(response.body()).unwrap();
+            assert_eq!(error["code"], code);
+            assert!(error["message"].as_str().is_some_and(|message| !message.is_empty()));
+        }
+    }
+}
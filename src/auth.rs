@@ -0,0 +1,268 @@
+use rand::rngs::OsRng;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use warp::http::Method;
+
+// Name of the cookie that carries a password login's session
+pub const SESSION_COOKIE: &str = "channel_surfer_session";
+
+// What a caller may do. Viewers tune and browse; admins can also change the library and channels.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Viewer,
+    Admin,
+}
+
+// Optional login for the server. With no tokens and no passwords every
+// request is treated as an admin, as before authentication existed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthConfig {
+    // Long-lived tokens for scripts and devices, sent as "Authorization: Bearer <token>"
+    pub tokens: Vec<ApiToken>,
+    // Shared household password, logging in as a viewer
+    pub viewer_password: Option<String>,
+    pub admin_password: Option<String>,
+    // How long a password login lasts; a week when unset
+    pub session_hours: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ApiToken {
+    // Shown in the server log, e.g. "living room tablet"
+    pub name: String,
+    pub token: String,
+    pub role: Role,
+}
+
+const DEFAULT_SESSION_HOURS: u64 = 24 * 7;
+
+impl AuthConfig {
+    pub fn enabled(&self) -> bool {
+        !self.tokens.is_empty() || self.viewer_password.is_some() || self.admin_password.is_some()
+    }
+
+    pub fn session_length(&self) -> Duration {
+        Duration::from_secs(self.session_hours.unwrap_or(DEFAULT_SESSION_HOURS) * 3600)
+    }
+
+    fn find_token(&self, token: &str) -> Option<&ApiToken> {
+        self.tokens.iter().find(|t| constant_time_eq(t.token.as_bytes(), token.as_bytes()))
+    }
+
+    pub fn token_role(&self, token: &str) -> Option<Role> {
+        self.find_token(token).map(|t| t.role)
+    }
+
+    pub fn token_name(&self, token: &str) -> Option<&str> {
+        self.find_token(token).map(|t| t.name.as_str())
+    }
+
+    // The role a password logs in as; the admin password wins if both match
+    pub fn password_role(&self, password: &str) -> Option<Role> {
+        let matches = |expected: &Option<String>| {
+            expected.as_deref().is_some_and(|expected| constant_time_eq(expected.as_bytes(), password.as_bytes()))
+        };
+        if matches(&self.admin_password) {
+            Some(Role::Admin)
+        } else if matches(&self.viewer_password) {
+            Some(Role::Viewer)
+        } else {
+            None
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for token in &self.tokens {
+            if token.token.len() < 16 {
+                return Err(format!("auth token '{}' must be at least 16 characters", token.name));
+            }
+        }
+        for (name, password) in [("viewer_password", &self.viewer_password), ("admin_password", &self.admin_password)] {
+            if password.as_deref().is_some_and(str::is_empty) {
+                return Err(format!("auth.{} must not be empty", name));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Compare secrets without returning early at the first differing byte
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+// The least role each route needs; None for routes anyone may use, such as the login page
+pub fn required_role(method: &Method, path: &str) -> Option<Role> {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    match (method, segments.as_slice()) {
+        (_, [""]) | (_, ["login"]) | (_, ["logout"]) => None,
+        (&Method::DELETE, ["videos", _]) => Some(Role::Admin),
+        (&Method::PUT, ["channels"]) => Some(Role::Admin),
+        (&Method::POST, ["subscriptions"]) | (&Method::DELETE, ["subscriptions", _]) => Some(Role::Admin),
+        _ => Some(Role::Viewer),
+    }
+}
+
+// 256 random bits, hex encoded, for session and unlock cookies
+pub(crate) fn random_id() -> String {
+    let mut bytes = [0u8; 32];
+    OsRng.fill_bytes(&mut bytes);
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+struct Session {
+    role: Role,
+    // Name given at login, for keeping separate watch histories
+    viewer: Option<String>,
+    expires: Instant,
+}
+
+// Password logins, kept in memory; restarting the server signs everyone out
+#[derive(Default)]
+pub struct Sessions {
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+impl Sessions {
+    pub fn create(&self, role: Role, viewer: Option<String>, length: Duration) -> String {
+        let id = random_id();
+        let mut sessions = self.sessions.lock().unwrap();
+        let now = Instant::now();
+        sessions.retain(|_, session| session.expires > now);
+        sessions.insert(id.clone(), Session { role, viewer, expires: now + length });
+        id
+    }
+
+    pub fn role(&self, id: &str) -> Option<Role> {
+        let sessions = self.sessions.lock().unwrap();
+        sessions.get(id).filter(|session| session.expires > Instant::now()).map(|session| session.role)
+    }
+
+    pub fn viewer(&self, id: &str) -> Option<String> {
+        let sessions = self.sessions.lock().unwrap();
+        sessions.get(id).filter(|session| session.expires > Instant::now()).and_then(|session| session.viewer.clone())
+    }
+
+    pub fn remove(&self, id: &str) {
+        self.sessions.lock().unwrap().remove(id);
+    }
+}
+
+// Wrong guesses allowed before a client has to wait between tries
+const FREE_ATTEMPTS: u32 = 3;
+// The first wait, doubled by every further wrong guess up to MAX_LOCKOUT
+const BASE_LOCKOUT: Duration = Duration::from_secs(1);
+const MAX_LOCKOUT: Duration = Duration::from_secs(15 * 60);
+// A client's wrong guesses are forgotten this long after its last wait ends
+const FORGET_AFTER: Duration = Duration::from_secs(60 * 60);
+
+struct Failures {
+    count: u32,
+    until: Instant,
+}
+
+// Wrong passwords and PINs by client address, shared by every route that
+// checks a secret so guesses can't be spread across them
+#[derive(Default)]
+pub struct Attempts {
+    failures: Mutex<HashMap<IpAddr, Failures>>,
+}
+
+impl Attempts {
+    // How much longer the client must wait before its next guess is checked
+    pub fn locked_out(&self, client: IpAddr, now: Instant) -> Option<Duration> {
+        let failures = self.failures.lock().unwrap();
+        failures.get(&client).map(|f| f.until.saturating_duration_since(now)).filter(|wait| !wait.is_zero())
+    }
+
+    pub fn fail(&self, client: IpAddr, now: Instant) {
+        let mut failures = self.failures.lock().unwrap();
+        failures.retain(|_, f| f.until + FORGET_AFTER > now);
+        let entry = failures.entry(client).or_insert(Failures { count: 0, until: now });
+        entry.count = entry.count.saturating_add(1);
+        entry.until = now + lockout(entry.count);
+    }
+
+    pub fn succeed(&self, client: IpAddr) {
+        self.failures.lock().unwrap().remove(&client);
+    }
+}
+
+fn lockout(count: u32) -> Duration {
+    match count.checked_sub(FREE_ATTEMPTS + 1) {
+        None => Duration::ZERO,
+        Some(doublings) => BASE_LOCKOUT.saturating_mul(1u32.checked_shl(doublings).unwrap_or(u32::MAX)).min(MAX_LOCKOUT),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn routes_need_viewer_unless_they_change_the_library() {
+        assert_eq!(required_role(&Method::GET, "/"), None);
+        assert_eq!(required_role(&Method::POST, "/login"), None);
+        assert_eq!(required_role(&Method::POST, "/logout"), None);
+        for (method, path) in [
+            (Method::GET, "/videos"),
+            (Method::GET, "/videos/abc"),
+            (Method::POST, "/play"),
+            (Method::GET, "/channels"),
+            (Method::GET, "/subscriptions"),
+            (Method::DELETE, "/videos"),
+            (Method::PUT, "/channels/3"),
+            (Method::GET, "/unknown/route"),
+        ] {
+            assert_eq!(required_role(&method, path), Some(Role::Viewer), "{} {}", method, path);
+        }
+        for (method, path) in [
+            (Method::DELETE, "/videos/abc"),
+            (Method::PUT, "/channels"),
+            (Method::POST, "/subscriptions"),
+            (Method::DELETE, "/subscriptions/news"),
+            (Method::DELETE, "/subscriptions/news/"),
+        ] {
+            assert_eq!(required_role(&method, path), Some(Role::Admin), "{} {}", method, path);
+        }
+    }
+
+    #[test]
+    fn repeated_failures_lock_a_client_out_with_growing_waits() {
+        let attempts = Attempts::default();
+        let client: IpAddr = "192.168.1.20".parse().unwrap();
+        let other: IpAddr = "192.168.1.21".parse().unwrap();
+        let start = Instant::now();
+
+        for _ in 0..FREE_ATTEMPTS {
+            attempts.fail(client, start);
+            assert_eq!(attempts.locked_out(client, start), None);
+        }
+        attempts.fail(client, start);
+        assert_eq!(attempts.locked_out(client, start), Some(BASE_LOCKOUT));
+        assert_eq!(attempts.locked_out(other, start), None);
+
+        let later = start + BASE_LOCKOUT;
+        assert_eq!(attempts.locked_out(client, later), None);
+        attempts.fail(client, later);
+        assert_eq!(attempts.locked_out(client, later), Some(BASE_LOCKOUT * 2));
+
+        for _ in 0..64 {
+            attempts.fail(client, later);
+        }
+        assert_eq!(attempts.locked_out(client, later), Some(MAX_LOCKOUT));
+
+        attempts.succeed(client);
+        assert_eq!(attempts.locked_out(client, later), None);
+    }
+}
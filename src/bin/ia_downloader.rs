@@ -2,7 +2,6 @@ use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
 // Standard ANSI color codes
 use indicatif::{ProgressBar, ProgressStyle};
-use regex::Regex;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::fs::{self, File};
@@ -53,7 +52,8 @@ struct SearchResponse {
 
 #[derive(Serialize, Deserialize, Debug)]
 struct SearchResponseInner {
-    numFound: usize,
+    #[serde(rename = "numFound")]
+    num_found: usize,
     start: usize,
     docs: Vec<Document>,
 }
@@ -144,7 +144,7 @@ async fn search_videos(client: &Client, query: &str, limit: usize, media_type: &
 
     println!(
         "📊 Found {} results (showing {})",
-        search_result.response.numFound,
+        search_result.response.num_found,
         search_result.response.docs.len()
     );
     println!("{}", "=".repeat(80));
@@ -166,8 +166,8 @@ async fn search_videos(client: &Client, query: &str, limit: usize, media_type: &
         const RESET: &str = "\x1b[0m";
 
         println!(
-            "{}{}{} {}{}{} ({})",
-            GOLD, format!("[{}]", i + 1), RESET,
+            "{}[{}]{} {}{}{} ({})",
+            GOLD, i + 1, RESET,
             WHITE, title, RESET,
             year
         );
@@ -274,15 +274,7 @@ async fn download_video(client: &Client, identifier: &str, output_dir: &Path) ->
         .as_ref()
         .map(|t| {
             // Replace invalid filename characters with a simple approach
-            t.replace('/', "_")
-             .replace('\\', "_")
-             .replace('?', "_")
-             .replace(':', "_")
-             .replace('*', "_")
-             .replace('"', "_")
-             .replace('<', "_")
-             .replace('>', "_")
-             .replace('|', "_")
+            t.replace(['/', '\\', '?', ':', '*', '"', '<', '>', '|'], "_")
         })
         .unwrap_or_else(|| identifier.to_string());
 
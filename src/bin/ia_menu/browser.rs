@@ -0,0 +1,540 @@
+use anyhow::Result;
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use regex::Regex;
+use reqwest::Client;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::sync::Mutex;
+use tui::layout::{Constraint, Direction, Layout, Rect};
+use tui::style::{Color, Modifier, Style};
+use tui::text::{Line, Span};
+use tui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap};
+use tui::Frame;
+use video_server::clock::SharedClock;
+use video_server::config::Config;
+use video_server::guide::{self, GUIDE_STYLE, SELECTED_STYLE, WARNING_STYLE};
+
+use super::{
+    fetch_metadata, format_size, pick_mp4, search_archive, spawn_download, Document, DownloadProgress, DownloadStage,
+    DownloadState, MetadataResponse,
+};
+
+// Results requested per search
+const SEARCH_ROWS: usize = 50;
+// Pause after the last keystroke before the query is searched
+const SEARCH_DELAY: Duration = Duration::from_millis(600);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    Query,
+    Results,
+}
+
+enum Details {
+    Loading,
+    Loaded(Box<MetadataResponse>),
+    Failed(String),
+}
+
+// Answers from background requests, delivered back to the screen
+enum Update {
+    Results { query: String, results: Result<Vec<Document>, String> },
+    Details { identifier: String, details: Result<Box<MetadataResponse>, String> },
+}
+
+enum Action {
+    Nothing,
+    Download(Vec<Document>),
+    Quit,
+}
+
+// Search screen state: the query being edited, the latest results and
+// everything queued from here
+struct Browser {
+    query: String,
+    focus: Focus,
+    // Set while the query has changed since it was last searched
+    edited_at: Option<Instant>,
+    searched: Option<String>,
+    searching: bool,
+    results: Vec<Document>,
+    list: ListState,
+    // Marks survive new searches, so picks from several queries can be downloaded together
+    marked: BTreeMap<String, Document>,
+    details: HashMap<String, Details>,
+    downloads: Vec<(String, DownloadProgress)>,
+    status: Option<(String, bool)>,
+}
+
+impl Browser {
+    fn new() -> Self {
+        Self {
+            query: String::new(),
+            focus: Focus::Query,
+            edited_at: None,
+            searched: None,
+            searching: false,
+            results: Vec::new(),
+            list: ListState::default(),
+            marked: BTreeMap::new(),
+            details: HashMap::new(),
+            downloads: Vec::new(),
+            status: None,
+        }
+    }
+
+    fn selected(&self) -> Option<&Document> {
+        self.list.selected().and_then(|index| self.results.get(index))
+    }
+
+    // The query to search now, if typing has paused and it hasn't been searched yet
+    fn search_due(&mut self) -> Option<String> {
+        let query = self.query.trim().to_string();
+        match self.edited_at {
+            Some(at) if at.elapsed() >= SEARCH_DELAY && !query.is_empty() && self.searched.as_deref() != Some(&query) => {
+                self.edited_at = None;
+                self.searched = Some(query.clone());
+                self.searching = true;
+                Some(query)
+            }
+            _ => None,
+        }
+    }
+
+    // The selected item, if its file list hasn't been requested yet
+    fn details_needed(&mut self) -> Option<String> {
+        let identifier = self.selected()?.identifier.clone();
+        if self.details.contains_key(&identifier) {
+            return None;
+        }
+        self.details.insert(identifier.clone(), Details::Loading);
+        Some(identifier)
+    }
+
+    fn apply(&mut self, update: Update) {
+        match update {
+            Update::Results { query, results } => {
+                // Ignore answers to queries that have since been replaced
+                if self.searched.as_deref() != Some(query.as_str()) {
+                    return;
+                }
+                self.searching = false;
+                match results {
+                    Ok(results) => {
+                        self.status = Some((format!("{} result(s) for \"{}\"", results.len(), query), false));
+                        self.list.select(if results.is_empty() { None } else { Some(0) });
+                        self.results = results;
+                    }
+                    Err(e) => self.status = Some((format!("Search failed: {}", e), true)),
+                }
+            }
+            Update::Details { identifier, details } => {
+                let details = match details {
+                    Ok(metadata) => Details::Loaded(metadata),
+                    Err(e) => Details::Failed(e),
+                };
+                self.details.insert(identifier, details);
+            }
+        }
+    }
+
+    fn handle_key(&mut self, code: KeyCode, modifiers: KeyModifiers) -> Action {
+        if code == KeyCode::Esc || (code == KeyCode::Char('c') && modifiers.contains(KeyModifiers::CONTROL)) {
+            return Action::Quit;
+        }
+        match self.focus {
+            Focus::Query => match code {
+                KeyCode::Char(c) => {
+                    self.query.push(c);
+                    self.edited_at = Some(Instant::now());
+                }
+                KeyCode::Backspace => {
+                    self.query.pop();
+                    self.edited_at = Some(Instant::now());
+                }
+                // Search right away instead of waiting for the pause
+                KeyCode::Enter => {
+                    self.edited_at = Some(Instant::now() - SEARCH_DELAY);
+                    self.searched = None;
+                    self.focus = Focus::Results;
+                }
+                KeyCode::Down | KeyCode::Tab if !self.results.is_empty() => self.focus = Focus::Results,
+                _ => {}
+            },
+            Focus::Results => match code {
+                KeyCode::Char('q') => return Action::Quit,
+                KeyCode::Char('/') | KeyCode::Tab => self.focus = Focus::Query,
+                KeyCode::Up => self.move_selection(-1),
+                KeyCode::Down => self.move_selection(1),
+                KeyCode::PageUp => self.move_selection(-10),
+                KeyCode::PageDown => self.move_selection(10),
+                KeyCode::Char(' ') => {
+                    if let Some(doc) = self.selected().cloned() {
+                        if self.marked.remove(&doc.identifier).is_none() {
+                            self.marked.insert(doc.identifier.clone(), doc);
+                        }
+                        self.move_selection(1);
+                    }
+                }
+                // Download everything marked, or just the highlighted item
+                KeyCode::Enter | KeyCode::Char('d') => {
+                    let picked: Vec<Document> = if self.marked.is_empty() {
+                        self.selected().cloned().into_iter().collect()
+                    } else {
+                        std::mem::take(&mut self.marked).into_values().collect()
+                    };
+                    return Action::Download(picked);
+                }
+                _ => {}
+            },
+        }
+        Action::Nothing
+    }
+
+    fn move_selection(&mut self, by: isize) {
+        if self.results.is_empty() {
+            return;
+        }
+        let current = self.list.selected().unwrap_or(0) as isize;
+        let next = (current + by).clamp(0, self.results.len() as isize - 1);
+        self.list.select(Some(next as usize));
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let download_rows = self.downloads.len().min(5) as u16;
+        let areas = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(1),
+                Constraint::Length(3),
+                Constraint::Min(6),
+                Constraint::Length(if download_rows > 0 { download_rows + 2 } else { 0 }),
+                Constraint::Length(1),
+            ])
+            .split(frame.size());
+
+        let header = Paragraph::new(" SEARCH THE INTERNET ARCHIVE").style(GUIDE_STYLE.add_modifier(Modifier::BOLD));
+        frame.render_widget(header, areas[0]);
+
+        self.draw_query(frame, areas[1]);
+
+        let panes = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(45), Constraint::Percentage(55)])
+            .split(areas[2]);
+        self.draw_results(frame, panes[0]);
+        self.draw_details(frame, panes[1]);
+
+        if download_rows > 0 {
+            self.draw_downloads(frame, areas[3]);
+        }
+
+        let footer = match &self.status {
+            Some((text, true)) => Line::styled(text.clone(), WARNING_STYLE),
+            Some((text, false)) => Line::from(format!(
+                "{}  ·  Space mark  Enter download  / edit search  Esc back",
+                text
+            )),
+            None => Line::from("Type to search  Enter show results  Esc back"),
+        };
+        frame.render_widget(Paragraph::new(footer), areas[4]);
+    }
+
+    fn focused_block(&self, title: &str, focus: Focus) -> Block<'static> {
+        let style = if self.focus == focus { Style::new().fg(Color::Yellow) } else { Style::new() };
+        Block::default().borders(Borders::ALL).title(title.to_string()).border_style(style)
+    }
+
+    fn draw_query(&self, frame: &mut Frame, area: Rect) {
+        let mut spans = vec![Span::raw(self.query.clone())];
+        if self.focus == Focus::Query {
+            spans.push(Span::styled(" ", Style::new().bg(Color::Yellow)));
+        }
+        if self.searching {
+            spans.push(Span::styled("  searching…", Style::new().fg(Color::DarkGray)));
+        }
+        let query = Paragraph::new(Line::from(spans)).block(self.focused_block(" Search ", Focus::Query));
+        frame.render_widget(query, area);
+    }
+
+    fn draw_results(&mut self, frame: &mut Frame, area: Rect) {
+        let items: Vec<ListItem> = self
+            .results
+            .iter()
+            .map(|doc| {
+                let mark = if self.marked.contains_key(&doc.identifier) { "● " } else { "  " };
+                let title = doc.title.as_deref().unwrap_or("(No Title)");
+                let year = doc.year.as_deref().map(|y| format!(" ({})", y)).unwrap_or_default();
+                let size = doc.item_size.map(format_size).unwrap_or_else(|| "size unknown".to_string());
+                ListItem::new(vec![
+                    Line::from(format!("{}{}{}", mark, title, year)),
+                    Line::styled(format!("  {}  {}", doc.identifier, size), Style::new().fg(Color::DarkGray)),
+                ])
+            })
+            .collect();
+
+        let title = format!(" Results ({} marked) ", self.marked.len());
+        let list = List::new(items)
+            .block(self.focused_block(&title, Focus::Results))
+            .highlight_style(SELECTED_STYLE);
+        frame.render_stateful_widget(list, area, &mut self.list);
+    }
+
+    fn draw_details(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::default().borders(Borders::ALL).title(" Details ");
+        let Some(doc) = self.selected() else {
+            frame.render_widget(block, area);
+            return;
+        };
+
+        let bold = Style::new().add_modifier(Modifier::BOLD);
+        let mut lines = vec![Line::styled(doc.title.clone().unwrap_or_else(|| doc.identifier.clone()), bold)];
+        let mut facts = Vec::new();
+        if let Some(year) = &doc.year {
+            facts.push(year.clone());
+        }
+        if !doc.creator.is_empty() {
+            facts.push(doc.creator.join(", "));
+        }
+        if let Some(downloads) = doc.downloads {
+            facts.push(format!("{} downloads", downloads));
+        }
+        lines.push(Line::from(facts.join(" · ")));
+        if !doc.subject.is_empty() {
+            lines.push(Line::from(format!("Subjects: {}", doc.subject.join(", "))));
+        }
+        lines.push(Line::from(""));
+        if let Some(description) = &doc.description {
+            lines.push(Line::from(plain_text(description)));
+            lines.push(Line::from(""));
+        }
+
+        match self.details.get(&doc.identifier) {
+            Some(Details::Loaded(metadata)) => {
+                let chosen = pick_mp4(&metadata.files).map(|file| file.name.as_str());
+                lines.push(Line::styled("Files", bold));
+                for file in &metadata.files {
+                    let marker = if Some(file.name.as_str()) == chosen { "▶ " } else { "  " };
+                    lines.push(Line::from(format!(
+                        "{}{}  {}  {}",
+                        marker,
+                        file.name,
+                        file.format.as_deref().unwrap_or("?"),
+                        file.size.map(|s| format_size(s as usize)).unwrap_or_else(|| "size unknown".to_string())
+                    )));
+                }
+                if chosen.is_none() {
+                    lines.push(Line::styled("No MP4 file to download", WARNING_STYLE));
+                }
+            }
+            Some(Details::Failed(e)) => lines.push(Line::styled(format!("Could not load files: {}", e), WARNING_STYLE)),
+            Some(Details::Loading) | None => lines.push(Line::styled("Loading files…", Style::new().fg(Color::DarkGray))),
+        }
+
+        let details = Paragraph::new(lines).block(block).wrap(Wrap { trim: true });
+        frame.render_widget(details, area);
+    }
+
+    fn draw_downloads(&self, frame: &mut Frame, area: Rect) {
+        let width = area.width.saturating_sub(2) as usize;
+        // Newest first, so fresh downloads stay visible
+        let lines: Vec<Line> = self
+            .downloads
+            .iter()
+            .rev()
+            .take(5)
+            .map(|(title, progress)| {
+                let snapshot = progress.snapshot();
+                let title: String = title.chars().take(30).collect();
+                match &snapshot.stage {
+                    DownloadStage::Downloading => {
+                        let bar_width = width.saturating_sub(60).clamp(10, 40);
+                        let filled = snapshot.percent().unwrap_or(0) as usize * bar_width / 100;
+                        Line::from(format!(
+                            "⬇ {:<30} [{}{}] {:>3}%  {} / {}",
+                            title,
+                            "█".repeat(filled),
+                            "░".repeat(bar_width - filled),
+                            snapshot.percent().unwrap_or(0),
+                            format_size(snapshot.downloaded as usize),
+                            if snapshot.total > 0 { format_size(snapshot.total as usize) } else { "?".to_string() }
+                        ))
+                    }
+                    DownloadStage::Queued => Line::from(format!("… {:<30} queued", title)),
+                    DownloadStage::FetchingMetadata => Line::from(format!("… {:<30} fetching metadata", title)),
+                    DownloadStage::Scheduling => Line::from(format!("… {:<30} adding to the schedule", title)),
+                    DownloadStage::Finished => Line::styled(format!("✓ {:<30} done", title), Style::new().fg(Color::Green)),
+                    DownloadStage::Failed(e) => Line::styled(format!("✗ {:<30} {}", title, e), WARNING_STYLE),
+                }
+            })
+            .collect();
+        let block = Block::default().borders(Borders::ALL).title(format!(" Downloads ({}) ", self.downloads.len()));
+        frame.render_widget(Paragraph::new(lines).block(block), area);
+    }
+}
+
+// Internet Archive descriptions are often HTML
+fn plain_text(description: &str) -> String {
+    let tags = Regex::new(r"<[^>]*>").expect("valid regex");
+    tags.replace_all(description, " ")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+        .replace("&quot;", "\"")
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn spawn_search(client: &Client, query: String, updates: &UnboundedSender<Update>) {
+    let client = client.clone();
+    let updates = updates.clone();
+    tokio::spawn(async move {
+        let results = search_archive(&client, &query, SEARCH_ROWS).await.map_err(|e| format!("{:#}", e));
+        let _ = updates.send(Update::Results { query, results });
+    });
+}
+
+fn spawn_details(client: &Client, identifier: String, updates: &UnboundedSender<Update>) {
+    let client = client.clone();
+    let updates = updates.clone();
+    tokio::spawn(async move {
+        let details = fetch_metadata(&client, &identifier).await.map(Box::new).map_err(|e| format!("{:#}", e));
+        let _ = updates.send(Update::Details { identifier, details });
+    });
+}
+
+// Full-screen search: results update as the query is typed, and downloads run
+// in the background with their progress shown at the bottom
+pub async fn run(
+    client: &Client,
+    config: &Config,
+    clock: SharedClock,
+    download_state: Arc<Mutex<DownloadState>>,
+) -> Result<()> {
+    let (updates, mut incoming) = mpsc::unbounded_channel();
+    let mut browser = Browser::new();
+    let (mut terminal, _guard) = guide::enter_terminal()?;
+
+    loop {
+        while let Ok(update) = incoming.try_recv() {
+            browser.apply(update);
+        }
+        if let Some(query) = browser.search_due() {
+            spawn_search(client, query, &updates);
+        }
+        if let Some(identifier) = browser.details_needed() {
+            spawn_details(client, identifier, &updates);
+        }
+
+        terminal.draw(|frame| browser.draw(frame))?;
+
+        if !event::poll(Duration::from_millis(100))? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else { continue };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match browser.handle_key(key.code, key.modifiers) {
+            Action::Quit => break,
+            Action::Download(docs) => {
+                let mut state = download_state.lock().await;
+                for doc in docs {
+                    if state.is_downloading(&doc.identifier) {
+                        continue;
+                    }
+                    let (handle, progress) = spawn_download(client, &doc.identifier, None, config, Arc::clone(&clock), Arc::clone(&state.slots), false);
+                    state.add_download(doc.identifier.clone(), handle, progress.clone()).await;
+                    browser.downloads.push((doc.title.clone().unwrap_or(doc.identifier), progress));
+                }
+            }
+            Action::Nothing => {}
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn doc(identifier: &str) -> Document {
+        serde_json::from_value(serde_json::json!({ "identifier": identifier, "title": identifier, "year": 1985 })).unwrap()
+    }
+
+    fn results(query: &str, identifiers: &[&str]) -> Update {
+        Update::Results { query: query.to_string(), results: Ok(identifiers.iter().map(|id| doc(id)).collect()) }
+    }
+
+    fn type_query(browser: &mut Browser, text: &str) {
+        for c in text.chars() {
+            browser.handle_key(KeyCode::Char(c), KeyModifiers::NONE);
+        }
+    }
+
+    fn downloaded(action: Action) -> Vec<String> {
+        match action {
+            Action::Download(docs) => docs.into_iter().map(|doc| doc.identifier).collect(),
+            _ => panic!("expected a download"),
+        }
+    }
+
+    #[test]
+    fn enter_searches_at_once_and_only_the_latest_answer_counts() {
+        let mut browser = Browser::new();
+        type_query(&mut browser, " news ");
+        // Still typing
+        assert_eq!(browser.search_due(), None);
+        browser.handle_key(KeyCode::Enter, KeyModifiers::NONE);
+        assert_eq!(browser.search_due().as_deref(), Some("news"));
+        assert_eq!(browser.search_due(), None);
+        assert!(browser.searching);
+
+        browser.apply(results("old query", &["stale"]));
+        assert!(browser.results.is_empty());
+        browser.apply(results("news", &["news-1", "news-2"]));
+        assert!(!browser.searching);
+        assert_eq!(browser.selected().map(|doc| doc.identifier.as_str()), Some("news-1"));
+        assert_eq!(browser.details_needed().as_deref(), Some("news-1"));
+        assert_eq!(browser.details_needed(), None);
+    }
+
+    #[test]
+    fn marks_from_several_searches_download_together() {
+        let mut browser = Browser::new();
+        type_query(&mut browser, "news");
+        browser.handle_key(KeyCode::Enter, KeyModifiers::NONE);
+        browser.search_due();
+        browser.apply(results("news", &["news-1", "news-2"]));
+        // Marking moves down, and the selection stops at the last result
+        browser.handle_key(KeyCode::Char(' '), KeyModifiers::NONE);
+        browser.handle_key(KeyCode::PageDown, KeyModifiers::NONE);
+        assert_eq!(browser.selected().map(|doc| doc.identifier.as_str()), Some("news-2"));
+
+        browser.handle_key(KeyCode::Tab, KeyModifiers::NONE);
+        browser.handle_key(KeyCode::Backspace, KeyModifiers::NONE);
+        type_query(&mut browser, "s ads");
+        browser.handle_key(KeyCode::Enter, KeyModifiers::NONE);
+        browser.search_due();
+        browser.apply(results("news ads", &["ad-1", "ad-2"]));
+        browser.handle_key(KeyCode::Down, KeyModifiers::NONE);
+        browser.handle_key(KeyCode::Char(' '), KeyModifiers::NONE);
+
+        let picked = downloaded(browser.handle_key(KeyCode::Char('d'), KeyModifiers::NONE));
+        assert_eq!(picked, ["ad-2", "news-1"]);
+        assert!(browser.marked.is_empty());
+        // With nothing marked, the highlighted item is downloaded
+        assert_eq!(downloaded(browser.handle_key(KeyCode::Enter, KeyModifiers::NONE)), ["ad-2"]);
+
+        assert!(matches!(browser.handle_key(KeyCode::Char('q'), KeyModifiers::NONE), Action::Quit));
+        assert!(matches!(browser.handle_key(KeyCode::Char('c'), KeyModifiers::CONTROL), Action::Quit));
+    }
+
+    #[test]
+    fn descriptions_lose_their_html() {
+        assert_eq!(plain_text("<p>Live from <b>Cleveland</b>&nbsp;&amp; Akron</p>\n<br/>"), "Live from Cleveland & Akron");
+    }
+}
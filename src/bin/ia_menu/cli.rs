@@ -0,0 +1,1038 @@
+// Non-interactive front end: each menu action as a subcommand, for scripts and cron jobs
+
+use anyhow::{anyhow, bail, Result};
+use chrono::Utc;
+use clap::Subcommand;
+use reqwest::Client;
+use serde::Serialize;
+use std::io::{self, IsTerminal};
+use std::path::{Path, PathBuf};
+use video_server::clock::{Clock, SharedClock};
+use video_server::config::Config;
+use video_server::local_time::TimeSettings;
+use video_server::library;
+use video_server::queue::DownloadQueue;
+use video_server::storage::{self, EvictionPlan};
+use video_server::subscriptions::{self, SearchFilters, Subscription, SubscriptionList};
+use video_server::trash::{DeleteFilter, TrashEntry};
+
+use super::import::{self, ImportPlan};
+use super::ops::DuplicateReport;
+use super::{download_video, format_size, ops, packs, prompt_user, search_archive, subscribe, DownloadProgress};
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// List the programs in the library with their next airing
+    List,
+    /// Search the Internet Archive for movies
+    Search {
+        /// Search keywords
+        query: String,
+        /// Number of results to return
+        #[arg(short, long, default_value = "10")]
+        limit: usize,
+    },
+    /// Download Internet Archive items by identifier and schedule them
+    Download {
+        /// Item identifiers, as shown by `search`
+        #[arg(required = true)]
+        identifiers: Vec<String>,
+    },
+    /// Move every video in the download directory to the trash
+    Clear,
+    /// Move the videos that match every condition given to the trash
+    Delete {
+        /// Only videos on this channel
+        #[arg(long)]
+        channel: Option<u8>,
+        /// Only videos in this category, e.g. "Commercial"
+        #[arg(long)]
+        category: Option<String>,
+        /// Only videos downloaded more than this many days ago
+        #[arg(long, value_name = "DAYS")]
+        older_than: Option<u32>,
+        /// Only videos whose file name or title matches this pattern, e.g. "*news*"
+        #[arg(long = "match", value_name = "PATTERN")]
+        pattern: Option<String>,
+    },
+    /// List, restore or empty deleted videos
+    Trash {
+        #[command(subcommand)]
+        action: TrashAction,
+    },
+    /// Find programs that are in the library more than once
+    Dedupe {
+        /// Also compare frames with ffmpeg, to catch different files of the same recording
+        #[arg(long)]
+        fingerprint: bool,
+        /// ffmpeg to run for --fingerprint
+        #[arg(long, default_value = "ffmpeg")]
+        ffmpeg: String,
+        /// Move every copy but the earliest download to the trash
+        #[arg(long)]
+        trash: bool,
+    },
+    /// Show library totals and what each channel is airing now
+    Status,
+    /// Download every item in an Internet Archive collection onto one channel
+    ImportCollection {
+        /// Collection identifier, as in archive.org/details/<collection>
+        collection: String,
+        /// Channel to put every item on
+        #[arg(long)]
+        channel: u8,
+    },
+    /// Show or finish downloads queued by imports
+    Queue {
+        #[command(subcommand)]
+        action: QueueAction,
+    },
+    /// Manage saved searches that download new items as they appear
+    Subscriptions {
+        #[command(subcommand)]
+        action: SubscriptionAction,
+    },
+    /// Show how full the download directory is and what the storage quota would remove
+    Storage {
+        /// Report what would be removed to make room for this many more megabytes
+        #[arg(long, default_value_t = 0)]
+        free_mb: u64,
+        /// Remove it instead of only reporting
+        #[arg(long)]
+        evict: bool,
+    },
+    /// Share channel lineups as packs of channel definitions and item identifiers
+    Pack {
+        #[command(subcommand)]
+        action: PackAction,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum PackAction {
+    /// Write channels and the identifiers of their programs to a pack file
+    Export {
+        /// File to write
+        file: PathBuf,
+        /// Name shown to whoever imports the pack, e.g. "Cleveland 1994"
+        #[arg(long)]
+        name: String,
+        #[arg(long, default_value = "")]
+        description: String,
+        /// Channel to include; repeat for several [default: every channel]
+        #[arg(long = "channel")]
+        channels: Vec<u8>,
+    },
+    /// Add a pack's channels and download the programs missing from the library
+    Import {
+        /// Pack file to read
+        file: PathBuf,
+        /// Also take the pack's filler and commercial break rules, which apply to every channel
+        #[arg(long)]
+        with_rules: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum TrashAction {
+    /// List what is in the trash
+    List,
+    /// Put videos back where they were
+    Restore {
+        /// Trash IDs, as shown by `trash list`, or item identifiers
+        #[arg(required = true)]
+        keys: Vec<String>,
+    },
+    /// Delete everything in the trash for good
+    Empty,
+}
+
+#[derive(Subcommand)]
+pub enum QueueAction {
+    /// List queued downloads
+    List,
+    /// Download everything still queued, e.g. after an import was interrupted
+    Run,
+    /// Empty the queue without downloading anything
+    Clear,
+}
+
+#[derive(Subcommand)]
+pub enum SubscriptionAction {
+    /// List saved searches and what their last run did
+    List,
+    /// Save a search
+    Add {
+        /// Name to refer to the search by
+        name: String,
+        /// Search keywords
+        query: String,
+        /// Only items in this Internet Archive collection
+        #[arg(long)]
+        collection: Option<String>,
+        /// Only items by this creator
+        #[arg(long)]
+        creator: Option<String>,
+        /// Only items from this year on
+        #[arg(long)]
+        year_from: Option<i32>,
+        /// Only items up to this year
+        #[arg(long)]
+        year_to: Option<i32>,
+        /// Channel to file new items under instead of guessing from their category
+        #[arg(long)]
+        channel: Option<u8>,
+        /// New items downloaded per run
+        #[arg(long, default_value_t = subscriptions::DEFAULT_MAX_ITEMS)]
+        max_items: usize,
+        /// Skip items larger than this many megabytes
+        #[arg(long)]
+        max_item_mb: Option<u64>,
+        /// Hours between runs
+        #[arg(long, default_value_t = subscriptions::DEFAULT_INTERVAL_HOURS)]
+        interval_hours: u32,
+    },
+    /// Delete a saved search
+    Remove {
+        name: String,
+    },
+    /// Run the saved searches that are due and download what's new, e.g. from cron
+    Run {
+        /// Run only these searches, whether or not they are due
+        names: Vec<String>,
+        /// Run every search, whether or not it is due
+        #[arg(long)]
+        all: bool,
+    },
+}
+
+// Options shared by every subcommand
+pub struct Output {
+    // Skip confirmation prompts
+    pub yes: bool,
+    // Print machine-readable JSON instead of text
+    pub json: bool,
+}
+
+impl Output {
+    fn print<T: Serialize>(&self, value: &T) -> Result<()> {
+        println!("{}", serde_json::to_string_pretty(value)?);
+        Ok(())
+    }
+}
+
+#[derive(Serialize)]
+struct DownloadResult {
+    identifier: String,
+    file: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct ImportResult {
+    plan: ImportPlan,
+    downloads: Vec<DownloadResult>,
+}
+
+#[derive(Serialize)]
+struct StorageReport {
+    directory: PathBuf,
+    programs: usize,
+    // Including the trash
+    used_bytes: u64,
+    trash_bytes: u64,
+    free_bytes: u64,
+    // None when no quota is set
+    plan: Option<EvictionPlan>,
+    evicted: bool,
+}
+
+#[derive(Serialize)]
+struct PackExport {
+    file: PathBuf,
+    channels: Vec<u8>,
+    programs: usize,
+}
+
+#[derive(Serialize)]
+struct PackImportResult {
+    name: String,
+    channels: Vec<u8>,
+    replaced: Vec<u8>,
+    downloads: Vec<DownloadResult>,
+}
+
+#[derive(Serialize)]
+struct SubscriptionRun {
+    subscription: String,
+    downloads: Vec<DownloadResult>,
+    error: Option<String>,
+}
+
+#[derive(Serialize)]
+struct DedupeResult {
+    #[serde(flatten)]
+    report: DuplicateReport,
+    trashed: Vec<TrashEntry>,
+}
+
+#[derive(Serialize)]
+struct Trashed {
+    // Deleted for good after storage.trash_days
+    purged: Vec<TrashEntry>,
+    trashed: Vec<TrashEntry>,
+}
+
+pub async fn run(command: Command, output: Output, client: &Client, config: &Config, time: TimeSettings, clock: SharedClock) -> Result<()> {
+    match command {
+        Command::List => list(&output, config, &time, clock),
+        Command::Search { query, limit } => search(&output, client, &query, limit).await,
+        Command::Download { identifiers } => download(&output, client, config, time, clock, identifiers).await,
+        Command::Clear => clear(&output, config, clock.as_ref()),
+        Command::Delete { channel, category, older_than, pattern } => {
+            let filter = DeleteFilter { channel, category, older_than_days: older_than, pattern };
+            delete(&output, config, clock.as_ref(), filter)
+        }
+        Command::Trash { action } => match action {
+            TrashAction::List => list_trash(&output, config, clock.as_ref()),
+            TrashAction::Restore { keys } => restore(&output, config, &keys),
+            TrashAction::Empty => empty_trash(&output, config),
+        },
+        Command::Dedupe { fingerprint, ffmpeg, trash } => dedupe(&output, config, clock.as_ref(), fingerprint.then_some(ffmpeg.as_str()), trash),
+        Command::Status => status(&output, config, &time, clock),
+        Command::ImportCollection { collection, channel } => import_collection(&output, client, config, time, clock, &collection, channel).await,
+        Command::Queue { action } => match action {
+            QueueAction::List => list_queue(&output, config),
+            QueueAction::Run => {
+                let results = run_queue(&output, client, config, time, clock, None).await?;
+                if output.json {
+                    output.print(&results)?;
+                } else if results.is_empty() {
+                    println!("Nothing is queued.");
+                }
+                check_failures(&results)
+            }
+            QueueAction::Clear => clear_queue(&output, config),
+        },
+        Command::Subscriptions { action } => match action {
+            SubscriptionAction::List => list_subscriptions(&output, config),
+            SubscriptionAction::Add { name, query, collection, creator, year_from, year_to, channel, max_items, max_item_mb, interval_hours } => {
+                let subscription = Subscription {
+                    name,
+                    query,
+                    filters: SearchFilters { collection, creator, year_from, year_to },
+                    channel,
+                    max_items,
+                    max_item_mb,
+                    interval_hours,
+                    last_run: None,
+                    last_result: None,
+                    seen: Default::default(),
+                };
+                add_subscription(&output, config, subscription)
+            }
+            SubscriptionAction::Remove { name } => remove_subscription(&output, config, &name),
+            SubscriptionAction::Run { names, all } => run_subscriptions(&output, client, config, time, clock, names, all).await,
+        },
+        Command::Storage { free_mb, evict } => storage(&output, config, &time, clock, free_mb, evict),
+        Command::Pack { action } => match action {
+            PackAction::Export { file, name, description, channels } => export_pack(&output, config, clock.as_ref(), file, &name, &description, &channels),
+            PackAction::Import { file, with_rules } => import_pack(&output, client, config, time, clock, &file, with_rules).await,
+        },
+    }
+}
+
+fn list(output: &Output, config: &Config, time: &TimeSettings, clock: SharedClock) -> Result<()> {
+    let listing = ops::load_library(config, time, clock.as_ref())?.listing();
+    if output.json {
+        return output.print(&listing);
+    }
+
+    if listing.programs.is_empty() {
+        println!("No videos found in {}", display_roots(config));
+    }
+    for program in &listing.programs {
+        let metadata = &program.metadata;
+        let airing = match metadata.start_time {
+            Some(start) => time.format_day_time(&time.local(start.with_timezone(&Utc))),
+            None => "unscheduled".to_string(),
+        };
+        let marker = if program.watched { "✓" } else { "•" };
+        println!("{} CH {:>3}  {:<12}  {}  ({})", marker, metadata.channel_number, airing, metadata.title, program.file);
+    }
+    if !listing.unscheduled.is_empty() {
+        println!("⚠️  {} program(s) did not fit anywhere on the schedule", listing.unscheduled.len());
+    }
+    for problem in &listing.problems {
+        eprintln!("⚠️  {}: {}", problem.file, problem.error);
+    }
+    Ok(())
+}
+
+async fn search(output: &Output, client: &Client, query: &str, limit: usize) -> Result<()> {
+    let docs = search_archive(client, query, limit).await?;
+    if output.json {
+        return output.print(&docs);
+    }
+
+    if docs.is_empty() {
+        println!("No results found for query: {}", query);
+    }
+    for doc in &docs {
+        let size = doc.item_size.map(format_size).unwrap_or_else(|| "unknown size".to_string());
+        println!(
+            "{}\t{} ({})\t{}",
+            doc.identifier,
+            doc.title.as_deref().unwrap_or("(No Title)"),
+            doc.year.as_deref().unwrap_or("Unknown"),
+            size
+        );
+    }
+    Ok(())
+}
+
+async fn download(output: &Output, client: &Client, config: &Config, time: TimeSettings, clock: SharedClock, identifiers: Vec<String>) -> Result<()> {
+    let results = download_each(output, client, config, time, clock, identifiers, None).await;
+    if output.json {
+        output.print(&results)?;
+    }
+    check_failures(&results)
+}
+
+// Downloads run one after another so progress output stays readable
+async fn download_each(output: &Output, client: &Client, config: &Config, time: TimeSettings, clock: SharedClock, identifiers: Vec<String>, channel: Option<u8>) -> Vec<DownloadResult> {
+    let mut results = Vec::new();
+    for identifier in identifiers {
+        results.push(download_one(output, client, config, time, clock.clone(), identifier, channel).await);
+    }
+    results
+}
+
+async fn download_one(output: &Output, client: &Client, config: &Config, time: TimeSettings, clock: SharedClock, identifier: String, channel: Option<u8>) -> DownloadResult {
+    let progress = DownloadProgress::new(!output.json);
+    let result = download_video(client, &identifier, channel, config, time, clock, &progress).await;
+    match &result {
+        Ok(file) if !output.json => println!("✓ Download completed: {}", file),
+        Err(e) if !output.json => eprintln!("✗ Download failed for {}: {:#}", identifier, e),
+        _ => {}
+    }
+    match result {
+        Ok(file) => DownloadResult { identifier, file: Some(file), error: None },
+        Err(e) => DownloadResult { identifier, file: None, error: Some(format!("{:#}", e)) },
+    }
+}
+
+// Work through the queue, or the part of it from `source`, noting each
+// outcome as it goes so an interrupted run can be picked up again
+async fn run_queue(output: &Output, client: &Client, config: &Config, time: TimeSettings, clock: SharedClock, source: Option<&str>) -> Result<Vec<DownloadResult>> {
+    let path = &config.downloads.queue_file;
+    let queue = import::load_queue(config)?;
+    let items = queue.items.into_iter().filter(|item| source.is_none_or(|source| item.source == source));
+    let mut results = Vec::new();
+    for item in items {
+        let result = download_one(output, client, config, time, clock.clone(), item.identifier, item.channel).await;
+        // Reload in case the menu changed the queue meanwhile
+        let mut queue = DownloadQueue::load(path)?;
+        match &result.error {
+            None => queue.complete(&result.identifier),
+            Some(error) => queue.fail(&result.identifier, error.clone()),
+        }
+        queue.save(path)?;
+        results.push(result);
+    }
+    Ok(results)
+}
+
+async fn import_collection(output: &Output, client: &Client, config: &Config, time: TimeSettings, clock: SharedClock, collection: &str, channel: u8) -> Result<()> {
+    if !output.json {
+        println!("🔍 Listing {}...", collection);
+    }
+    let plan = import::plan(client, config, collection).await?;
+    if !output.json {
+        println!("{}", plan.summary());
+    }
+    if plan.identifiers.is_empty() && !output.json {
+        println!("Nothing new to import.");
+    }
+    if !plan.identifiers.is_empty() && !output.yes {
+        if !io::stdin().is_terminal() {
+            return Err(anyhow!("refusing to download {} item(s) without confirmation; pass --yes", plan.identifiers.len()));
+        }
+        let confirm = prompt_user(&format!("Download {} item(s) onto channel {}? (y/n): ", plan.identifiers.len(), channel))?;
+        if confirm.to_lowercase() != "y" {
+            println!("Operation cancelled.");
+            return Ok(());
+        }
+    }
+
+    import::enqueue(config, clock.as_ref(), &plan, channel)?;
+    let downloads = run_queue(output, client, config, time, clock, Some(&import::source(collection))).await?;
+    let failures = check_failures(&downloads);
+    if output.json {
+        output.print(&ImportResult { plan, downloads })?;
+    }
+    failures
+}
+
+fn storage(output: &Output, config: &Config, time: &TimeSettings, clock: SharedClock, free_mb: u64, evict: bool) -> Result<()> {
+    let programs = ops::stored_programs(config, time, clock.as_ref())?;
+    let trash_bytes = ops::trash_bytes(config);
+    let mut report = StorageReport {
+        directory: config.download_dir().to_path_buf(),
+        programs: programs.len(),
+        used_bytes: trash_bytes + programs.iter().map(|program| program.bytes).sum::<u64>(),
+        trash_bytes,
+        free_bytes: storage::free_space(config.download_dir())?,
+        plan: config.storage.plan(programs, trash_bytes, free_mb * 1024 * 1024),
+        evicted: false,
+    };
+
+    if !output.json {
+        let directory = report.directory.display();
+        let used = if report.trash_bytes > 0 {
+            format!("{} ({} of it in the trash)", format_size(report.used_bytes as usize), format_size(report.trash_bytes as usize))
+        } else {
+            format_size(report.used_bytes as usize)
+        };
+        let free = format_size(report.free_bytes as usize);
+        let Some(plan) = &report.plan else {
+            println!("💾 {}: {} in {} program(s), {} free on the disk; no storage quota is set.", directory, used, report.programs, free);
+            return Ok(());
+        };
+        println!(
+            "💾 {}: {} in {} program(s) of a {} quota, evicting {}; {} free on the disk",
+            directory,
+            used,
+            report.programs,
+            format_size(plan.quota_bytes as usize),
+            plan.policy,
+            free
+        );
+        if plan.empty_trash {
+            println!("The trash would be emptied, freeing {}.", format_size(plan.trash_bytes as usize));
+        }
+        if plan.evict.is_empty() {
+            if !plan.empty_trash {
+                println!("Nothing needs removing.");
+            }
+        } else {
+            println!("{} program(s) would be removed, freeing {}:", plan.evict.len(), format_size(plan.freed_bytes() as usize));
+        }
+        for program in &plan.evict {
+            let aired = program.last_aired.map(|at| time.format_day_time(&time.local(at))).unwrap_or_else(|| "not airing".to_string());
+            let watched = program.last_watched.map(|at| time.format_day_time(&time.local(at))).unwrap_or_else(|| "never".to_string());
+            println!(
+                "  - {} ({}; downloaded {}, last aired {}, last watched {})",
+                program.title,
+                format_size(program.bytes as usize),
+                time.local(program.downloaded).format("%Y-%m-%d"),
+                aired,
+                watched
+            );
+        }
+        if plan.shortfall_bytes > 0 {
+            println!("⚠️  Still {} over the quota; everything else is pinned or the policy removes nothing.", format_size(plan.shortfall_bytes as usize));
+        }
+    }
+
+    let to_remove = report.plan.as_ref().map_or(0, |plan| plan.evict.len());
+    let empty_trash = report.plan.as_ref().is_some_and(|plan| plan.empty_trash);
+    if evict && (to_remove > 0 || empty_trash) {
+        if !output.yes {
+            if !io::stdin().is_terminal() {
+                return Err(anyhow!("refusing to remove {} program(s) without confirmation; pass --yes", to_remove));
+            }
+            let what = if empty_trash { format!("Empty the trash and remove {} program(s)", to_remove) } else { format!("Remove {} program(s)", to_remove) };
+            if prompt_user(&format!("{}? (y/n): ", what))?.to_lowercase() != "y" {
+                println!("Operation cancelled.");
+                return Ok(());
+            }
+        }
+        if let Some(plan) = &report.plan {
+            ops::evict(config, plan)?;
+        }
+        report.evicted = true;
+        if !output.json {
+            let trash = if empty_trash { ", and emptied the trash" } else { "" };
+            println!("✓ Removed {} program(s) and their sidecars{}.", to_remove, trash);
+        }
+    }
+    if output.json {
+        output.print(&report)?;
+    }
+    Ok(())
+}
+
+fn export_pack(output: &Output, config: &Config, clock: &dyn Clock, file: PathBuf, name: &str, description: &str, channels: &[u8]) -> Result<()> {
+    let pack = packs::export(config, clock, name, description, channels)?;
+    if pack.channels.is_empty() {
+        bail!("no channels to pack; channels are defined in {}", config.channel_map.display());
+    }
+    pack.save(&file)?;
+    if output.json {
+        let channels = pack.channels.iter().map(|channel| channel.number).collect();
+        return output.print(&PackExport { file, channels, programs: pack.programs.len() });
+    }
+    println!("✓ Packed {} channel(s) and {} program(s) into {}", pack.channels.len(), pack.programs.len(), file.display());
+    Ok(())
+}
+
+async fn import_pack(output: &Output, client: &Client, config: &Config, time: TimeSettings, clock: SharedClock, file: &Path, with_rules: bool) -> Result<()> {
+    let import = packs::prepare(config, file)?;
+    if !output.json {
+        println!("{}", import.summary());
+    }
+    if !output.yes {
+        if !io::stdin().is_terminal() {
+            return Err(anyhow!("refusing to import {} without confirmation; pass --yes", import.pack.name));
+        }
+        if prompt_user("Add these channels? (y/n): ")?.to_lowercase() != "y" {
+            println!("Operation cancelled.");
+            return Ok(());
+        }
+    }
+
+    packs::apply(config, clock.as_ref(), &import, with_rules)?;
+    if !output.json {
+        println!("✓ Channels added to {}", config.channel_map.display());
+    }
+    let downloads = run_queue(output, client, config, time, clock, Some(&packs::source(&import.pack))).await?;
+    let failures = check_failures(&downloads);
+    if output.json {
+        output.print(&PackImportResult {
+            name: import.pack.name.clone(),
+            channels: import.pack.channels.iter().map(|channel| channel.number).collect(),
+            replaced: import.conflicts,
+            downloads,
+        })?;
+    }
+    failures
+}
+
+fn list_queue(output: &Output, config: &Config) -> Result<()> {
+    let queue = import::load_queue(config)?;
+    if output.json {
+        return output.print(&queue.items);
+    }
+
+    if queue.items.is_empty() {
+        println!("Nothing is queued.");
+    }
+    for item in &queue.items {
+        let channel = item.channel.map(|channel| format!("CH {:>3}", channel)).unwrap_or_else(|| "CH   -".to_string());
+        match &item.error {
+            Some(error) => println!("{}  {}  {}  ✗ {}", channel, item.identifier, item.source, error),
+            None => println!("{}  {}  {}", channel, item.identifier, item.source),
+        }
+    }
+    Ok(())
+}
+
+fn clear_queue(output: &Output, config: &Config) -> Result<()> {
+    let path = &config.downloads.queue_file;
+    let queue = DownloadQueue::load(path)?;
+    DownloadQueue::default().save(path)?;
+    if output.json {
+        return output.print(&queue.items);
+    }
+    println!("✓ Removed {} queued download(s).", queue.items.len());
+    Ok(())
+}
+
+fn check_failures(results: &[DownloadResult]) -> Result<()> {
+    let failed = results.iter().filter(|result| result.error.is_some()).count();
+    if failed > 0 {
+        bail!("{} of {} download(s) failed", failed, results.len());
+    }
+    Ok(())
+}
+
+fn list_subscriptions(output: &Output, config: &Config) -> Result<()> {
+    let list = SubscriptionList::load(&config.downloads.subscriptions_file)?;
+    if output.json {
+        return output.print(&list.subscriptions);
+    }
+
+    if list.subscriptions.is_empty() {
+        println!("No saved searches in {}", config.downloads.subscriptions_file.display());
+    }
+    for subscription in &list.subscriptions {
+        println!("{}", subscribe::summary(subscription));
+    }
+    Ok(())
+}
+
+fn add_subscription(output: &Output, config: &Config, subscription: Subscription) -> Result<()> {
+    let path = &config.downloads.subscriptions_file;
+    let mut list = SubscriptionList::load(path)?;
+    list.add(subscription.clone())?;
+    list.save(path)?;
+    if output.json {
+        return output.print(&subscription);
+    }
+    println!("✓ Saved search {}", subscription.name);
+    Ok(())
+}
+
+fn remove_subscription(output: &Output, config: &Config, name: &str) -> Result<()> {
+    let path = &config.downloads.subscriptions_file;
+    let mut list = SubscriptionList::load(path)?;
+    let removed = list.remove(name).ok_or_else(|| anyhow!("no saved search named '{}'", name))?;
+    list.save(path)?;
+    if output.json {
+        return output.print(&removed);
+    }
+    println!("✓ Removed saved search {}", removed.name);
+    Ok(())
+}
+
+async fn run_subscriptions(output: &Output, client: &Client, config: &Config, time: TimeSettings, clock: SharedClock, names: Vec<String>, all: bool) -> Result<()> {
+    let list = SubscriptionList::load(&config.downloads.subscriptions_file)?;
+    for name in &names {
+        if list.get(name).is_none() {
+            bail!("no saved search named '{}'", name);
+        }
+    }
+    let now = clock.now();
+    let selected = list.subscriptions.iter().filter(|subscription| {
+        if names.is_empty() {
+            all || subscription.is_due(now)
+        } else {
+            names.contains(&subscription.name)
+        }
+    });
+
+    let mut runs = Vec::new();
+    for subscription in selected {
+        if !output.json {
+            println!("🔍 {}", subscription.name);
+        }
+        let run = match subscribe::find_new(client, config, subscription).await {
+            Ok(found) => {
+                if !output.json {
+                    println!("   {} new item(s)", found.len());
+                }
+                let downloads = download_each(output, client, config, time, clock.clone(), found.clone(), subscription.channel).await;
+                subscribe::update_list(config, |list| list.record_run(&subscription.name, now, &found))?;
+                SubscriptionRun { subscription: subscription.name.clone(), downloads, error: None }
+            }
+            Err(e) => {
+                let error = format!("{:#}", e);
+                if !output.json {
+                    eprintln!("✗ Search failed for {}: {}", subscription.name, error);
+                }
+                subscribe::update_list(config, |list| list.record_failure(&subscription.name, &error))?;
+                SubscriptionRun { subscription: subscription.name.clone(), downloads: Vec::new(), error: Some(error) }
+            }
+        };
+        runs.push(run);
+    }
+
+    if output.json {
+        output.print(&runs)?;
+    } else if runs.is_empty() {
+        println!("No saved searches are due.");
+    }
+    let failed = runs.iter().filter(|run| run.error.is_some()).count();
+    if failed > 0 {
+        bail!("{} of {} saved search(es) failed", failed, runs.len());
+    }
+    let downloads: Vec<DownloadResult> = runs.into_iter().flat_map(|run| run.downloads).collect();
+    check_failures(&downloads)
+}
+
+// Only the download directory is cleared; other library roots are left alone
+fn clear(output: &Output, config: &Config, clock: &dyn Clock) -> Result<()> {
+    let videos_dir = config.download_dir();
+    if !output.yes {
+        if !io::stdin().is_terminal() {
+            return Err(anyhow!("refusing to delete videos without confirmation; pass --yes"));
+        }
+        let confirm = prompt_user(&format!("⚠️  Move every video in {} to the trash? (y/n): ", videos_dir.display()))?;
+        if confirm.to_lowercase() != "y" {
+            println!("Operation cancelled.");
+            return Ok(());
+        }
+    }
+
+    let purged = ops::purge_expired_trash(config, clock)?;
+    let trashed = ops::clear_videos(config, clock)?;
+    report_trashed(output, config, Trashed { purged, trashed })
+}
+
+fn delete(output: &Output, config: &Config, clock: &dyn Clock, filter: DeleteFilter) -> Result<()> {
+    if filter.is_empty() {
+        bail!("give at least one of --channel, --category, --older-than or --match; `clear` empties the download directory");
+    }
+    let scan = library::scan_roots(&config.library_roots)?;
+    let selected = filter.select(&scan.items, clock.now())?;
+    if !output.json {
+        for item in &selected {
+            println!("CH {:>3}  {}  {}", item.metadata.channel_number, ops::file_name(&item.video_path), item.metadata.title);
+        }
+        if selected.is_empty() {
+            println!("No videos match.");
+        }
+    }
+    if !selected.is_empty() && !output.yes {
+        if !io::stdin().is_terminal() {
+            return Err(anyhow!("refusing to delete {} video(s) without confirmation; pass --yes", selected.len()));
+        }
+        if prompt_user(&format!("Move these {} video(s) to the trash? (y/n): ", selected.len()))?.to_lowercase() != "y" {
+            println!("Operation cancelled.");
+            return Ok(());
+        }
+    }
+
+    let purged = ops::purge_expired_trash(config, clock)?;
+    let trashed = ops::trash_items(config, clock, &selected)?;
+    report_trashed(output, config, Trashed { purged, trashed })
+}
+
+fn report_trashed(output: &Output, config: &Config, result: Trashed) -> Result<()> {
+    if output.json {
+        return output.print(&result);
+    }
+    if !result.purged.is_empty() {
+        println!("Emptied {} video(s) from the trash after {} days.", result.purged.len(), config.storage.trash_days);
+    }
+    println!(
+        "✓ Moved {} video(s) to the trash in {}; `trash restore` brings them back for {} days.",
+        result.trashed.len(),
+        config.trash().dir().display(),
+        config.storage.trash_days
+    );
+    Ok(())
+}
+
+fn list_trash(output: &Output, config: &Config, clock: &dyn Clock) -> Result<()> {
+    ops::purge_expired_trash(config, clock)?;
+    let entries = config.trash().entries()?;
+    if output.json {
+        return output.print(&entries);
+    }
+
+    if entries.is_empty() {
+        println!("The trash is empty.");
+    }
+    for entry in &entries {
+        println!("{}  {}  {} file(s)  {}", entry.trashed_at.format("%Y-%m-%d %H:%M UTC"), entry.id, entry.files.len(), entry.title);
+    }
+    Ok(())
+}
+
+fn restore(output: &Output, config: &Config, keys: &[String]) -> Result<()> {
+    let trash = config.trash();
+    let mut restored = Vec::new();
+    for key in keys {
+        let entry = trash.find(key)?.ok_or_else(|| anyhow!("nothing in the trash matches '{}'", key))?;
+        trash.restore(&entry)?;
+        if !output.json {
+            println!("✓ Restored {}", entry.title);
+        }
+        restored.push(entry);
+    }
+    if output.json {
+        return output.print(&restored);
+    }
+    Ok(())
+}
+
+fn empty_trash(output: &Output, config: &Config) -> Result<()> {
+    let trash = config.trash();
+    let entries = trash.entries()?;
+    if !entries.is_empty() && !output.yes {
+        if !io::stdin().is_terminal() {
+            return Err(anyhow!("refusing to empty the trash without confirmation; pass --yes"));
+        }
+        if prompt_user(&format!("⚠️  Delete {} video(s) in the trash for good? (y/n): ", entries.len()))?.to_lowercase() != "y" {
+            println!("Operation cancelled.");
+            return Ok(());
+        }
+    }
+    for entry in &entries {
+        trash.purge(entry)?;
+    }
+    if output.json {
+        return output.print(&entries);
+    }
+    println!("✓ Deleted {} video(s) for good.", entries.len());
+    Ok(())
+}
+
+fn dedupe(output: &Output, config: &Config, clock: &dyn Clock, ffmpeg: Option<&str>, trash: bool) -> Result<()> {
+    let report = ops::find_duplicates(config, ffmpeg)?;
+    let extras = report.extras().count();
+    if !output.json {
+        if report.hashed > 0 {
+            println!("Hashed {} video(s) and saved the hashes in their sidecars.", report.hashed);
+        }
+        for skipped in &report.skipped {
+            println!("⚠️  Couldn't fingerprint {}: {}", skipped.file, skipped.reason);
+        }
+        for group in &report.groups {
+            println!("\n{} copies, {}:", group.files.len(), group.reason);
+            for (i, file) in group.files.iter().enumerate() {
+                let mark = if i == 0 { "keep" } else { "    " };
+                println!("  {}  {}  {}  {}", mark, file.download_date.format("%Y-%m-%d"), ops::file_name(&file.video_path), file.original_id);
+            }
+        }
+        if report.groups.is_empty() {
+            println!("No duplicates among {} program(s).", report.programs);
+        } else {
+            println!("\n{} extra cop(ies) in {} group(s).", extras, report.groups.len());
+        }
+    }
+
+    let mut trashed = Vec::new();
+    if trash && extras > 0 {
+        if !output.yes {
+            if !io::stdin().is_terminal() {
+                return Err(anyhow!("refusing to delete {} video(s) without confirmation; pass --yes", extras));
+            }
+            if prompt_user(&format!("Move the {} extra cop(ies) to the trash? (y/n): ", extras))?.to_lowercase() != "y" {
+                println!("Operation cancelled.");
+                return Ok(());
+            }
+        }
+        ops::purge_expired_trash(config, clock)?;
+        trashed = ops::trash_duplicates(config, clock, &report)?;
+        if !output.json {
+            println!("✓ Moved {} video(s) to the trash; `trash restore` brings them back.", trashed.len());
+        }
+    } else if extras > 0 && !output.json {
+        println!("Run with --trash to move the extra copies to the trash.");
+    }
+    if output.json {
+        return output.print(&DedupeResult { report, trashed });
+    }
+    Ok(())
+}
+
+fn status(output: &Output, config: &Config, time: &TimeSettings, clock: SharedClock) -> Result<()> {
+    let mut library = ops::load_library(config, time, clock.as_ref())?;
+    library.apply_parental_locks(&config.parental);
+    let status = ops::status(&library, time, clock.as_ref());
+    if output.json {
+        return output.print(&status);
+    }
+
+    println!("{}", time.format_day_time(&time.local(status.now.with_timezone(&Utc))));
+    println!(
+        "{} video(s), {}, {} unscheduled, {} problem(s)",
+        status.videos,
+        format_size(status.library_bytes as usize),
+        status.unscheduled,
+        status.problems
+    );
+    for channel in &status.channels {
+        println!(
+            "CH {:>3}  {:<6}  {:<7}  {} (until {})",
+            channel.channel,
+            channel.callsign,
+            channel.status,
+            channel.segment_title,
+            time.format_time(&time.local(channel.ends_at.with_timezone(&Utc)))
+        );
+    }
+    Ok(())
+}
+
+fn display_roots(config: &Config) -> String {
+    config.library_roots.iter().map(|root| root.display().to_string()).collect::<Vec<_>>().join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use clap::Parser;
+    use std::fs;
+    use std::sync::Arc;
+    use video_server::clock::SimulatedClock;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(subcommand)]
+        command: Command,
+    }
+
+    fn parse(args: &[&str]) -> Result<Command, clap::Error> {
+        Cli::try_parse_from(std::iter::once("ia_menu").chain(args.iter().copied())).map(|cli| cli.command)
+    }
+
+    fn test_config(name: &str) -> Config {
+        let dir = std::env::temp_dir().join(format!("cli-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let mut config = Config { library_roots: vec![dir.clone()], ..Config::default() };
+        config.downloads.queue_file = dir.join("queue.json");
+        config.downloads.subscriptions_file = dir.join("subscriptions.json");
+        config
+    }
+
+    async fn run_script(config: &Config, args: &[&str]) -> Result<()> {
+        let clock: SharedClock = Arc::new(SimulatedClock::new(Utc.timestamp_opt(1_700_000_000, 0).unwrap(), 1.0));
+        let time = TimeSettings { timezone: chrono_tz::UTC, ..TimeSettings::default() };
+        run(parse(args).unwrap(), Output { yes: true, json: true }, &Client::new(), config, time, clock).await
+    }
+
+    #[test]
+    fn parses_filters_and_repeated_flags() {
+        let Ok(Command::Delete { channel, category, older_than, pattern }) = parse(&["delete", "--channel", "3", "--older-than", "30", "--match", "*news*"]) else {
+            panic!("expected delete");
+        };
+        assert_eq!((channel, category, older_than, pattern.as_deref()), (Some(3), None, Some(30), Some("*news*")));
+
+        let Ok(Command::Pack { action: PackAction::Export { file, name, description, channels } }) =
+            parse(&["pack", "export", "lineup.json", "--name", "Cleveland 1994", "--channel", "3", "--channel", "5"])
+        else {
+            panic!("expected pack export");
+        };
+        assert_eq!((file, name.as_str(), description.as_str(), channels), (PathBuf::from("lineup.json"), "Cleveland 1994", "", vec![3, 5]));
+
+        assert!(parse(&["download"]).is_err());
+        assert!(parse(&["import-collection", "classic_tv_commercials"]).is_err());
+        assert!(parse(&["search", "news", "--limit", "many"]).is_err());
+    }
+
+    #[test]
+    fn any_failed_download_fails_the_command() {
+        let result = |identifier: &str, error: Option<&str>| DownloadResult {
+            identifier: identifier.to_string(),
+            file: None,
+            error: error.map(str::to_string),
+        };
+        assert!(check_failures(&[]).is_ok());
+        assert!(check_failures(&[result("news", None)]).is_ok());
+        let error = check_failures(&[result("news", None), result("movie", Some("404"))]).unwrap_err();
+        assert_eq!(error.to_string(), "1 of 2 download(s) failed");
+    }
+
+    #[tokio::test]
+    async fn saved_searches_are_added_and_removed_by_name() {
+        let config = test_config("subscriptions");
+        run_script(&config, &["subscriptions", "add", "cleveland", "cleveland news", "--channel", "5", "--max-item-mb", "500"]).await.unwrap();
+        let list = SubscriptionList::load(&config.downloads.subscriptions_file).unwrap();
+        let saved = list.get("cleveland").unwrap();
+        assert_eq!((saved.query.as_str(), saved.channel, saved.max_item_mb), ("cleveland news", Some(5), Some(500)));
+
+        // Names are unique, and removing one that isn't there is an error for the script to see
+        assert!(run_script(&config, &["subscriptions", "add", "cleveland", "akron news"]).await.is_err());
+        assert!(run_script(&config, &["subscriptions", "remove", "akron"]).await.is_err());
+        run_script(&config, &["subscriptions", "remove", "cleveland"]).await.unwrap();
+        assert!(SubscriptionList::load(&config.downloads.subscriptions_file).unwrap().subscriptions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn clearing_the_queue_empties_it() {
+        let config = test_config("queue");
+        let mut queue = DownloadQueue::default();
+        queue.enqueue("news", Some(5), "collection:cleveland", Utc.timestamp_opt(1_700_000_000, 0).unwrap());
+        queue.save(&config.downloads.queue_file).unwrap();
+
+        run_script(&config, &["queue", "list"]).await.unwrap();
+        run_script(&config, &["queue", "clear"]).await.unwrap();
+        assert!(DownloadQueue::load(&config.downloads.queue_file).unwrap().items.is_empty());
+    }
+}
@@ -0,0 +1,127 @@
+// Deleting videos by channel, category, age or name, and getting them back
+// out of the trash
+
+use anyhow::Result;
+use std::io::{self, Write};
+use video_server::clock::Clock;
+use video_server::config::Config;
+use video_server::library;
+use video_server::trash::DeleteFilter;
+
+use super::{ops, prompt_user};
+
+pub fn menu(config: &Config, clock: &dyn Clock) -> Result<()> {
+    print!("\x1B[2J\x1B[1;1H");
+    io::stdout().flush()?;
+    println!("🗑️  Delete Videos");
+    println!("=====================================\n");
+
+    for entry in ops::purge_expired_trash(config, clock)? {
+        println!("Emptied {} from the trash after {} days.", entry.title, config.storage.trash_days);
+    }
+    println!(
+        "Deleted videos go to the trash in {} with their sidecars and thumbnails, and can be restored for {} days.\n",
+        config.trash().dir().display(),
+        config.storage.trash_days
+    );
+    println!("c. By channel");
+    println!("g. By category");
+    println!("o. Downloaded more than a number of days ago");
+    println!("p. File name or title matching a pattern, e.g. \"*news*\"");
+    println!("d. Duplicates, keeping the earliest download of each");
+    println!("a. Everything in {}", config.download_dir().display());
+    println!("r. Restore from the trash");
+
+    let mut filter = DeleteFilter::default();
+    match prompt_user("\nEnter your choice (or press Enter to return): ")?.as_str() {
+        "c" => match prompt_user("Channel: ")?.parse() {
+            Ok(channel) => filter.channel = Some(channel),
+            Err(_) => return finish("Invalid channel number."),
+        },
+        "g" => filter.category = Some(prompt_user("Category, e.g. \"Commercial\": ")?).filter(|category| !category.is_empty()),
+        "o" => match prompt_user("Days: ")?.parse() {
+            Ok(days) => filter.older_than_days = Some(days),
+            Err(_) => return finish("Invalid number of days."),
+        },
+        "p" => filter.pattern = Some(prompt_user("Pattern: ")?).filter(|pattern| !pattern.is_empty()),
+        "a" => {
+            let confirm = prompt_user(&format!("⚠️  Move every video in {} to the trash? (y/n): ", config.download_dir().display()))?;
+            if confirm.to_lowercase() != "y" {
+                return finish("Operation cancelled.");
+            }
+            let trashed = ops::clear_videos(config, clock)?;
+            return finish(&format!("✓ Moved {} video(s) to the trash.", trashed.len()));
+        }
+        "d" => return duplicates(config, clock),
+        "r" => return restore(config),
+        _ => return Ok(()),
+    }
+    if filter.is_empty() {
+        return Ok(());
+    }
+
+    let scan = library::scan_roots(&config.library_roots)?;
+    let selected = match filter.select(&scan.items, clock.now()) {
+        Ok(selected) => selected,
+        Err(e) => return finish(&format!("{:#}", e)),
+    };
+    if selected.is_empty() {
+        return finish("No videos match.");
+    }
+    println!();
+    for item in &selected {
+        println!(" - CH {:>3}  {} ({})", item.metadata.channel_number, item.metadata.title, item.metadata.category);
+    }
+    if prompt_user(&format!("\nMove these {} video(s) to the trash? (y/n): ", selected.len()))?.to_lowercase() != "y" {
+        return finish("Operation cancelled.");
+    }
+    let trashed = ops::trash_items(config, clock, &selected)?;
+    finish(&format!("✓ Moved {} video(s) to the trash.", trashed.len()))
+}
+
+fn duplicates(config: &Config, clock: &dyn Clock) -> Result<()> {
+    println!("\nComparing files; videos without a content hash in their sidecar are hashed first...");
+    let report = ops::find_duplicates(config, None)?;
+    if report.groups.is_empty() {
+        return finish(&format!("No duplicates among {} program(s).", report.programs));
+    }
+    for group in &report.groups {
+        println!("\n{} copies, {}:", group.files.len(), group.reason);
+        for (i, file) in group.files.iter().enumerate() {
+            let mark = if i == 0 { "keep" } else { "    " };
+            println!("  {}  {}  {}", mark, file.download_date.format("%Y-%m-%d"), ops::file_name(&file.video_path));
+        }
+    }
+    let extras = report.extras().count();
+    if prompt_user(&format!("\nMove the {} extra cop(ies) to the trash? (y/n): ", extras))?.to_lowercase() != "y" {
+        return finish("Operation cancelled.");
+    }
+    let trashed = ops::trash_duplicates(config, clock, &report)?;
+    finish(&format!("✓ Moved {} video(s) to the trash.", trashed.len()))
+}
+
+fn restore(config: &Config) -> Result<()> {
+    let trash = config.trash();
+    let entries = trash.entries()?;
+    if entries.is_empty() {
+        return finish("The trash is empty.");
+    }
+    println!();
+    for (i, entry) in entries.iter().enumerate() {
+        println!("[{}] {} (deleted {})", i + 1, entry.title, entry.trashed_at.format("%Y-%m-%d %H:%M UTC"));
+    }
+    let entry = match prompt_user("\nNumber to restore: ")?.parse::<usize>() {
+        Ok(number) if number > 0 && number <= entries.len() => &entries[number - 1],
+        _ => return finish("Invalid selection."),
+    };
+    match trash.restore(entry) {
+        Ok(()) => finish(&format!("✓ Restored {}", entry.title)),
+        Err(e) => finish(&format!("Could not restore: {:#}", e)),
+    }
+}
+
+fn finish(message: &str) -> Result<()> {
+    println!("{}", message);
+    prompt_user("\nPress Enter to return to the main menu...")?;
+    Ok(())
+}
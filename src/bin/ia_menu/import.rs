@@ -0,0 +1,320 @@
+// Importing a whole Internet Archive collection onto one channel. Items go
+// through the download queue, so an interrupted import can be picked up again.
+
+use anyhow::Result;
+use reqwest::Client;
+use serde::Serialize;
+use std::io::{self, Write};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_server::clock::{Clock, SharedClock};
+use video_server::config::Config;
+use video_server::queue::DownloadQueue;
+use video_server::storage;
+
+use super::{format_size, ops, prompt_user, search_archive_page, spawn_download, Document, DownloadState};
+
+// Results fetched per page while listing a collection
+const PAGE_ROWS: usize = 100;
+
+// Listing stops here; the biggest collections hold far more than anyone wants on one channel
+const MAX_COLLECTION_ITEMS: usize = 5000;
+
+// Rough sustained download speed from archive.org, only used for estimates
+const ESTIMATED_BYTES_PER_SECOND: u64 = 2 * 1024 * 1024;
+
+// What importing a collection would download, shown before asking to go ahead
+#[derive(Serialize)]
+pub struct ImportPlan {
+    pub collection: String,
+    // Items listed in the collection
+    pub items: usize,
+    pub in_library: usize,
+    // Items to download
+    pub identifiers: Vec<String>,
+    // Total size of the items the search reported a size for; item sizes
+    // include every format the Internet Archive holds, so this is an upper bound
+    pub known_bytes: u64,
+    pub unknown_sizes: usize,
+    pub estimated_seconds: u64,
+    // Space left on the disk the downloads go to
+    pub free_bytes: u64,
+}
+
+impl ImportPlan {
+    pub fn summary(&self) -> String {
+        let mut size = format_size(self.known_bytes as usize);
+        if self.unknown_sizes > 0 {
+            size = format!("{} plus {} item(s) of unknown size", size, self.unknown_sizes);
+        }
+        let mut summary = format!(
+            "📚 {}: {} item(s), {} already in the library\n📥 {} to download: up to {}, about {} at {}/s",
+            self.collection,
+            self.items,
+            self.in_library,
+            self.identifiers.len(),
+            size,
+            format_duration(self.estimated_seconds),
+            format_size(ESTIMATED_BYTES_PER_SECOND as usize)
+        );
+        if self.known_bytes > self.free_bytes {
+            summary.push_str(&format!(
+                "\n⚠️  That may be more than the {} free on the disk; each download is checked before it starts and refused if it won't fit",
+                format_size(self.free_bytes as usize)
+            ));
+        }
+        summary
+    }
+}
+
+fn format_duration(seconds: u64) -> String {
+    match (seconds / 3600, (seconds % 3600) / 60) {
+        (0, 0) => "under a minute".to_string(),
+        (0, minutes) => format!("{}m", minutes),
+        (hours, minutes) => format!("{}h {}m", hours, minutes),
+    }
+}
+
+// Queue entries made by importing this collection
+pub fn source(collection: &str) -> String {
+    format!("collection:{}", collection)
+}
+
+// Every item in the collection, a page at a time
+async fn list_collection(client: &Client, collection: &str) -> Result<Vec<Document>> {
+    let query = format!("collection:({})", collection);
+    let mut docs = Vec::new();
+    for page in 1.. {
+        let batch = search_archive_page(client, &query, PAGE_ROWS, page).await?;
+        let last_page = batch.len() < PAGE_ROWS;
+        docs.extend(batch);
+        if last_page || docs.len() >= MAX_COLLECTION_ITEMS {
+            break;
+        }
+    }
+    docs.truncate(MAX_COLLECTION_ITEMS);
+    Ok(docs)
+}
+
+pub async fn plan(client: &Client, config: &Config, collection: &str) -> Result<ImportPlan> {
+    let docs = list_collection(client, collection).await?;
+    let library = ops::library_ids(config)?;
+    let mut plan = ImportPlan {
+        collection: collection.to_string(),
+        items: docs.len(),
+        in_library: 0,
+        identifiers: Vec::new(),
+        known_bytes: 0,
+        unknown_sizes: 0,
+        estimated_seconds: 0,
+        free_bytes: storage::free_space(config.download_dir())?,
+    };
+    for doc in docs {
+        if library.contains(&doc.identifier) {
+            plan.in_library += 1;
+            continue;
+        }
+        match doc.item_size {
+            Some(size) => plan.known_bytes += size as u64,
+            None => plan.unknown_sizes += 1,
+        }
+        plan.identifiers.push(doc.identifier);
+    }
+    plan.estimated_seconds = plan.known_bytes / ESTIMATED_BYTES_PER_SECOND;
+    Ok(plan)
+}
+
+// Add the plan's items to the download queue, pinned to `channel`
+pub fn enqueue(config: &Config, clock: &dyn Clock, plan: &ImportPlan, channel: u8) -> Result<usize> {
+    let path = &config.downloads.queue_file;
+    let mut queue = DownloadQueue::load(path)?;
+    let now = clock.now();
+    let source = source(&plan.collection);
+    let added = plan
+        .identifiers
+        .iter()
+        .filter(|identifier| queue.enqueue(identifier, Some(channel), &source, now))
+        .count();
+    queue.save(path)?;
+    Ok(added)
+}
+
+// The queue with finished items removed
+pub fn load_queue(config: &Config) -> Result<DownloadQueue> {
+    let path = &config.downloads.queue_file;
+    let mut queue = DownloadQueue::load(path)?;
+    if queue.prune(&ops::library_ids(config)?) > 0 {
+        queue.save(path)?;
+    }
+    Ok(queue)
+}
+
+// Start background downloads for everything queued that isn't already
+// downloading. Finished items drop out of the queue the next time it is loaded.
+pub async fn start_queued(client: &Client, config: &Config, clock: SharedClock, download_state: &Mutex<DownloadState>) -> Result<usize> {
+    let queue = load_queue(config)?;
+    let mut state = download_state.lock().await;
+    let mut started = 0;
+    for item in &queue.items {
+        if state.is_downloading(&item.identifier) {
+            continue;
+        }
+        let (handle, progress) = spawn_download(client, &item.identifier, item.channel, config, Arc::clone(&clock), Arc::clone(&state.slots), false);
+        state.add_download(item.identifier.clone(), handle, progress).await;
+        started += 1;
+    }
+    Ok(started)
+}
+
+pub async fn menu(client: &Client, config: &Config, clock: SharedClock, download_state: Arc<Mutex<DownloadState>>) -> Result<()> {
+    print!("\x1B[2J\x1B[1;1H");
+    io::stdout().flush()?;
+    println!("📚 Import a Collection");
+    println!("=====================================\n");
+
+    let queued = load_queue(config)?.items.len();
+    if queued > 0 {
+        println!("{} download(s) are still queued from an earlier import.", queued);
+        if prompt_user("Resume them now? (y/n): ")?.to_lowercase() == "y" {
+            let started = start_queued(client, config, clock, &download_state).await?;
+            println!("📥 Resumed {} download(s) in the background.", started);
+            prompt_user("\nPress Enter to return to the main menu...")?;
+            return Ok(());
+        }
+        println!();
+    }
+
+    let collection = prompt_user("Collection identifier, as in archive.org/details/<collection>: ")?;
+    if collection.is_empty() {
+        return Ok(());
+    }
+    println!("\n🔍 Listing {}...", collection);
+    let plan = match plan(client, config, &collection).await {
+        Ok(plan) => plan,
+        Err(e) => {
+            println!("Error listing the collection: {:#}", e);
+            prompt_user("\nPress Enter to return to the main menu...")?;
+            return Ok(());
+        }
+    };
+    println!("{}", plan.summary());
+    if plan.identifiers.is_empty() {
+        println!("\nNothing to import.");
+        prompt_user("\nPress Enter to return to the main menu...")?;
+        return Ok(());
+    }
+
+    let channel = match prompt_user("\nChannel to put them all on: ")?.parse::<u8>() {
+        Ok(channel) => channel,
+        Err(_) => {
+            println!("Invalid channel number.");
+            prompt_user("\nPress Enter to return to the main menu...")?;
+            return Ok(());
+        }
+    };
+    let confirm = prompt_user(&format!("Download {} item(s) onto channel {}? (y/n): ", plan.identifiers.len(), channel))?;
+    if confirm.to_lowercase() != "y" {
+        println!("Operation cancelled.");
+        prompt_user("\nPress Enter to return to the main menu...")?;
+        return Ok(());
+    }
+
+    enqueue(config, clock.as_ref(), &plan, channel)?;
+    let started = start_queued(client, config, clock, &download_state).await?;
+    println!("📥 Downloading {} item(s) in the background.", started);
+    println!("If the menu is closed first, run `ia_menu queue run` or come back here to finish the import.");
+    prompt_user("\nPress Enter to return to the main menu...")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+    use std::fs;
+    use video_server::clock::SimulatedClock;
+    use video_server::sidecar::{TvGuideMetadata, SCHEMA_VERSION};
+
+    fn test_config(name: &str) -> Config {
+        let dir = std::env::temp_dir().join(format!("import-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let mut config = Config { library_roots: vec![dir.clone()], ..Config::default() };
+        config.downloads.queue_file = dir.join("queue.json");
+        config
+    }
+
+    fn test_plan(identifiers: &[&str]) -> ImportPlan {
+        ImportPlan {
+            collection: "classic_tv_commercials".to_string(),
+            items: identifiers.len(),
+            in_library: 0,
+            identifiers: identifiers.iter().map(|id| id.to_string()).collect(),
+            known_bytes: 0,
+            unknown_sizes: identifiers.len(),
+            estimated_seconds: 0,
+            free_bytes: 0,
+        }
+    }
+
+    // A downloaded item as the library scan finds it
+    fn add_to_library(config: &Config, identifier: &str) {
+        let dir = &config.library_roots[0];
+        fs::write(dir.join(format!("{}.mp4", identifier)), b"").unwrap();
+        let metadata = TvGuideMetadata {
+            schema_version: SCHEMA_VERSION,
+            title: identifier.to_string(),
+            station: "WEWS".to_string(),
+            description: String::new(),
+            year: "1985".to_string(),
+            duration: "0:30".to_string(),
+            category: "Commercial".to_string(),
+            channel_number: 3,
+            start_time: None,
+            end_time: None,
+            thumbnail_url: String::new(),
+            tags: Vec::new(),
+            original_id: identifier.to_string(),
+            download_date: Utc.timestamp_opt(500_000_000, 0).unwrap(),
+            station_callsign: "WEWS".to_string(),
+            is_featured: false,
+            rating: None,
+            content_sha1: None,
+        };
+        metadata.save(&dir.join(format!("{}.json", identifier))).unwrap();
+    }
+
+    #[test]
+    fn imported_items_are_queued_onto_the_chosen_channel_once() {
+        let config = test_config("enqueue");
+        let clock = SimulatedClock::new(Utc.timestamp_opt(1_700_000_000, 0).unwrap(), 1.0);
+
+        let added = enqueue(&config, &clock, &test_plan(&["ad-1", "ad-2"]), 7).unwrap();
+        assert_eq!(added, 2);
+        let queue = DownloadQueue::load(&config.downloads.queue_file).unwrap();
+        assert!(queue.items.iter().all(|item| item.channel == Some(7)));
+        assert!(queue.items.iter().all(|item| item.source == "collection:classic_tv_commercials"));
+
+        // Importing again onto another channel only adds what isn't queued,
+        // and leaves the queued items on the channel they were given first
+        let added = enqueue(&config, &clock, &test_plan(&["ad-2", "ad-3"]), 9).unwrap();
+        assert_eq!(added, 1);
+        let queue = DownloadQueue::load(&config.downloads.queue_file).unwrap();
+        let channels: Vec<_> = queue.items.iter().map(|item| (item.identifier.as_str(), item.channel)).collect();
+        assert_eq!(channels, [("ad-1", Some(7)), ("ad-2", Some(7)), ("ad-3", Some(9))]);
+    }
+
+    #[test]
+    fn loading_the_queue_drops_items_already_in_the_library() {
+        let config = test_config("prune");
+        let clock = SimulatedClock::new(Utc.timestamp_opt(1_700_000_000, 0).unwrap(), 1.0);
+        enqueue(&config, &clock, &test_plan(&["ad-1", "ad-2"]), 7).unwrap();
+        add_to_library(&config, "ad-1");
+
+        let queue = load_queue(&config).unwrap();
+        let identifiers: Vec<_> = queue.items.iter().map(|item| item.identifier.as_str()).collect();
+        assert_eq!(identifiers, ["ad-2"]);
+        // The pruned queue is saved for next time
+        assert_eq!(DownloadQueue::load(&config.downloads.queue_file).unwrap().items.len(), 1);
+    }
+}
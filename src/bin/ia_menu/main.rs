@@ -0,0 +1,1476 @@
+use anyhow::{bail, Context, Result, anyhow};
+use url::Url;
+use futures_util::StreamExt;
+use indicatif::{ProgressBar, ProgressStyle};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::fs;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::collections::HashMap;
+use std::time::Instant;
+use chrono::{DateTime, Utc};
+use clap::Parser;
+use tokio::sync::{watch, Mutex, Semaphore};
+use tokio::task::JoinHandle;
+use video_server::channels::ChannelMap;
+use video_server::clock::{self, Clock, SharedClock};
+use video_server::config::{Config, ConfigArgs, ConfigWatcher};
+use video_server::guide::{self, Guide};
+use video_server::library;
+use video_server::local_time::TimeSettings;
+use video_server::parental::Rating;
+use video_server::schedule::{self, SlotKind};
+use video_server::sidecar::{self, TvGuideMetadata};
+use video_server::surf;
+use video_server::tuner::Tuner;
+
+mod browser;
+mod cli;
+mod delete;
+mod import;
+mod ops;
+mod packs;
+mod subscribe;
+
+// Data structures for Internet Archive API - made more flexible for varying API responses
+#[derive(Serialize, Deserialize, Debug, Clone)]
+struct Document {
+    identifier: String,
+    title: Option<String>,
+    description: Option<String>,
+    mediatype: Option<String>,
+    #[serde(deserialize_with = "deserialize_string_or_int")]
+    year: Option<String>,
+    #[serde(default)]
+    #[serde(deserialize_with = "deserialize_string_or_vec")]
+    creator: Vec<String>,
+    #[serde(default)]
+    #[serde(deserialize_with = "deserialize_string_or_vec")]
+    subject: Vec<String>,
+    item_size: Option<usize>,
+    downloads: Option<usize>,
+}
+
+// Custom deserializer to handle both String and Vec<String> cases
+fn deserialize_string_or_vec<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    struct StringOrVec;
+
+    impl<'de> serde::de::Visitor<'de> for StringOrVec {
+        type Value = Vec<String>;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("string or list of strings")
+        }
+
+        fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            Ok(vec![value.to_string()])
+        }
+
+        fn visit_string<E>(self, value: String) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            Ok(vec![value])
+        }
+
+        fn visit_seq<S>(self, visitor: S) -> Result<Self::Value, S::Error>
+        where
+            S: serde::de::SeqAccess<'de>,
+        {
+            Deserialize::deserialize(serde::de::value::SeqAccessDeserializer::new(visitor))
+        }
+
+        fn visit_none<E>(self) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            Ok(Vec::new())
+        }
+
+        fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            Deserialize::deserialize(deserializer)
+        }
+    }
+
+    deserializer.deserialize_any(StringOrVec)
+}
+
+// Custom deserializer to handle both String and integer values for the year field
+fn deserialize_string_or_int<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    struct StringOrInt;
+
+    impl<'de> serde::de::Visitor<'de> for StringOrInt {
+        type Value = Option<String>;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("string or integer")
+        }
+
+        fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            Ok(Some(value.to_string()))
+        }
+
+        fn visit_string<E>(self, value: String) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            Ok(Some(value))
+        }
+
+        fn visit_i64<E>(self, value: i64) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            Ok(Some(value.to_string()))
+        }
+
+        fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            Ok(Some(value.to_string()))
+        }
+
+        fn visit_none<E>(self) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            Ok(None)
+        }
+
+        fn visit_unit<E>(self) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            Ok(None)
+        }
+
+        fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            Deserialize::deserialize(deserializer)
+        }
+    }
+
+    deserializer.deserialize_any(StringOrInt)
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct MetadataResponse {
+    files: Vec<FileInfo>,
+    metadata: Metadata,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct FileInfo {
+    name: String,
+    format: Option<String>,
+    #[serde(default)]
+    #[serde(deserialize_with = "deserialize_size")]
+    size: Option<u64>,
+    // SHA-1 of the file as the Internet Archive lists it
+    sha1: Option<String>,
+    source: Option<String>,
+    // Optional length/duration field
+    runtime: Option<String>,
+    length: Option<String>, // Alternative field for duration
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct Metadata {
+    identifier: String,
+    title: Option<String>,
+    year: Option<String>,
+    description: Option<String>,
+    creator: Option<String>,
+    subject: Option<String>,
+    collection: Option<String>,
+    // Additional fields that might be useful
+    date: Option<String>,
+    coverage: Option<String>,
+}
+
+// Where a download has got to
+#[derive(Debug, Clone, PartialEq)]
+enum DownloadStage {
+    Queued,
+    FetchingMetadata,
+    Downloading,
+    Scheduling,
+    Finished,
+    Failed(String),
+}
+
+#[derive(Debug, Clone)]
+struct ProgressSnapshot {
+    stage: DownloadStage,
+    downloaded: u64,
+    total: u64,
+}
+
+impl ProgressSnapshot {
+    fn percent(&self) -> Option<u64> {
+        (self.downloaded * 100).checked_div(self.total).map(|percent| percent.min(100))
+    }
+}
+
+// Progress of one download, shared between the download task and whatever is showing it
+#[derive(Clone)]
+struct DownloadProgress {
+    state: Arc<std::sync::Mutex<ProgressSnapshot>>,
+    // Print messages and a progress bar; off while a full-screen view owns the terminal
+    console: bool,
+}
+
+impl DownloadProgress {
+    fn new(console: bool) -> Self {
+        Self {
+            state: Arc::new(std::sync::Mutex::new(ProgressSnapshot {
+                stage: DownloadStage::Queued,
+                downloaded: 0,
+                total: 0,
+            })),
+            console,
+        }
+    }
+
+    fn snapshot(&self) -> ProgressSnapshot {
+        self.state.lock().unwrap().clone()
+    }
+
+    fn set_stage(&self, stage: DownloadStage) {
+        self.state.lock().unwrap().stage = stage;
+    }
+
+    fn set_total(&self, total: u64) {
+        self.state.lock().unwrap().total = total;
+    }
+
+    fn set_downloaded(&self, downloaded: u64) {
+        self.state.lock().unwrap().downloaded = downloaded;
+    }
+
+    fn message(&self, text: &str) {
+        if self.console {
+            println!("{}", text);
+        }
+    }
+}
+
+// State tracking for downloads
+struct DownloadState {
+    active_downloads: HashMap<String, JoinHandle<Result<()>>>,
+    progress: HashMap<String, DownloadProgress>,
+    // One permit per download allowed to run at once
+    slots: Arc<Semaphore>,
+    // Messages from background work, shown the next time the menu is drawn
+    notices: Vec<String>,
+}
+
+impl DownloadState {
+    fn new(max_concurrent: usize) -> Self {
+        Self {
+            active_downloads: HashMap::new(),
+            progress: HashMap::new(),
+            slots: Arc::new(Semaphore::new(max_concurrent)),
+            notices: Vec::new(),
+        }
+    }
+
+    async fn add_download(&mut self, identifier: String, handle: JoinHandle<Result<()>>, progress: DownloadProgress) {
+        self.progress.insert(identifier.clone(), progress);
+        self.active_downloads.insert(identifier, handle);
+    }
+
+    fn notice(&mut self, text: String) {
+        self.notices.push(text);
+    }
+
+    fn is_downloading(&self, identifier: &str) -> bool {
+        self.active_downloads.contains_key(identifier)
+    }
+
+    async fn check_downloads(&mut self) {
+        for notice in self.notices.drain(..) {
+            println!("{}", notice);
+        }
+        
+        let mut completed = Vec::new();
+        
+        for (id, handle) in &self.active_downloads {
+            if handle.is_finished() {
+                completed.push(id.clone());
+            }
+        }
+
+        for id in completed {
+            self.progress.remove(&id);
+            if let Some(handle) = self.active_downloads.remove(&id) {
+                match handle.await {
+                    Ok(Ok(())) => println!("✓ Download completed: {}", id),
+                    Ok(Err(e)) => println!("✗ Download failed for {}: {}", id, e),
+                    Err(e) => println!("✗ Download task failed for {}: {}", id, e),
+                }
+            }
+        }
+    }
+
+    fn has_active_downloads(&self) -> bool {
+        !self.active_downloads.is_empty()
+    }
+
+    fn active_download_count(&self) -> usize {
+        self.active_downloads.len()
+    }
+}
+
+#[derive(Parser)]
+#[command(name = "ia_menu")]
+#[command(about = "Channel Surfer menu", long_about = None)]
+struct Args {
+    #[command(flatten)]
+    config: ConfigArgs,
+
+    /// Show the guide as if the current local time were this moment, e.g. "1994-10-17T19:30"
+    #[arg(long, global = true)]
+    simulate_at: Option<String>,
+
+    /// How fast simulated time runs (1 = real time, 0 = frozen, at most 10000)
+    #[arg(long, global = true, default_value = "1", requires = "simulate_at")]
+    speed: f64,
+
+    /// Answer yes to confirmation prompts
+    #[arg(long, global = true)]
+    yes: bool,
+
+    /// Print machine-readable JSON instead of text
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Run one operation and exit instead of showing the menu
+    #[command(subcommand)]
+    command: Option<cli::Command>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    let watcher = ConfigWatcher::new(args.config)?;
+    let config = watcher.config().clone();
+    
+    // Create the download directory if it doesn't exist
+    fs::create_dir_all(config.download_dir())?;
+    
+    let client = Client::new();
+    let download_state = Arc::new(Mutex::new(DownloadState::new(config.downloads.max_concurrent)));
+    let time = config.time_settings()?;
+    let clock = clock::from_options(&time, args.simulate_at.as_deref(), args.speed).map_err(|e| anyhow!(e))?;
+    
+    // Scripted use: run one operation and exit
+    if let Some(command) = args.command {
+        let output = cli::Output { yes: args.yes, json: args.json };
+        return cli::run(command, output, &client, &config, time, clock).await;
+    }
+    
+    // Rerun saved searches in the background while the menu is open
+    let (config_tx, config_rx) = watch::channel(config.clone());
+    subscribe::spawn_job(client.clone(), config_rx, Arc::clone(&clock), Arc::clone(&download_state));
+    
+    // Main application loop
+    run_simple_menu(&client, watcher, config_tx, time, clock, download_state).await
+}
+
+async fn run_simple_menu(client: &Client, mut watcher: ConfigWatcher, config_tx: watch::Sender<Config>, mut time: TimeSettings, clock: SharedClock, download_state: Arc<Mutex<DownloadState>>) -> Result<()> {
+    // When the parental PIN was last entered, until its window closes
+    let mut unlocked_until: Option<Instant> = None;
+    loop {
+        // Pick up edits to the config file and channel map between actions
+        let reload = watcher.poll();
+        let config = watcher.config().clone();
+        if let Some(Ok(_)) = &reload {
+            time = config.time_settings()?;
+            fs::create_dir_all(config.download_dir())?;
+            config_tx.send_replace(config.clone());
+        }
+        
+        // Clear the screen with a simple method
+        print!("\x1B[2J\x1B[1;1H"); // ANSI escape sequence to clear screen and move cursor to top-left
+        io::stdout().flush()?;
+        
+        match reload {
+            Some(Ok(reload)) if reload.restart_required.is_empty() => println!("✓ Configuration reloaded\n"),
+            Some(Ok(reload)) => println!("✓ Configuration reloaded; restart to apply: {}\n", reload.restart_required.join(", ")),
+            Some(Err(e)) => println!("⚠️  Configuration not reloaded: {:#}\n", e),
+            None => {}
+        }
+        
+        // Check if there are any completed downloads
+        download_state.lock().await.check_downloads().await;
+        
+        // Show active downloads count
+        let active_downloads = download_state.lock().await.active_download_count();
+        if active_downloads > 0 {
+            println!("📥 Active downloads: {}\n", active_downloads);
+        }
+        
+        println!("🎬 Channel Surfer 🎬");
+        println!("=====================================\n");
+        
+        // Simple numbered menu
+        println!("1. Start TV guide server");
+        println!("2. List local videos");
+        println!("3. Search Internet Archive videos");
+        println!("4. Delete videos");
+        println!("5. Channel surf");
+        println!("6. Saved searches");
+        println!("7. Import a collection");
+        println!("8. Lineup packs");
+        println!("9. Exit");
+        
+        if active_downloads > 0 {
+            println!("10. Show download status");
+        }
+        
+        print!("\nEnter your choice: ");
+        io::stdout().flush()?;
+        
+        let mut choice = String::new();
+        io::stdin().read_line(&mut choice)?;
+        
+        match choice.trim() {
+            "1" => start_server(&config).await?,
+            "2" => list_local_videos(&config, &time, clock.as_ref(), &mut unlocked_until).await?,
+            "3" => search_and_download(client, &config, Arc::clone(&clock), Arc::clone(&download_state)).await?,
+            "4" => delete::menu(&config, clock.as_ref())?,
+            "5" => channel_surf(&config, &time, clock.as_ref(), &mut unlocked_until).await?,
+            "6" => subscribe::menu(client, &config, Arc::clone(&clock), Arc::clone(&download_state)).await?,
+            "7" => import::menu(client, &config, Arc::clone(&clock), Arc::clone(&download_state)).await?,
+            "8" => packs::menu(client, &config, Arc::clone(&clock), Arc::clone(&download_state)).await?,
+            "9" => {
+                if download_state.lock().await.has_active_downloads() {
+                    print!("⚠️  You have active downloads. Are you sure you want to exit? (y/n): ");
+                    io::stdout().flush()?;
+                    
+                    let mut confirm = String::new();
+                    io::stdin().read_line(&mut confirm)?;
+                    if confirm.trim().to_lowercase() == "y" {
+                        println!("Exiting. Note: background downloads will be terminated.");
+                        break;
+                    }
+                } else {
+                    println!("Goodbye!");
+                    break;
+                }
+            },
+            "10" if active_downloads > 0 => {
+                println!("\nCurrent active downloads:");
+                let state = download_state.lock().await;
+                for (id, progress) in &state.progress {
+                    match progress.snapshot().percent() {
+                        Some(percent) => println!(" - {} ({}%)", id, percent),
+                        None => println!(" - {}", id),
+                    }
+                }
+                
+                println!("\nPress Enter to continue...");
+                let mut buffer = String::new();
+                io::stdin().read_line(&mut buffer)?;
+            },
+            _ => println!("Invalid choice. Please try again."),
+        }
+    }
+    
+    Ok(())
+}
+
+async fn list_local_videos(config: &Config, time: &TimeSettings, clock: &dyn Clock, unlocked_until: &mut Option<Instant>) -> Result<()> {
+    // Load the library and lay it out on the weekly grid
+    let mut library = ops::load_library(config, time, clock)?;
+    if !unlock_parental(config, unlocked_until)? {
+        library.apply_parental_locks(&config.parental);
+    }
+    let ops::Library { channel_map, scan, weekly, watched } = library;
+    let videos_dir = config.library_roots.iter().map(|root| root.display().to_string()).collect::<Vec<_>>().join(", ");
+    let callsigns = library::callsigns(&scan.items, &channel_map);
+    
+    // Full-screen guide when attached to a terminal, plain listing otherwise
+    if io::stdout().is_terminal() {
+        let unscheduled = weekly.unscheduled.len();
+        let mut guide = Guide::new(weekly, &scan.items, callsigns, *time, clock.now());
+        guide.set_watched(watched);
+        if scan.items.is_empty() && scan.problems.is_empty() {
+            guide.add_notice(format!("No videos found in {}", videos_dir), false);
+        }
+        if unscheduled > 0 {
+            guide.add_notice(format!("⚠️  {} program(s) did not fit anywhere on the schedule", unscheduled), false);
+        }
+        if scan.migrated > 0 {
+            guide.add_notice(format!("✓ Upgraded {} sidecar(s) to schema version {}", scan.migrated, sidecar::SCHEMA_VERSION), false);
+        }
+        if !scan.problems.is_empty() {
+            let names: Vec<String> = scan.problems.iter()
+                .map(|(path, _)| path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default())
+                .collect();
+            guide.add_notice(format!("⚠️  {} sidecar(s) could not be loaded: {}", names.len(), names.join(", ")), true);
+        }
+        guide::run(&mut guide, clock)?;
+        return Ok(());
+    }
+    
+    // Show TV Guide header with the local time
+    let now = clock.now();
+    let clock_text = time.format_time(&time.local(now));
+    
+    // Build TV Guide themed header
+    println!("\x1B[44m\x1B[33m"); // Blue background, yellow text (ANSI colors)
+    println!("TV GUIDE{}{:>10}", " ".repeat(70), clock_text);
+    println!("\x1B[0m"); // Reset colors
+    
+    if scan.items.is_empty() && scan.problems.is_empty() {
+        println!("\nNo videos found in {}\n", videos_dir);
+    } else {
+        let now_second_of_week = time.second_of_week(now);
+        
+        for (channel, slots) in &weekly.channels {
+            // Draw channel box with channel number and callsign
+            let callsign = callsigns.get(channel).map(String::as_str).unwrap_or("");
+            
+            // Draw the channel info in purple background (like the screenshot)
+            println!("\x1B[45m\x1B[37m{: ^15}\x1B[0m", format!("CH {}", channel)); // Channel number
+            println!("\x1B[45m\x1B[37m{: ^15}\x1B[0m", callsign); // Station callsign
+            
+            // Start from whatever is airing now and wrap around the end of the week
+            let current = slots.iter()
+                .position(|slot| now_second_of_week < slot.end_second())
+                .unwrap_or(0);
+            
+            // Display up to 3 programs for this channel
+            for (i, slot) in slots.iter().cycle().skip(current).take(slots.len().min(3)).enumerate() {
+                // Program start time & title (blue background)
+                // Programs already in the watch history get a check mark
+                let marker = if slot.kind != SlotKind::Program { " " } else if watched.contains(&slot.original_id) { "✓" } else { "•" };
+                println!("\x1B[44m\x1B[33m{: ^10}\x1B[0m \x1B[44m\x1B[33m{} {: <28}\x1B[0m", 
+                         time.format_time(&time.airing_start(slot.start_second, slot.length_seconds, now)), // Left box with time
+                         marker,
+                         slot.title.chars().take(26).collect::<String>()); // Right box with title
+                         
+                // Only show details for the first 2 entries to save space
+                if i < 2 {
+                    println!("\x1B[44m\x1B[33m{: ^10}\x1B[0m", format!("{}m", slot.length_seconds / 60));
+                }
+            }
+            
+            // Add a blank line between channels
+            println!();
+        }
+        
+        if !weekly.unscheduled.is_empty() {
+            println!("⚠️  {} program(s) did not fit anywhere on the schedule", weekly.unscheduled.len());
+        }
+        
+        if scan.migrated > 0 {
+            println!("✓ Upgraded {} sidecar(s) to schema version {}", scan.migrated, sidecar::SCHEMA_VERSION);
+        }
+        
+        // Report sidecars that could not be used instead of silently dropping them
+        if !scan.problems.is_empty() {
+            println!("\x1B[31m⚠️  {} sidecar(s) could not be loaded:\x1B[0m", scan.problems.len());
+            for (path, error) in &scan.problems {
+                let name = path.file_name().map(|n| n.to_string_lossy()).unwrap_or_default();
+                println!("\x1B[31m   {}: {}\x1B[0m", name, error);
+            }
+        }
+    }
+    
+    println!("\nPress Enter to return to the main menu...");
+    let mut buffer = String::new();
+    io::stdin().read_line(&mut buffer)?;
+    
+    Ok(())
+}
+
+// Flip through the channels in the player, each showing whatever the schedule says is on right now
+async fn channel_surf(config: &Config, time: &TimeSettings, clock: &dyn Clock, unlocked_until: &mut Option<Instant>) -> Result<()> {
+    let mut library = ops::load_library(config, time, clock)?;
+    if !unlock_parental(config, unlocked_until)? {
+        library.apply_parental_locks(&config.parental);
+    }
+    let ops::Library { channel_map, scan, weekly, .. } = library;
+    
+    if weekly.channels.is_empty() {
+        println!("\nNothing is on yet. Download some videos first.");
+        prompt_user("\nPress Enter to return to the main menu...")?;
+        return Ok(());
+    }
+    
+    let tuner = Tuner::new(weekly, &scan.items, &channel_map, *time);
+    if let Err(e) = surf::run(&tuner, clock, &config.player, None) {
+        println!("\n❌ Could not start channel surfing: {}", e);
+        if e.kind() == io::ErrorKind::NotFound {
+            println!("   Channel surfing plays through {}. Please install mpv or set \"player\" and try again.", config.player);
+        }
+        prompt_user("\nPress Enter to return to the main menu...")?;
+    }
+    
+    Ok(())
+}
+
+// Offer to unlock parental controls before showing the lineup. Returns true
+// when nothing is locked or the PIN was entered within the unlock window.
+fn unlock_parental(config: &Config, unlocked_until: &mut Option<Instant>) -> Result<bool> {
+    let parental = &config.parental;
+    if !parental.enabled() || unlocked_until.is_some_and(|until| Instant::now() < until) {
+        return Ok(true);
+    }
+    let pin = prompt_user("🔒 Some programming is locked. Enter the PIN to unlock it, or press Enter to continue: ")?;
+    if pin.is_empty() {
+        return Ok(false);
+    }
+    if !parental.check_pin(&pin) {
+        println!("❌ Wrong PIN; locked programming stays hidden.");
+        prompt_user("\nPress Enter to continue...")?;
+        return Ok(false);
+    }
+    *unlocked_until = Some(Instant::now() + parental.unlock_length());
+    Ok(true)
+}
+
+async fn search_and_download(client: &Client, config: &Config, clock: SharedClock, download_state: Arc<Mutex<DownloadState>>) -> Result<()> {
+    // Full-screen browser when attached to a terminal
+    if io::stdout().is_terminal() {
+        return browser::run(client, config, clock, download_state).await;
+    }
+    
+    // Simple clear screen
+    print!("\x1B[2J\x1B[1;1H");
+    io::stdout().flush()?;
+    
+    let query = prompt_user("Enter search keywords: ")?;
+    let limit = prompt_user("Number of results to display (default 10): ")?;
+    let limit = limit.trim().parse::<usize>().unwrap_or(10);
+    
+    println!("\n🔍 Searching for: {}", query);
+    
+    let docs = match search_archive(client, &query, limit).await {
+        Ok(docs) => docs,
+        Err(e) => {
+            println!("Error searching the Internet Archive: {:#}", e);
+            prompt_user("\nPress Enter to return to the main menu...")?;
+            return Ok(());
+        }
+    };
+    
+    if docs.is_empty() {
+        println!("No results found for query: {}", query);
+        prompt_user("\nPress Enter to return to the main menu...")?;
+        return Ok(());
+    }
+    
+    println!("\n📋 Found {} results:", docs.len());
+
+    for (i, doc) in docs.iter().enumerate() {
+        let title = doc.title.as_deref().unwrap_or("(No Title)");
+        let year = doc.year.as_deref().unwrap_or("Unknown");
+        let creators = if !doc.creator.is_empty() {
+            if doc.creator.len() > 1 {
+                format!("{} et al", doc.creator[0])
+            } else {
+                doc.creator[0].clone()
+            }
+        } else {
+            "Unknown".to_string()
+        };
+        
+        // Format downloads with commas for readability
+        let downloads = doc.downloads.unwrap_or(0);
+        
+        // Get the estimated file size if available
+        let size_str = match doc.item_size {
+            Some(size) => format_size(size),
+            None => "size unknown".to_string()
+        };
+        
+        // Compact 2-line listing with all key info
+        println!("[{}] {} ({}) {}", i + 1, title, year, size_str);
+        println!("    Creator: {}  ID: {}  Downloads: {}", creators, doc.identifier, downloads);
+        println!("{}", "-".repeat(60));
+    }
+
+    println!("\nEnter the number of the video to download (or press Enter to cancel): ");
+    let mut selection = String::new();
+    io::stdin().read_line(&mut selection)?;
+    
+    if selection.is_empty() {
+        return Ok(());
+    }
+    
+    let selected = match selection.trim().parse::<usize>() {
+        Ok(num) if num > 0 && num <= docs.len() => num - 1,
+        _ => {
+            println!("Invalid selection.");
+            prompt_user("\nPress Enter to return to the main menu...")?;
+            return Ok(());
+        }
+    };
+
+    // Get selected document and download it
+    let selected_doc = &docs[selected];
+    let identifier = selected_doc.identifier.clone();
+    
+    
+    // Check the real size of the file that would be downloaded, not the item's
+    // total, against the disk and the storage quota
+    println!("\n📏 Checking the size of {}...", identifier);
+    let file_size = match fetch_metadata(client, &identifier).await {
+        Ok(metadata) => match pick_mp4(&metadata.files) {
+            Some(file) => file.size,
+            None => {
+                println!("No MP4 files found for {}.", identifier);
+                prompt_user("\nPress Enter to return to the main menu...")?;
+                return Ok(());
+            }
+        },
+        Err(e) => {
+            println!("Error fetching the item's files: {:#}", e);
+            prompt_user("\nPress Enter to return to the main menu...")?;
+            return Ok(());
+        }
+    };
+    let preflight = ops::preflight(config, &config.time_settings()?, clock.as_ref(), file_size)?;
+    match file_size {
+        Some(size) => println!("Download size: {} ({} free)", format_size(size as usize), format_size(preflight.free_bytes as usize)),
+        None => println!("Download size: unknown, so it can't be checked against the free space until it starts"),
+    }
+    if let Some(problem) = preflight.problem() {
+        println!("⚠️  {}", problem);
+        prompt_user("\nPress Enter to return to the main menu...")?;
+        return Ok(());
+    }
+    let empty_trash = preflight.plan.as_ref().is_some_and(|plan| plan.empty_trash);
+    if empty_trash || !preflight.evictions().is_empty() {
+        println!("To stay under the storage quota, this removes:");
+        if empty_trash {
+            println!(" - everything in the trash");
+        }
+        for program in preflight.evictions() {
+            println!(" - {} ({})", program.title, format_size(program.bytes as usize));
+        }
+        if prompt_user("Go ahead? (y/n): ")?.to_lowercase() != "y" {
+            println!("Operation cancelled.");
+            prompt_user("\nPress Enter to return to the main menu...")?;
+            return Ok(());
+        }
+    }
+    
+    println!("Starting download for: {}", selected_doc.title.as_deref().unwrap_or(&identifier));
+    
+    // Start download in background and register it
+    let mut state = download_state.lock().await;
+    let (handle, progress) = spawn_download(client, &identifier, None, config, clock, Arc::clone(&state.slots), true);
+    state.add_download(identifier, handle, progress).await;
+    drop(state);
+    
+    prompt_user("\nDownload started in background. Press Enter to return to the main menu...")?;
+    
+    // No terminal mode handling needed
+    
+    Ok(())
+}
+
+// Search the Internet Archive for movies matching the query
+async fn search_archive(client: &Client, query: &str, limit: usize) -> Result<Vec<Document>> {
+    search_archive_page(client, query, limit, 1).await
+}
+
+// One page of results, counting from 1, for walking through large result sets
+async fn search_archive_page(client: &Client, query: &str, rows: usize, page: usize) -> Result<Vec<Document>> {
+    let mut url = Url::parse_with_params("https://archive.org/advancedsearch.php", &[
+        ("q", format!("mediatype:movies {}", query)),
+        ("output", "json".to_string()),
+        ("rows", rows.to_string()),
+        ("page", page.to_string()),
+    ])?;
+    for field in ["identifier", "title", "description", "year", "creator", "subject", "item_size", "downloads"] {
+        url.query_pairs_mut().append_pair("fl[]", field);
+    }
+    
+    let response_text = client.get(url).send().await
+        .context("Error making search request")?
+        .text().await
+        .context("Error reading response")?;
+    
+    try_extract_identifiers(&response_text, rows).with_context(|| {
+        format!("Unexpected search response: {}", response_text.chars().take(200).collect::<String>())
+    })
+}
+
+// Fetch an item's metadata and file list
+async fn fetch_metadata(client: &Client, identifier: &str) -> Result<MetadataResponse> {
+    let metadata_url = format!("https://archive.org/metadata/{}", identifier);
+    
+    let response = client
+        .get(&metadata_url)
+        .send()
+        .await
+        .context("Failed to fetch metadata")?;
+        
+    // First get the raw JSON to diagnose issues if needed
+    let raw_metadata = response.text().await?;
+    
+    serde_json::from_str(&raw_metadata).with_context(|| {
+        format!("Failed to parse metadata, response began: {}", raw_metadata.chars().take(200).collect::<String>())
+    })
+}
+
+// The file a download would fetch: the largest MP4 that isn't a text derivative
+fn pick_mp4(files: &[FileInfo]) -> Option<&FileInfo> {
+    files.iter()
+        .filter(|file| {
+            let name = file.name.to_lowercase();
+            name.ends_with(".mp4") && !name.contains("_text_")
+        })
+        .max_by_key(|file| file.size.unwrap_or(0u64))
+}
+
+// Start a download in the background, reporting progress as it goes. It
+// stays queued until one of `slots` is free.
+fn spawn_download(client: &Client, identifier: &str, channel: Option<u8>, config: &Config, clock: SharedClock, slots: Arc<Semaphore>, console: bool) -> (JoinHandle<Result<()>>, DownloadProgress) {
+    let progress = DownloadProgress::new(console);
+    
+    // Clone what we need for the async block
+    let client = client.clone();
+    let identifier = identifier.to_string();
+    let config = config.clone();
+    let task_progress = progress.clone();
+    
+    let handle = tokio::spawn(async move {
+        let _slot = slots.acquire_owned().await?;
+        let result = match config.time_settings() {
+            Ok(time) => download_video(&client, &identifier, channel, &config, time, clock, &task_progress).await,
+            Err(e) => Err(e),
+        };
+        match &result {
+            Ok(_) => task_progress.set_stage(DownloadStage::Finished),
+            Err(e) => task_progress.set_stage(DownloadStage::Failed(format!("{:#}", e))),
+        }
+        result.map(|_| ())
+    });
+    
+    (handle, progress)
+}
+
+// Download an item and its sidecar, returning the saved video's file name.
+// `channel` files it under that channel instead of the one its category suggests.
+async fn download_video(client: &Client, identifier: &str, channel: Option<u8>, config: &Config, time: TimeSettings, clock: SharedClock, progress: &DownloadProgress) -> Result<String> {
+    progress.set_stage(DownloadStage::FetchingMetadata);
+    progress.message(&format!("📥 Fetching detailed metadata for {}...", identifier));
+    
+    // Fetch metadata for the video
+    let metadata_response = fetch_metadata(client, identifier).await?;
+    
+    // Use the largest MP4 file
+    let mp4_file = pick_mp4(&metadata_response.files)
+        .ok_or_else(|| anyhow!("No MP4 files found for {}", identifier))?;
+
+    // Don't download what the library already has, under any name
+    if let Some(existing) = ops::existing_copy(config, identifier, mp4_file.sha1.as_deref())? {
+        bail!("{} is already in the library as {}", identifier, ops::file_name(&existing.video_path));
+    }
+        
+    // Construct download URL
+    let download_url = format!("https://archive.org/download/{}/{}", identifier, mp4_file.name);
+    
+    // Get metadata fields for filename
+    let meta = &metadata_response.metadata;
+    let title = meta.title.as_deref().unwrap_or(identifier);
+    let year = meta.year.as_deref().unwrap_or("");
+    
+    // Clean the title for use in filename
+    let clean_title = title
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            _ => c
+        })
+        .collect::<String>()
+        .trim()
+        .to_string();
+        
+    // Create filename with metadata - keep it shorter
+    let base = if year.is_empty() {
+        clean_title
+    } else {
+        format!("{}.{}", clean_title, year)
+    };
+    
+    // Extract and structure the TV Guide metadata
+    let mut tv_metadata = extract_tv_guide_metadata(&metadata_response, identifier, clock.now())?;
+    if let Some(channel) = channel {
+        tv_metadata.channel_number = channel;
+        if let Some(callsign) = ChannelMap::load(&config.channel_map)?.channel(channel).and_then(|c| c.callsign.clone()) {
+            tv_metadata.station_callsign = callsign;
+        }
+    }
+    
+    // Download the file
+    let response = client
+        .get(&download_url)
+        .send()
+        .await
+        .context("Failed to start download")?;
+        
+    // Size from the file list, or from the download server when the list has none
+    let expected_size = mp4_file.size.or(response.content_length());
+    
+    // Make sure it fits on the disk and under the storage quota before anything
+    // is written, holding the room until the download is done
+    let (evicted, reservation) = ops::make_room(config, &time, clock.as_ref(), expected_size)?;
+    for program in evicted {
+        progress.message(&format!("🗑️  Removed {} to stay under the storage quota", program.title));
+    }
+    
+    let total_size = expected_size.unwrap_or(0);
+    progress.set_total(total_size);
+    progress.set_stage(DownloadStage::Downloading);
+        
+    // Create a progress bar for the download with fixed-width
+    let pb = if progress.console { ProgressBar::new(total_size) } else { ProgressBar::hidden() };
+    pb.set_style(ProgressStyle::default_bar()
+        .template("[{elapsed_precise}] [{wide_bar:.cyan/blue}] {bytes}/{total_bytes} ({eta})")?  // Using wide_bar instead of bar
+        .progress_chars("█▓▒░-"));
+    
+    // Download under a temporary name so an interrupted download never looks
+    // like a finished one in the library; the guard removes it if we bail out.
+    // The identifier is added to the name only when another item already has it.
+    let (filename, partial_path, mut file) = ops::claim_download_name(config.download_dir(), &base, identifier)?;
+    let partial = PartialDownload::new(partial_path);
+    let filepath = config.download_dir().join(&filename);
+    let mut downloaded: u64 = 0;
+    let mut checked_at: u64 = 0;
+    let mut hasher = Sha1::new();
+    let mut stream = response.bytes_stream();
+    
+    // Process the stream of bytes
+    while let Some(item) = stream.next().await {
+        let chunk = item.context("Error while downloading file")?;
+        file.write_all(&chunk)
+            .context("Error while writing to file")?;
+        hasher.update(&chunk);
+            
+        let new = downloaded + (chunk.len() as u64);
+        downloaded = new;
+        pb.set_position(new);
+        progress.set_downloaded(new);
+        reservation.set_written(new);
+        // The size may be unknown or wrong, and other programs write to the disk too
+        if new - checked_at >= ops::FREE_SPACE_CHECK_BYTES {
+            checked_at = new;
+            ops::ensure_reserve(config)?;
+        }
+    }
+    
+    drop(file);
+    partial.finish(&filepath)?;
+    
+    // Only a finished video gets a sidecar, with the hash so a later copy of
+    // the same file is recognised
+    tv_metadata.content_sha1 = Some(format!("{:x}", hasher.finalize()));
+    if let Err(e) = tv_metadata.save(&sidecar::sidecar_path(&filepath)) {
+        let _ = fs::remove_file(&filepath);
+        return Err(e).context("Error while writing the sidecar");
+    }
+    drop(reservation);
+    
+    // Finish the progress bar
+    pb.finish_with_message(format!("Downloaded {}", &filename));
+    
+    // Re-pack the weekly grid so the new program gets a slot
+    progress.set_stage(DownloadStage::Scheduling);
+    ops::load_library(config, &time, clock.as_ref())?;
+    
+    Ok(filename)
+}
+
+// A download's .part file, removed when the download stops before finishing
+struct PartialDownload {
+    path: PathBuf,
+    finished: bool,
+}
+
+impl PartialDownload {
+    fn new(path: PathBuf) -> Self {
+        Self { path, finished: false }
+    }
+
+    // Give the finished file its real name
+    fn finish(mut self, path: &Path) -> Result<()> {
+        fs::rename(&self.path, path).context("Error while saving the download")?;
+        self.finished = true;
+        Ok(())
+    }
+}
+
+impl Drop for PartialDownload {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = fs::remove_file(&self.path);
+        }
+    }
+}
+
+// Helper function to format file sizes in human-readable format
+fn format_size(size_bytes: usize) -> String {
+    if size_bytes < 1024 {
+        return format!("{} B", size_bytes);
+    }
+    
+    let kb = size_bytes as f64 / 1024.0;
+    if kb < 1024.0 {
+        return format!("{:.1} KB", kb);
+    }
+    
+    let mb = kb / 1024.0;
+    if mb < 1024.0 {
+        return format!("{:.1} MB", mb);
+    }
+    
+    let gb = mb / 1024.0;    
+    format!("{:.2} GB", gb)
+}
+
+// Custom deserializer for file size (handles string and numeric values)
+fn deserialize_size<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    struct SizeVisitor;
+
+    impl<'de> serde::de::Visitor<'de> for SizeVisitor {
+        type Value = Option<u64>;
+
+        fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+            formatter.write_str("string or integer size")
+        }
+
+        fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            // Try to parse string as a number
+            match value.parse::<u64>() {
+                Ok(n) => Ok(Some(n)),
+                Err(_) => Ok(None),
+            }
+        }
+
+        fn visit_string<E>(self, value: String) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            self.visit_str(&value)
+        }
+
+        fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            Ok(Some(value))
+        }
+
+        fn visit_i64<E>(self, value: i64) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            Ok(u64::try_from(value).ok())
+        }
+
+        fn visit_none<E>(self) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            Ok(None)
+        }
+
+        fn visit_unit<E>(self) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            Ok(None)
+        }
+
+        fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+        where
+            D: serde::Deserializer<'de>,
+        {
+            deserializer.deserialize_any(SizeVisitor)
+        }
+    }
+
+    deserializer.deserialize_any(SizeVisitor)
+}
+
+// Build TV Guide metadata from the Internet Archive item metadata
+fn extract_tv_guide_metadata(response: &MetadataResponse, identifier: &str, now: DateTime<Utc>) -> Result<TvGuideMetadata> {
+    let meta = &response.metadata;
+    
+    // Basic program information
+    let title = meta.title.clone().unwrap_or_else(|| identifier.to_string());
+    let description = meta.description.clone().unwrap_or_default();
+    let year = meta.year.clone()
+        .or_else(|| meta.date.as_ref().map(|d| d.chars().take(4).collect()))
+        .unwrap_or_else(|| "Unknown".to_string());
+    
+    // Use the creator as the originating station, falling back to the collection
+    let station = meta.creator.clone()
+        .or_else(|| meta.collection.clone())
+        .unwrap_or_else(|| "Internet Archive".to_string());
+    
+    // Find an appropriate thumbnail
+    let thumbnail_url = format!("https://archive.org/services/img/{}", identifier);
+    
+    // Extract duration if possible
+    let duration = find_video_duration(response);
+    
+    // Categorize content based on title and description
+    let category = categorize_content(&title, &description);
+    
+    // Extract tags for better searching
+    let mut tags = Vec::new();
+    if let Some(subject) = &meta.subject {
+        tags = subject.split(",")
+            .map(|s| s.trim().to_string())
+            .collect();
+    }
+    
+    // Determine channel number and station callsign based on content category
+    let (channel_number, station_callsign) = assign_channel_and_callsign(&category, &station, &tags);
+    
+    // Featured status - either special content or longer format programs
+    let duration_mins = schedule::parse_duration_minutes(&duration).unwrap_or(30);
+    let is_featured = duration_mins > 60 || 
+                      tags.iter().any(|tag| tag.to_lowercase().contains("special"));
+    
+    // Current download date
+    let download_date = now;
+    
+    // Guess a rating for parental controls; it can be corrected in the sidecar
+    let rating = Rating::from_subjects(&tags, &category);
+    
+    Ok(TvGuideMetadata {
+        schema_version: sidecar::SCHEMA_VERSION,
+        title,
+        station,
+        description,
+        year,
+        duration,
+        category,
+        channel_number,
+        // Filled in when the library is scheduled
+        start_time: None,
+        end_time: None,
+        thumbnail_url,
+        tags,
+        original_id: identifier.to_string(),
+        download_date,
+        station_callsign,
+        is_featured,
+        rating,
+        // Recorded once the video has downloaded
+        content_sha1: None,
+    })
+}
+
+// Assign a TV channel number and callsign based on content category
+fn assign_channel_and_callsign(category: &str, creator: &str, tags: &[String]) -> (u8, String) {
+    let category_lower = category.to_lowercase();
+    let creator_lower = creator.to_lowercase();
+    
+    // Channel assignment based on content category
+    if category_lower.contains("news") || creator_lower.contains("news") {
+        // News channels
+        if creator_lower.contains("cbs") { return (19, "WCIO".to_string()); }
+        if creator_lower.contains("abc") { return (5, "WEWS".to_string()); }
+        if creator_lower.contains("nbc") { return (3, "WKYC".to_string()); }
+        if creator_lower.contains("fox") { return (8, "WJW".to_string()); }
+        return (5, "WEWS".to_string()); // Default news channel
+    } 
+    else if category_lower.contains("movie") || category_lower.contains("film") || 
+             tags.iter().any(|t| t.to_lowercase().contains("movie")) {
+        return (4, "WUAB".to_string()); // Movie channel
+    }
+    else if category_lower.contains("documentary") || 
+             creator_lower.contains("pbs") || 
+             creator_lower.contains("discovery") {
+        return (25, "WVIZ".to_string()); // Documentary/PBS channel
+    }
+    else if category_lower.contains("comedy") || 
+             category_lower.contains("sitcom") ||
+             tags.iter().any(|t| t.to_lowercase().contains("comedy")) {
+        return (8, "WJW".to_string()); // Comedy channel
+    }
+    else if category_lower.contains("drama") ||
+             category_lower.contains("series") {
+        return (3, "WKYC".to_string()); // Drama channel
+    }
+    else if category_lower.contains("kids") || 
+             category_lower.contains("animation") || 
+             category_lower.contains("children") {
+        return (43, "WUAB".to_string()); // Kids channel
+    }
+    else if category_lower.contains("sport") {
+        return (35, "ESPN".to_string()); // Sports channel
+    }
+    
+    // For unknown categories, assign a channel based on the hash of the creator name
+    let hash_value = creator.bytes().fold(0u8, |acc, b| acc.wrapping_add(b));
+    let channel = (hash_value % 40) + 2; // Channels 2-42
+    
+    // Generate a random callsign for unknown channels
+    let callsign = format!("W{}{}{}", 
+        (b'A' + (hash_value % 26)) as char,
+        (b'A' + ((hash_value / 2) % 26)) as char,
+        (b'A' + ((hash_value / 3) % 26)) as char);
+    
+    (channel, callsign)
+}
+
+// Helper to find video duration from various file metadata fields
+fn find_video_duration(response: &MetadataResponse) -> String {
+    for file in &response.files {
+        if file.name.ends_with(".mp4") {
+            // Try various fields that might contain duration, skipping any
+            // that the sidecar would be rejected for
+            for duration in [&file.runtime, &file.length].into_iter().flatten() {
+                if schedule::parse_duration_seconds(duration).is_some() {
+                    return duration.clone();
+                }
+            }
+        }
+    }
+    // Default value
+    "00:30:00".to_string()
+}
+
+// Categorize content based on title and description
+fn categorize_content(title: &str, description: &str) -> String {
+    let combined = format!("{} {}", title, description).to_lowercase();
+    // Whole words, so short keywords like "ad" don't match "made" or "road"
+    let words: Vec<&str> = combined.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).collect();
+    let has_word = |word: &str| words.contains(&word);
+    // Consecutive whole words, so "sign on" matches "Sign-On" but not "Design on a budget"
+    let has_phrase = |phrase: &[&str]| words.windows(phrase.len()).any(|window| window == phrase);
+    
+    // Check for interstitials first so a "news bumper" isn't scheduled as news
+    if has_phrase(&["sign", "off"]) || has_word("signoff") {
+        "Sign Off".to_string()
+    } else if has_phrase(&["sign", "on"]) || has_word("signon") {
+        "Sign On".to_string()
+    } else if combined.contains("station id") || combined.contains("station identification") || has_word("ident") {
+        "Station ID".to_string()
+    } else if combined.contains("bumper") {
+        "Bumper".to_string()
+    } else if combined.contains("commercial") || has_word("ad") || has_word("ads") || combined.contains("advertisement") {
+        "Commercial".to_string()
+    } else if combined.contains("news") || combined.contains("report") || combined.contains("update") {
+        "News".to_string()
+    } else if combined.contains("sport") || combined.contains("game") || 
+              combined.contains("match") || combined.contains("championship") {
+        "Sports".to_string()
+    } else if combined.contains("cartoon") || combined.contains("animation") {
+        "Cartoon".to_string()
+    } else if combined.contains("documentary") || combined.contains("educational") {
+        "Documentary".to_string()
+    } else if combined.contains("movie") || combined.contains("film") {
+        "Movie".to_string()
+    } else if combined.contains("show") || combined.contains("series") || combined.contains("episode") {
+        "TV Show".to_string()
+    } else {
+        "Entertainment".to_string() // Default category
+    }
+}
+
+
+
+// Function to manually extract identifiers and titles from response JSON
+fn try_extract_identifiers(json_text: &str, max: usize) -> Result<Vec<Document>> {
+    let mut docs = Vec::new();
+    
+    // Very simple JSON extraction to be more robust
+    let v: serde_json::Value = serde_json::from_str(json_text)?;
+    
+    // Try different paths to find the docs array
+    let doc_array = match &v["response"]["docs"] {
+        serde_json::Value::Array(arr) => Some(arr),
+        _ => None,
+    };
+    
+    if let Some(array) = doc_array {
+        for (i, item) in array.iter().enumerate() {
+            if i >= max {
+                break;
+            }
+            
+            // Extract basic info
+            let identifier = item["identifier"].as_str().unwrap_or("unknown").to_string();
+            let title = item["title"].as_str().unwrap_or("(No Title)").to_string();
+            
+            // Only add items with valid identifiers
+            if identifier != "unknown" {
+                // Fetch file size - try different possible fields
+                let item_size = item["size"].as_u64()
+                    .or_else(|| item["item_size"].as_u64()) // Try alternate field name
+                    .or_else(|| { // Try to parse from string size field
+                        item["size"].as_str()
+                            .and_then(|s| s.parse::<u64>().ok())
+                            .or_else(|| item["item_size"].as_str().and_then(|s| s.parse::<u64>().ok()))
+                    })
+                    .map(|s| s as usize);
+                
+                let doc = Document {
+                    identifier,
+                    title: Some(title),
+                    description: extract_string_array(item, "description").first().cloned(),
+                    mediatype: Some("movies".to_string()),
+                    year: item["year"].as_str().map(|s| s.to_string())
+                        .or_else(|| item["year"].as_i64().map(|i| i.to_string())),
+                    creator: extract_string_array(item, "creator"),
+                    subject: extract_string_array(item, "subject"),
+                    item_size,
+                    downloads: item["downloads"].as_u64().map(|d| d as usize),
+                };
+                
+                docs.push(doc);
+            }
+        }
+    }
+
+    
+    Ok(docs)
+}
+
+// Helper to extract string arrays from JSON
+fn extract_string_array(item: &serde_json::Value, key: &str) -> Vec<String> {
+    match &item[key] {
+        serde_json::Value::Array(arr) => {
+            arr.iter()
+                .filter_map(|v| v.as_str().map(|s| s.to_string()))
+                .collect()
+        },
+        serde_json::Value::String(s) => vec![s.clone()],
+        _ => Vec::new(),
+    }
+}
+
+// Start the existing video server using npm scripts
+async fn start_server(config: &Config) -> Result<()> {
+    print!("\x1B[2J\x1B[1;1H");
+    io::stdout().flush()?;
+    
+    println!("🖥️  Starting Channel Surfer Server...");
+    println!("===============================");
+    
+    // Path to the project root
+    let project_dir = std::env::current_dir()?;
+    
+    // Check if package.json exists
+    let package_json = project_dir.join("package.json");
+    if !package_json.exists() {
+        println!("⚠️  Could not find package.json at {}", package_json.display());
+        println!("Please start your server manually from a terminal.");
+        prompt_user("\nPress Enter to return to the main menu...")?;
+        return Ok(());
+    }
+    
+    // Check if npm is installed
+    let npm_check = std::process::Command::new("npm")
+        .arg("--version")
+        .output();
+    
+    if npm_check.is_err() {
+        println!("⚠️  npm not found. Please install Node.js and npm to run the server.");
+        prompt_user("\nPress Enter to return to the main menu...")?;
+        return Ok(());
+    }
+    
+    println!("Starting your Vite development server...");
+    
+    // Start the development server with npm run dev
+    let server_process = std::process::Command::new("npm")
+        .arg("run")
+        .arg("dev")
+        .arg("--")
+        .arg("--port")
+        .arg(config.dev_server_port.to_string())
+        .current_dir(&project_dir)
+        .spawn()?;
+    
+    println!("\n🎬 Server started successfully with process ID: {}", server_process.id());
+    println!("\n🌐 Access Channel Surfer at: http://localhost:{}", config.dev_server_port);
+    println!("\n⚠️  Note: Server will continue running in the background.");
+    println!("   You can stop it by pressing Ctrl+C in its terminal or");
+    println!("   by ending process ID {} when you're done.", server_process.id());
+    
+    prompt_user("\nPress Enter to return to the main menu...")?;
+    
+    Ok(())
+}
+
+
+
+fn prompt_user(prompt: &str) -> Result<String> {
+    print!("{}", prompt);
+    io::stdout().flush()?;
+    
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    
+    Ok(input.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interstitials_are_categorized_before_programs() {
+        for (title, description, category) in [
+            ("1978 News Commercial", "", "Commercial"),
+            ("Board game ad", "", "Commercial"),
+            ("Toy ads from 1985", "Saturday morning", "Commercial"),
+            ("Cereal advertisement", "cartoon mascot", "Commercial"),
+            ("Eyewitness News bumper", "", "Bumper"),
+            ("WXYZ station ID", "news at 11", "Station ID"),
+            ("Sign-off", "national anthem", "Sign Off"),
+            ("Morning sign on", "", "Sign On"),
+            ("WEWS signon", "", "Sign On"),
+            ("Design on a budget", "", "Entertainment"),
+            ("Design on a budget", "home makeover show", "TV Show"),
+            ("Cosign off the record", "", "Entertainment"),
+            ("Evening News", "report from Washington", "News"),
+            ("Championship game", "", "Sports"),
+            ("Made for TV", "road trip", "Entertainment"),
+            ("Saturday cartoon", "", "Cartoon"),
+        ] {
+            assert_eq!(categorize_content(title, description), category, "{} / {}", title, description);
+        }
+    }
+}
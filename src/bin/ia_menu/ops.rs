@@ -0,0 +1,522 @@
+// Library operations shared by the interactive menu and the scripting subcommands
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, FixedOffset};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use video_server::channels::ChannelMap;
+use video_server::clock::Clock;
+use video_server::config::Config;
+use video_server::dedupe::{self, DuplicateFile, DuplicateGroup, Fingerprint};
+use video_server::history::WatchHistory;
+use video_server::library::{self, LibraryItem, LibraryScan};
+use video_server::local_time::TimeSettings;
+use video_server::parental::ParentalConfig;
+use video_server::schedule::{self, WeeklySchedule};
+use video_server::sidecar::{self, TvGuideMetadata};
+use video_server::storage::{self, EvictionPlan, StoredProgram};
+use video_server::trash::TrashEntry;
+use video_server::tuner::Tuner;
+
+use super::format_size;
+
+// The scanned library laid out on the weekly grid
+pub struct Library {
+    pub channel_map: ChannelMap,
+    pub scan: LibraryScan,
+    pub weekly: WeeklySchedule,
+    // original_ids of programs in the watch history
+    pub watched: HashSet<String>,
+}
+
+// Scan and schedule the library, recording each program's next airing in its sidecar
+pub fn load_library(config: &Config, time: &TimeSettings, clock: &dyn Clock) -> Result<Library> {
+    let channel_map = ChannelMap::load(&config.channel_map)?;
+    let history = WatchHistory::load(&config.history.file)?;
+    let schedule_config = config.history.schedule_config(&history, clock.now());
+    let (scan, weekly) = library::schedule_library(&config.library_roots, &schedule_config, &channel_map, time, clock)?;
+    Ok(Library { channel_map, scan, weekly, watched: history.watched() })
+}
+
+#[derive(Serialize)]
+pub struct ProgramEntry {
+    pub file: String,
+    pub watched: bool,
+    #[serde(flatten)]
+    pub metadata: TvGuideMetadata,
+}
+
+#[derive(Serialize)]
+pub struct Problem {
+    pub file: String,
+    pub error: String,
+}
+
+// Everything in the library, for `list`
+#[derive(Serialize)]
+pub struct Listing {
+    pub programs: Vec<ProgramEntry>,
+    pub unscheduled: Vec<String>,
+    pub problems: Vec<Problem>,
+    pub migrated: usize,
+}
+
+pub fn file_name(path: &Path) -> String {
+    path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+impl Library {
+    // Hide locked channels and programs behind "LOCKED" placeholders
+    pub fn apply_parental_locks(&mut self, parental: &ParentalConfig) {
+        parental.mask_schedule(&mut self.weekly, &self.scan.items);
+    }
+
+    pub fn listing(&self) -> Listing {
+        Listing {
+            programs: self
+                .scan
+                .items
+                .iter()
+                .map(|item| ProgramEntry {
+                    file: file_name(&item.video_path),
+                    watched: self.watched.contains(&item.metadata.original_id),
+                    metadata: item.metadata.clone(),
+                })
+                .collect(),
+            unscheduled: self.weekly.unscheduled.clone(),
+            problems: self
+                .scan
+                .problems
+                .iter()
+                .map(|(path, error)| Problem {
+                    file: file_name(path),
+                    error: error.to_string(),
+                })
+                .collect(),
+            migrated: self.scan.migrated,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ChannelStatus {
+    pub channel: u8,
+    pub callsign: String,
+    pub status: &'static str,
+    pub title: String,
+    pub segment_title: String,
+    pub ends_at: DateTime<FixedOffset>,
+}
+
+// A summary of the library and what every channel is airing, for `status`
+#[derive(Serialize)]
+pub struct Status {
+    pub now: DateTime<FixedOffset>,
+    pub videos: usize,
+    pub library_bytes: u64,
+    pub unscheduled: usize,
+    pub problems: usize,
+    pub channels: Vec<ChannelStatus>,
+}
+
+pub fn status(library: &Library, time: &TimeSettings, clock: &dyn Clock) -> Status {
+    let now = clock.now();
+    let tuner = Tuner::new(library.weekly.clone(), &library.scan.items, &library.channel_map, *time);
+    let channels = tuner
+        .channels()
+        .into_iter()
+        .filter_map(|channel| tuner.tune(channel, now))
+        .map(|tuning| ChannelStatus {
+            channel: tuning.channel,
+            callsign: tuning.callsign,
+            status: if tuning.off_air { "OFF AIR" } else { "ON AIR" },
+            title: tuning.title,
+            segment_title: tuning.segment_title,
+            ends_at: time.local(tuning.ends_at).fixed_offset(),
+        })
+        .collect();
+
+    Status {
+        now: time.local(now).fixed_offset(),
+        videos: library.scan.items.len(),
+        library_bytes: library
+            .scan
+            .items
+            .iter()
+            .filter_map(|item| fs::metadata(&item.video_path).ok())
+            .map(|metadata| metadata.len())
+            .sum(),
+        unscheduled: library.weekly.unscheduled.len(),
+        problems: library.scan.problems.len(),
+        channels,
+    }
+}
+
+// original_ids of every program in the library, for telling which downloads are new
+pub fn library_ids(config: &Config) -> Result<HashSet<String>> {
+    Ok(library::scan_roots(&config.library_roots)?
+        .items
+        .into_iter()
+        .map(|item| item.metadata.original_id)
+        .collect())
+}
+
+// Programs in the download directory, which the storage quota applies to.
+// A download directory that isn't a library root is scanned as well, though
+// nothing in it airs.
+pub fn stored_programs(config: &Config, time: &TimeSettings, clock: &dyn Clock) -> Result<Vec<StoredProgram>> {
+    let mut library = load_library(config, time, clock)?;
+    let dir = config.download_dir();
+    let resolved = storage::resolve_dir(dir);
+    if !config.library_roots.iter().any(|root| storage::resolve_dir(root) == resolved) && dir.exists() {
+        library.scan.items.extend(library::scan(dir)?.items);
+    }
+    let history = WatchHistory::load(&config.history.file)?;
+    Ok(config.storage.stored_programs(&library.scan.items, dir, &library.weekly, &history, time, clock.now()))
+}
+
+// Space the trash takes from the download directory; none when it is kept elsewhere
+pub fn trash_bytes(config: &Config) -> u64 {
+    let trash = config.trash();
+    if storage::resolve_dir(trash.dir()).starts_with(storage::resolve_dir(config.download_dir())) {
+        trash.size()
+    } else {
+        0
+    }
+}
+
+// What would have to leave the download directory for `incoming_bytes` more
+// to fit under the storage quota; None when there is no quota
+pub fn eviction_plan(config: &Config, time: &TimeSettings, clock: &dyn Clock, incoming_bytes: u64) -> Result<Option<EvictionPlan>> {
+    if config.storage.quota_mb.is_none() {
+        return Ok(None);
+    }
+    Ok(config.storage.plan(stored_programs(config, time, clock)?, trash_bytes(config), incoming_bytes))
+}
+
+// Carry out a plan: empty the trash if it says so, then remove its programs,
+// videos and sidecars together
+pub fn evict(config: &Config, plan: &EvictionPlan) -> Result<()> {
+    if plan.empty_trash {
+        let trash = config.trash();
+        for entry in trash.entries()? {
+            trash.purge(&entry)?;
+        }
+    }
+    for program in &plan.evict {
+        storage::remove(program)?;
+    }
+    Ok(())
+}
+
+// Whether a download fits, checked before it starts
+#[derive(Serialize)]
+pub struct Preflight {
+    // None when neither the Internet Archive nor its download server said how big it is
+    pub bytes: Option<u64>,
+    pub free_bytes: u64,
+    // Still to be written by downloads already running
+    pub in_flight_bytes: u64,
+    // None when there is no storage quota
+    pub plan: Option<EvictionPlan>,
+}
+
+impl Preflight {
+    // Programs the storage quota would remove to make room
+    pub fn evictions(&self) -> &[StoredProgram] {
+        self.plan.as_ref().map_or(&[], |plan| &plan.evict)
+    }
+
+    // Why the download can't go ahead, if it can't. A download of unknown size
+    // still needs the reserve free, and is stopped if it eats into it.
+    pub fn problem(&self) -> Option<String> {
+        let bytes = self.bytes.unwrap_or(0);
+        let size = self.bytes.map_or_else(|| "A download of unknown size".to_string(), |bytes| format_size(bytes as usize));
+        if let Some(plan) = self.plan.as_ref().filter(|plan| plan.shortfall_bytes > 0) {
+            return Some(format!(
+                "{} would go {} over the {} storage quota even after removing everything the \"{}\" eviction policy allows",
+                size,
+                format_size(plan.shortfall_bytes as usize),
+                format_size(plan.quota_bytes as usize),
+                plan.policy
+            ));
+        }
+        let freed = self.plan.as_ref().map_or(0, EvictionPlan::freed_bytes);
+        let available = self.free_bytes.saturating_add(freed).saturating_sub(self.in_flight_bytes);
+        if bytes.saturating_add(storage::FREE_SPACE_RESERVE) > available {
+            return Some(format!(
+                "{} won't fit: {} is free on the disk once running downloads finish and {} is kept free",
+                size,
+                format_size(available as usize),
+                format_size(storage::FREE_SPACE_RESERVE as usize)
+            ));
+        }
+        None
+    }
+}
+
+// Bytes a download writes between checks that the disk still has the reserve free
+pub const FREE_SPACE_CHECK_BYTES: u64 = 8 * 1024 * 1024;
+
+// Fail once a running download has eaten into the space kept free on the disk
+pub fn ensure_reserve(config: &Config) -> Result<()> {
+    let free = storage::free_space(config.download_dir())?;
+    if free < storage::FREE_SPACE_RESERVE {
+        bail!(
+            "Stopped downloading: only {} is left free on the disk and {} is kept free",
+            format_size(free as usize),
+            format_size(storage::FREE_SPACE_RESERVE as usize)
+        );
+    }
+    Ok(())
+}
+
+// Room promised to a running download, by reservation number
+struct InFlight {
+    bytes: u64,
+    written: Arc<AtomicU64>,
+}
+
+// Downloads that passed the room check and haven't finished. Held while a
+// check runs, so downloads starting together can't each count the same space.
+static IN_FLIGHT: Mutex<BTreeMap<u64, InFlight>> = Mutex::new(BTreeMap::new());
+static NEXT_RESERVATION: AtomicU64 = AtomicU64::new(0);
+
+// Room held for a download until it is dropped
+pub struct Reservation {
+    id: u64,
+    written: Arc<AtomicU64>,
+}
+
+impl Reservation {
+    // Bytes written so far, which the disk's free space already reflects
+    pub fn set_written(&self, bytes: u64) {
+        self.written.store(bytes, Ordering::Relaxed);
+    }
+}
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        IN_FLIGHT.lock().unwrap_or_else(|e| e.into_inner()).remove(&self.id);
+    }
+}
+
+fn check_room(config: &Config, time: &TimeSettings, clock: &dyn Clock, bytes: Option<u64>, in_flight: &BTreeMap<u64, InFlight>) -> Result<Preflight> {
+    // In-flight downloads aren't in the library yet, so the quota counts all of
+    // them, while the disk has already taken what they've written
+    let reserved: u64 = in_flight.values().map(|download| download.bytes).sum();
+    let unwritten: u64 = in_flight
+        .values()
+        .map(|download| download.bytes.saturating_sub(download.written.load(Ordering::Relaxed)))
+        .sum();
+    Ok(Preflight {
+        bytes,
+        free_bytes: storage::free_space(config.download_dir())?,
+        in_flight_bytes: unwritten,
+        plan: eviction_plan(config, time, clock, bytes.unwrap_or(0) + reserved)?,
+    })
+}
+
+pub fn preflight(config: &Config, time: &TimeSettings, clock: &dyn Clock, bytes: Option<u64>) -> Result<Preflight> {
+    let in_flight = IN_FLIGHT.lock().unwrap_or_else(|e| e.into_inner());
+    check_room(config, time, clock, bytes, &in_flight)
+}
+
+// Check a download fits on the disk and under the storage quota, alongside
+// the downloads already running, and remove what the eviction policy picks to
+// make room. Returns what was removed and the room held for the download.
+pub fn make_room(config: &Config, time: &TimeSettings, clock: &dyn Clock, bytes: Option<u64>) -> Result<(Vec<StoredProgram>, Reservation)> {
+    let mut in_flight = IN_FLIGHT.lock().unwrap_or_else(|e| e.into_inner());
+    let preflight = check_room(config, time, clock, bytes, &in_flight)?;
+    if let Some(problem) = preflight.problem() {
+        bail!(problem);
+    }
+    let evicted = match preflight.plan {
+        Some(plan) => {
+            evict(config, &plan)?;
+            plan.evict
+        }
+        None => Vec::new(),
+    };
+    let id = NEXT_RESERVATION.fetch_add(1, Ordering::Relaxed);
+    let written = Arc::new(AtomicU64::new(0));
+    in_flight.insert(id, InFlight { bytes: bytes.unwrap_or(0), written: Arc::clone(&written) });
+    Ok((evicted, Reservation { id, written }))
+}
+
+// Move programs to the trash along with their sidecars and thumbnails
+pub fn trash_items(config: &Config, clock: &dyn Clock, items: &[&LibraryItem]) -> Result<Vec<TrashEntry>> {
+    let trash = config.trash();
+    let now = clock.now();
+    items
+        .iter()
+        .map(|item| trash.put(&item.video_path, &item.metadata.title, &item.metadata.original_id, now))
+        .collect()
+}
+
+// Move every video in the download directory to the trash, with its sidecar
+// and thumbnails, including videos the library skips for lack of a sidecar
+pub fn clear_videos(config: &Config, clock: &dyn Clock) -> Result<Vec<TrashEntry>> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(config.download_dir())? {
+        let path = entry?.path();
+        if path.is_file() && library::is_video_file(&path) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    let trash = config.trash();
+    let now = clock.now();
+    paths
+        .iter()
+        .map(|path| {
+            let (title, original_id) = match sidecar::load(&sidecar::sidecar_path(path)) {
+                Ok(loaded) => (loaded.metadata.title, loaded.metadata.original_id),
+                Err(_) => (file_name(path), String::new()),
+            };
+            trash.put(path, &title, &original_id, now)
+        })
+        .collect()
+}
+
+// Delete for good whatever has been in the trash longer than storage.trash_days
+pub fn purge_expired_trash(config: &Config, clock: &dyn Clock) -> Result<Vec<TrashEntry>> {
+    config.trash().purge_expired(config.storage.trash_days, clock.now())
+}
+
+// The library program a download would duplicate: one from the same
+// Internet Archive item, or one with the same content hash
+pub fn existing_copy(config: &Config, identifier: &str, sha1: Option<&str>) -> Result<Option<LibraryItem>> {
+    let scan = library::scan_roots(&config.library_roots)?;
+    Ok(scan.items.into_iter().find(|item| {
+        item.metadata.original_id == identifier || sha1.is_some_and(|sha1| item.metadata.content_sha1.as_deref() == Some(sha1))
+    }))
+}
+
+// Claim a file name in `dir` for a download that doesn't collide with
+// anything there: "title.year.ia.mp4" when free, otherwise with the
+// identifier added. The name is held by creating its .part file, which only
+// one download can do, so downloads running together never share a name.
+pub fn claim_download_name(dir: &Path, base: &str, identifier: &str) -> Result<(String, PathBuf, File)> {
+    for n in 1.. {
+        let name = match n {
+            1 => format!("{}.ia.mp4", base),
+            2 => format!("{}.{}.ia.mp4", base, identifier),
+            _ => format!("{}.{}-{}.ia.mp4", base, identifier, n - 1),
+        };
+        let path = dir.join(&name);
+        if path.exists() || sidecar::sidecar_path(&path).exists() {
+            continue;
+        }
+        let partial = dir.join(format!("{}.part", name));
+        match OpenOptions::new().write(true).create_new(true).open(&partial) {
+            Ok(file) => return Ok((name, partial, file)),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e).with_context(|| format!("Failed to create {}", partial.display())),
+        }
+    }
+    unreachable!("names run out before numbers do")
+}
+
+// Duplicates in the library, with how many content hashes had to be worked out
+#[derive(Serialize)]
+pub struct DuplicateReport {
+    pub programs: usize,
+    pub hashed: usize,
+    pub groups: Vec<DuplicateGroup>,
+    pub skipped: Vec<Unfingerprinted>,
+}
+
+// A program that could not be fingerprinted, and why
+#[derive(Serialize)]
+pub struct Unfingerprinted {
+    pub file: String,
+    pub reason: String,
+}
+
+impl DuplicateReport {
+    pub fn extras(&self) -> impl Iterator<Item = &DuplicateFile> {
+        self.groups.iter().flat_map(|group| group.extras())
+    }
+}
+
+// Look for duplicates across every library root. Content hashes missing from
+// sidecars are worked out and saved, so later checks are quick. With
+// `ffmpeg`, programs are also compared by fingerprint.
+pub fn find_duplicates(config: &Config, ffmpeg: Option<&str>) -> Result<DuplicateReport> {
+    let mut items = library::scan_roots(&config.library_roots)?.items;
+    let mut hashed = 0;
+    for item in items.iter_mut().filter(|item| item.metadata.content_sha1.is_none()) {
+        item.metadata.content_sha1 = Some(dedupe::content_sha1(&item.video_path)?);
+        item.metadata.save(&item.sidecar_path)?;
+        hashed += 1;
+    }
+
+    let mut fingerprints = HashMap::new();
+    let mut skipped = Vec::new();
+    if let Some(ffmpeg) = ffmpeg {
+        dedupe::check_ffmpeg(ffmpeg)?;
+        for item in &items {
+            let Some(seconds) = schedule::parse_duration_seconds(&item.metadata.duration) else {
+                skipped.push(Unfingerprinted {
+                    file: file_name(&item.video_path),
+                    reason: format!("unknown duration \"{}\"", item.metadata.duration),
+                });
+                continue;
+            };
+            match Fingerprint::take(ffmpeg, &item.video_path, seconds) {
+                Ok(fingerprint) => {
+                    fingerprints.insert(item.video_path.clone(), fingerprint);
+                }
+                Err(e) => skipped.push(Unfingerprinted { file: file_name(&item.video_path), reason: format!("{:#}", e) }),
+            }
+        }
+    }
+
+    Ok(DuplicateReport {
+        programs: items.len(),
+        hashed,
+        groups: dedupe::find_duplicates(&items, &fingerprints),
+        skipped,
+    })
+}
+
+// Move every duplicate except the copy kept in each group to the trash
+pub fn trash_duplicates(config: &Config, clock: &dyn Clock, report: &DuplicateReport) -> Result<Vec<TrashEntry>> {
+    let trash = config.trash();
+    let now = clock.now();
+    report.extras().map(|file| trash.put(&file.video_path, &file.title, &file.original_id, now)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MB: u64 = 1024 * 1024;
+
+    fn preflight(bytes: Option<u64>, free_bytes: u64) -> Preflight {
+        Preflight { bytes, free_bytes, in_flight_bytes: 0, plan: None }
+    }
+
+    #[test]
+    fn downloads_must_leave_the_reserve_free_even_when_their_size_is_unknown() {
+        let reserve = storage::FREE_SPACE_RESERVE;
+        assert!(preflight(Some(50 * MB), reserve + 50 * MB).problem().is_none());
+        assert!(preflight(Some(50 * MB + 1), reserve + 50 * MB).problem().is_some());
+        assert!(preflight(Some(u64::MAX), 4 * reserve).problem().is_some());
+
+        assert!(preflight(None, reserve).problem().is_none());
+        let problem = preflight(None, reserve - 1).problem().unwrap();
+        assert!(problem.starts_with("A download of unknown size won't fit"), "{}", problem);
+
+        // Running downloads still to be written count against the free space
+        let busy = Preflight { in_flight_bytes: 10 * MB, ..preflight(None, reserve + 5 * MB) };
+        assert!(busy.problem().is_some());
+    }
+}
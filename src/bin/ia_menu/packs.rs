@@ -0,0 +1,155 @@
+// Lineup packs: exporting channels to share, and importing them by adding the
+// channels and queueing downloads for the programs that are missing
+
+use anyhow::Result;
+use reqwest::Client;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use video_server::channels::ChannelMap;
+use video_server::clock::{Clock, SharedClock};
+use video_server::config::Config;
+use video_server::library;
+use video_server::pack::{LineupPack, PackedProgram};
+use video_server::queue::DownloadQueue;
+
+use super::{import, ops, prompt_user, DownloadState};
+
+// Pack the channels from this machine's channel map and library
+pub fn export(config: &Config, clock: &dyn Clock, name: &str, description: &str, channels: &[u8]) -> Result<LineupPack> {
+    let map = ChannelMap::load(&config.channel_map)?;
+    let scan = library::scan_roots(&config.library_roots)?;
+    Ok(LineupPack::build(name, description, channels, &map, &scan.items, clock.now()))
+}
+
+// What importing a pack would change here
+pub struct PackImport {
+    pub pack: LineupPack,
+    // Channels whose local definitions the pack replaces
+    pub conflicts: Vec<u8>,
+    pub missing: Vec<PackedProgram>,
+}
+
+impl PackImport {
+    pub fn summary(&self) -> String {
+        let pack = &self.pack;
+        let mut lines = vec![format!("📦 {}", pack.name)];
+        if !pack.description.is_empty() {
+            lines.push(format!("   {}", pack.description));
+        }
+        let numbers: Vec<String> = pack.channels.iter().map(|channel| channel.number.to_string()).collect();
+        lines.push(format!("📺 {} channel(s): {}", pack.channels.len(), numbers.join(", ")));
+        if !self.conflicts.is_empty() {
+            let conflicts: Vec<String> = self.conflicts.iter().map(|number| number.to_string()).collect();
+            lines.push(format!("⚠️  Replaces the existing definition of channel(s) {}", conflicts.join(", ")));
+        }
+        lines.push(format!(
+            "📥 {} of {} program(s) to download",
+            self.missing.len(),
+            pack.programs.len()
+        ));
+        lines.join("\n")
+    }
+}
+
+pub fn source(pack: &LineupPack) -> String {
+    format!("pack:{}", pack.name)
+}
+
+pub fn prepare(config: &Config, path: &Path) -> Result<PackImport> {
+    let pack = LineupPack::load(path)?;
+    let map = ChannelMap::load(&config.channel_map)?;
+    let library = ops::library_ids(config)?;
+    let conflicts = pack.conflicts(&map);
+    let missing = pack.missing(&library).into_iter().cloned().collect();
+    Ok(PackImport { pack, conflicts, missing })
+}
+
+// Add the pack's channels to the channel map and queue its missing programs
+// on the channels they aired on. Returns how many downloads were queued.
+pub fn apply(config: &Config, clock: &dyn Clock, import: &PackImport, with_rules: bool) -> Result<usize> {
+    let mut map = ChannelMap::load(&config.channel_map)?;
+    import.pack.apply(&mut map, with_rules);
+    map.save(&config.channel_map)?;
+
+    let path = &config.downloads.queue_file;
+    let mut queue = DownloadQueue::load(path)?;
+    let now = clock.now();
+    let source = source(&import.pack);
+    let queued = import
+        .missing
+        .iter()
+        .filter(|program| queue.enqueue(&program.identifier, Some(program.channel), &source, now))
+        .count();
+    queue.save(path)?;
+    Ok(queued)
+}
+
+pub async fn menu(client: &Client, config: &Config, clock: SharedClock, download_state: Arc<Mutex<DownloadState>>) -> Result<()> {
+    print!("\x1B[2J\x1B[1;1H");
+    io::stdout().flush()?;
+    println!("📦 Lineup Packs");
+    println!("=====================================\n");
+    println!("e. Export channels to a pack");
+    println!("i. Import a pack");
+
+    match prompt_user("\nEnter your choice (or press Enter to return): ")?.as_str() {
+        "e" => {
+            let name = prompt_user("Pack name, e.g. \"Cleveland 1994\": ")?;
+            if name.is_empty() {
+                return Ok(());
+            }
+            let description = prompt_user("Description (optional): ")?;
+            let channels = prompt_user("Channels to include, separated by spaces (Enter for all): ")?;
+            let channels: Vec<u8> = match channels.split_whitespace().map(|number| number.parse()).collect() {
+                Ok(channels) => channels,
+                Err(_) => {
+                    println!("Invalid channel number.");
+                    prompt_user("\nPress Enter to return to the main menu...")?;
+                    return Ok(());
+                }
+            };
+            let file = prompt_user("File to write (default lineup-pack.json): ")?;
+            let file = PathBuf::from(if file.is_empty() { "lineup-pack.json" } else { &file });
+            let pack = export(config, clock.as_ref(), &name, &description, &channels)?;
+            if pack.channels.is_empty() {
+                println!("No channels to pack; channels are defined in {}.", config.channel_map.display());
+                prompt_user("\nPress Enter to return to the main menu...")?;
+                return Ok(());
+            }
+            pack.save(&file)?;
+            println!("✓ Packed {} channel(s) and {} program(s) into {}", pack.channels.len(), pack.programs.len(), file.display());
+        }
+        "i" => {
+            let file = prompt_user("Pack file: ")?;
+            if file.is_empty() {
+                return Ok(());
+            }
+            let import = match prepare(config, Path::new(&file)) {
+                Ok(import) => import,
+                Err(e) => {
+                    println!("Error reading the pack: {:#}", e);
+                    prompt_user("\nPress Enter to return to the main menu...")?;
+                    return Ok(());
+                }
+            };
+            println!("\n{}", import.summary());
+            if prompt_user("\nAdd these channels? (y/n): ")?.to_lowercase() != "y" {
+                println!("Operation cancelled.");
+                prompt_user("\nPress Enter to return to the main menu...")?;
+                return Ok(());
+            }
+            let with_rules = prompt_user("Also use the pack's filler and commercial break rules for every channel? (y/n): ")?.to_lowercase() == "y";
+            apply(config, clock.as_ref(), &import, with_rules)?;
+            let started = import::start_queued(client, config, clock, &download_state).await?;
+            println!("✓ Channels added. 📥 Downloading {} item(s) in the background.", started);
+            if started > 0 {
+                println!("If the menu is closed first, run `ia_menu queue run` to finish.");
+            }
+        }
+        _ => return Ok(()),
+    }
+    prompt_user("\nPress Enter to return to the main menu...")?;
+    Ok(())
+}
@@ -0,0 +1,199 @@
+// Saved searches: finding what's new for them, the job that reruns them while
+// the menu is open, and the menu screen for managing them
+
+use anyhow::Result;
+use reqwest::Client;
+use std::io::{self, Write};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{watch, Mutex};
+use video_server::clock::SharedClock;
+use video_server::config::Config;
+use video_server::subscriptions::{self, SearchFilters, Subscription, SubscriptionList};
+
+use super::{ops, prompt_user, search_archive, spawn_download, DownloadState};
+
+// Search results looked through per run
+const SEARCH_ROWS: usize = 100;
+
+// How often the menu looks for subscriptions that are due
+const CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+// Identifiers the subscription should download now
+pub async fn find_new(client: &Client, config: &Config, subscription: &Subscription) -> Result<Vec<String>> {
+    let docs = search_archive(client, &subscription.search_query(), SEARCH_ROWS).await?;
+    let library = ops::library_ids(config)?;
+    let results = docs.iter().map(|doc| (doc.identifier.as_str(), doc.item_size.map(|size| size as u64)));
+    Ok(subscription.pick_new(results, &library))
+}
+
+// Apply a run's outcome to the latest copy of the list, keeping edits made while it ran
+pub fn update_list(config: &Config, update: impl FnOnce(&mut SubscriptionList)) -> Result<()> {
+    let path = &config.downloads.subscriptions_file;
+    let mut list = SubscriptionList::load(path)?;
+    update(&mut list);
+    list.save(path)
+}
+
+// Rerun the subscriptions that are due, or all of them when `force` is set,
+// and queue their new items as background downloads. Returns how many were queued.
+pub async fn queue_due(client: &Client, config: &Config, clock: SharedClock, download_state: &Mutex<DownloadState>, force: bool) -> Result<usize> {
+    let list = SubscriptionList::load(&config.downloads.subscriptions_file)?;
+    let now = clock.now();
+    let mut total = 0;
+    for subscription in list.subscriptions.iter().filter(|subscription| force || subscription.is_due(now)) {
+        match find_new(client, config, subscription).await {
+            Ok(found) => {
+                let mut state = download_state.lock().await;
+                let queued: Vec<String> = found.into_iter().filter(|identifier| !state.is_downloading(identifier)).collect();
+                for identifier in &queued {
+                    let (handle, progress) = spawn_download(client, identifier, subscription.channel, config, Arc::clone(&clock), Arc::clone(&state.slots), false);
+                    state.add_download(identifier.clone(), handle, progress).await;
+                }
+                if !queued.is_empty() {
+                    state.notice(format!("📡 {}: queued {} new item(s)", subscription.name, queued.len()));
+                }
+                drop(state);
+                total += queued.len();
+                update_list(config, |list| list.record_run(&subscription.name, now, &queued))?;
+            }
+            Err(e) => {
+                let error = format!("{:#}", e);
+                download_state.lock().await.notice(format!("⚠️  {}: search failed: {}", subscription.name, error));
+                update_list(config, |list| list.record_failure(&subscription.name, &error))?;
+            }
+        }
+    }
+    Ok(total)
+}
+
+// Check for due subscriptions every few minutes for as long as the menu runs,
+// using whatever configuration the menu last loaded
+pub fn spawn_job(client: Client, config: watch::Receiver<Config>, clock: SharedClock, download_state: Arc<Mutex<DownloadState>>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            let config = config.borrow().clone();
+            if let Err(e) = queue_due(&client, &config, Arc::clone(&clock), &download_state, false).await {
+                download_state.lock().await.notice(format!("⚠️  Saved searches not checked: {:#}", e));
+            }
+        }
+    });
+}
+
+// One line per subscription for the menu and `subscriptions list`
+pub fn summary(subscription: &Subscription) -> String {
+    let channel = subscription.channel.map(|channel| format!(" → CH {}", channel)).unwrap_or_default();
+    let mut last_run = match subscription.last_run {
+        Some(at) => format!("last run {}", at.format("%Y-%m-%d %H:%M UTC")),
+        None => "never run".to_string(),
+    };
+    if let Some(result) = &subscription.last_result {
+        last_run = format!("{} ({})", last_run, result);
+    }
+    format!(
+        "{} — \"{}\"{}, up to {} every {}h; {}",
+        subscription.name,
+        subscription.search_query(),
+        channel,
+        subscription.max_items,
+        subscription.interval_hours,
+        last_run
+    )
+}
+
+pub async fn menu(client: &Client, config: &Config, clock: SharedClock, download_state: Arc<Mutex<DownloadState>>) -> Result<()> {
+    let path = &config.downloads.subscriptions_file;
+    loop {
+        print!("\x1B[2J\x1B[1;1H");
+        io::stdout().flush()?;
+        println!("📡 Saved Searches");
+        println!("=====================================\n");
+
+        let mut list = SubscriptionList::load(path)?;
+        if list.subscriptions.is_empty() {
+            println!("No saved searches yet. New items for each are downloaded automatically.");
+        }
+        for (i, subscription) in list.subscriptions.iter().enumerate() {
+            println!("[{}] {}", i + 1, summary(subscription));
+        }
+
+        println!("\na. Add a saved search");
+        println!("r. Remove a saved search");
+        println!("n. Check every saved search now");
+        let choice = prompt_user("\nEnter your choice (or press Enter to return): ")?;
+        match choice.as_str() {
+            "" => return Ok(()),
+            "a" => {
+                if let Some(subscription) = prompt_subscription()? {
+                    match list.add(subscription).and_then(|_| list.save(path)) {
+                        Ok(()) => println!("✓ Saved. It will be checked within a few minutes."),
+                        Err(e) => println!("Could not save: {:#}", e),
+                    }
+                }
+            }
+            "r" => {
+                let number = prompt_user("Number of the saved search to remove: ")?;
+                match number.parse::<usize>() {
+                    Ok(number) if number > 0 && number <= list.subscriptions.len() => {
+                        let removed = list.subscriptions.remove(number - 1);
+                        list.save(path)?;
+                        println!("✓ Removed {}", removed.name);
+                    }
+                    _ => println!("Invalid selection."),
+                }
+            }
+            "n" => {
+                println!("\n🔍 Searching...");
+                match queue_due(client, config, Arc::clone(&clock), &download_state, true).await {
+                    Ok(0) => println!("Nothing new to download."),
+                    Ok(count) => println!("📥 Queued {} download(s) in the background.", count),
+                    Err(e) => println!("Error checking saved searches: {:#}", e),
+                }
+            }
+            _ => println!("Invalid choice."),
+        }
+        prompt_user("\nPress Enter to continue...")?;
+    }
+}
+
+// Ask for a new subscription's settings; None if the user gave up
+fn prompt_subscription() -> Result<Option<Subscription>> {
+    let name = prompt_user("Name, e.g. \"90s commercials\": ")?;
+    let query = prompt_user("Search keywords: ")?;
+    if name.is_empty() || query.is_empty() {
+        println!("A name and keywords are required.");
+        return Ok(None);
+    }
+    let optional = |prompt: &str| -> Result<Option<String>> {
+        let value = prompt_user(prompt)?;
+        Ok(Some(value).filter(|value| !value.is_empty()))
+    };
+    let filters = SearchFilters {
+        collection: optional("Collection (optional): ")?,
+        creator: optional("Creator (optional): ")?,
+        year_from: optional("From year (optional): ")?.and_then(|year| year.parse().ok()),
+        year_to: optional("To year (optional): ")?.and_then(|year| year.parse().ok()),
+    };
+    let channel = optional("Channel to file new items under (optional): ")?.and_then(|channel| channel.parse().ok());
+    let max_items = optional(&format!("New items per run (default {}): ", subscriptions::DEFAULT_MAX_ITEMS))?
+        .and_then(|count| count.parse().ok())
+        .unwrap_or(subscriptions::DEFAULT_MAX_ITEMS);
+    let max_item_mb = optional("Skip items larger than this many MB (optional): ")?.and_then(|mb| mb.parse().ok());
+    let interval_hours = optional(&format!("Hours between checks (default {}): ", subscriptions::DEFAULT_INTERVAL_HOURS))?
+        .and_then(|hours| hours.parse().ok())
+        .unwrap_or(subscriptions::DEFAULT_INTERVAL_HOURS);
+    Ok(Some(Subscription {
+        name,
+        query,
+        filters,
+        channel,
+        max_items,
+        max_item_mb,
+        interval_hours,
+        last_run: None,
+        last_result: None,
+        seen: Default::default(),
+    }))
+}
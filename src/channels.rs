@@ -0,0 +1,370 @@
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use crate::schedule::{DAYS, MINUTES_PER_DAY};
+use crate::sidecar::TvGuideMetadata;
+
+// Default location of the channel map, relative to the working directory
+pub const DEFAULT_CHANNEL_MAP: &str = "./channels.json";
+
+// Personal channels are numbered from here up unless a number is asked for
+pub const PERSONAL_CHANNEL_BASE: u8 = 100;
+
+// Per-channel programming rules loaded from the channel map
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ChannelMap {
+    #[serde(default)]
+    pub channels: Vec<ChannelConfig>,
+    #[serde(default)]
+    pub filler: FillerConfig,
+    #[serde(default)]
+    pub interstitials: InterstitialConfig,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ChannelConfig {
+    pub number: u8,
+    // Overrides the callsign stored in the sidecars
+    #[serde(default)]
+    pub callsign: Option<String>,
+    #[serde(default)]
+    pub dayparts: Vec<Daypart>,
+    // When unset the channel broadcasts around the clock
+    #[serde(default)]
+    pub broadcast_hours: Option<BroadcastHours>,
+    // Shown in the guide in place of a callsign, e.g. "My Saturday Cartoons"
+    #[serde(default)]
+    pub name: Option<String>,
+    // Makes this a personal channel that airs these programs instead of the
+    // ones whose sidecars name its number
+    #[serde(default)]
+    pub selection: Option<ProgramSelection>,
+    // Viewer who made the personal channel
+    #[serde(default)]
+    pub owner: Option<String>,
+}
+
+// Programs chosen for a personal channel: the ones picked by hand or starred
+// by a viewer, plus any matching the query fields that are set
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProgramSelection {
+    // original_ids
+    pub programs: Vec<String>,
+    // Everything this viewer has starred
+    pub favorites_of: Option<String>,
+    pub categories: Vec<String>,
+    pub year_from: Option<i32>,
+    pub year_to: Option<i32>,
+    // Programs tagged with any of these subjects
+    pub subjects: Vec<String>,
+}
+
+impl ProgramSelection {
+    fn has_query(&self) -> bool {
+        !self.categories.is_empty() || self.year_from.is_some() || self.year_to.is_some() || !self.subjects.is_empty()
+    }
+
+    // favorites maps each viewer to the original_ids they have starred
+    pub fn matches(&self, program: &TvGuideMetadata, favorites: &BTreeMap<String, HashSet<String>>) -> bool {
+        let id = &program.original_id;
+        if self.programs.contains(id) {
+            return true;
+        }
+        if let Some(viewer) = &self.favorites_of {
+            if favorites.get(viewer).is_some_and(|starred| starred.contains(id)) {
+                return true;
+            }
+        }
+        if !self.has_query() {
+            return false;
+        }
+        let year = program.year.get(..4).and_then(|year| year.parse::<i32>().ok());
+        let category = self.categories.is_empty() || self.categories.iter().any(|c| c.eq_ignore_ascii_case(&program.category));
+        let year_from = self.year_from.is_none_or(|from| year.is_some_and(|year| year >= from));
+        let year_to = self.year_to.is_none_or(|to| year.is_some_and(|year| year <= to));
+        let subject = self.subjects.is_empty()
+            || self.subjects.iter().any(|subject| program.tags.iter().any(|tag| tag.eq_ignore_ascii_case(subject)));
+        category && year_from && year_to && subject
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.programs.is_empty() && self.favorites_of.is_none() && !self.has_query() {
+            return Err("selection picks no programs".to_string());
+        }
+        if let (Some(from), Some(to)) = (self.year_from, self.year_to) {
+            if from > to {
+                return Err(format!("year_from {} is after year_to {}", from, to));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Daily sign-on and sign-off times; between them the channel is off the air
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BroadcastHours {
+    pub sign_on: String,
+    pub sign_off: String,
+    // original_id of the clips to play; otherwise the library's "Sign On"/"Sign Off" items are used
+    #[serde(default)]
+    pub sign_on_clip: Option<String>,
+    #[serde(default)]
+    pub sign_off_clip: Option<String>,
+    #[serde(default)]
+    pub pattern: OffAirPattern,
+}
+
+// What the channel shows between sign-off and sign-on
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OffAirPattern {
+    #[default]
+    Bars,
+    Static,
+}
+
+impl OffAirPattern {
+    pub fn title(&self) -> &'static str {
+        match self {
+            OffAirPattern::Bars => "SMPTE Color Bars",
+            OffAirPattern::Static => "Static",
+        }
+    }
+
+    // Source for mpv's libavfilter input, generated on the fly
+    pub fn mpv_video(&self) -> &'static str {
+        match self {
+            OffAirPattern::Bars => "av://lavfi:smptebars=size=640x480:rate=30",
+            OffAirPattern::Static => "av://lavfi:nullsrc=size=640x480:rate=30,geq=lum='random(1)*255':cb=128:cr=128",
+        }
+    }
+
+    // mpv properties to set before loading the video, as (property, value) pairs
+    pub fn mpv_properties(&self) -> Vec<(&'static str, serde_json::Value)> {
+        let tone = match self {
+            OffAirPattern::Bars => "av://lavfi:sine=frequency=1000",
+            OffAirPattern::Static => "av://lavfi:anoisesrc=color=white:amplitude=0.3",
+        };
+        vec![("audio-files", serde_json::json!([tone]))]
+    }
+}
+
+// A recurring window reserved for programs of certain categories,
+// e.g. cartoons from 7:00 AM to 11:00 AM on Saturday
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Daypart {
+    pub name: String,
+    // Day names, "weekdays", "weekends" or "daily"; empty means every day
+    #[serde(default)]
+    pub days: Vec<String>,
+    pub start: String,
+    pub end: String,
+    pub categories: Vec<String>,
+}
+
+// What airs when a window has no program that fits
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct FillerConfig {
+    // Title shown in the guide for generated filler
+    #[serde(default = "default_filler_title")]
+    pub title: String,
+    // Library categories that may be used as filler
+    #[serde(default)]
+    pub categories: Vec<String>,
+}
+
+fn default_filler_title() -> String {
+    "Paid Programming".to_string()
+}
+
+impl Default for FillerConfig {
+    fn default() -> Self {
+        Self {
+            title: default_filler_title(),
+            categories: Vec::new(),
+        }
+    }
+}
+
+// Commercials, station IDs and bumpers that air between and inside programs
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct InterstitialConfig {
+    // Library categories treated as interstitials rather than programs
+    #[serde(default = "default_interstitial_categories")]
+    pub categories: Vec<String>,
+    // Minutes into each program at which to cut to a commercial break
+    #[serde(default)]
+    pub break_points_minutes: Vec<u32>,
+    #[serde(default = "default_break_seconds")]
+    pub break_seconds: u32,
+    // Clips longer than this are ad reels, played a break's worth at a time
+    #[serde(default = "default_reel_threshold_seconds")]
+    pub reel_threshold_seconds: u32,
+}
+
+fn default_interstitial_categories() -> Vec<String> {
+    vec!["Commercial".to_string(), "Station ID".to_string(), "Bumper".to_string()]
+}
+
+fn default_break_seconds() -> u32 {
+    120
+}
+
+fn default_reel_threshold_seconds() -> u32 {
+    300
+}
+
+impl Default for InterstitialConfig {
+    fn default() -> Self {
+        Self {
+            categories: default_interstitial_categories(),
+            break_points_minutes: Vec::new(),
+            break_seconds: default_break_seconds(),
+            reel_threshold_seconds: default_reel_threshold_seconds(),
+        }
+    }
+}
+
+impl InterstitialConfig {
+    pub fn matches_category(&self, category: &str) -> bool {
+        self.categories.iter().any(|c| c.eq_ignore_ascii_case(category))
+    }
+}
+
+impl ChannelMap {
+    // Load the channel map, treating a missing file as an empty map
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read channel map {}", path.display()))?;
+        let map: ChannelMap = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse channel map {}", path.display()))?;
+        map.validate()?;
+        Ok(map)
+    }
+
+    // Validate and write the map, e.g. after it was edited through the server
+    pub fn save(&self, path: &Path) -> Result<()> {
+        self.validate()?;
+        let content = serde_json::to_string_pretty(self)?;
+        fs::write(path, content).with_context(|| format!("Failed to write channel map {}", path.display()))
+    }
+
+    pub fn channel(&self, number: u8) -> Option<&ChannelConfig> {
+        self.channels.iter().find(|c| c.number == number)
+    }
+
+    pub fn is_personal(&self, number: u8) -> bool {
+        self.channel(number).is_some_and(|c| c.selection.is_some())
+    }
+
+    // Lowest number from PERSONAL_CHANNEL_BASE up that neither the map nor `taken` uses
+    pub fn free_personal_number(&self, taken: &[u8]) -> Option<u8> {
+        (PERSONAL_CHANNEL_BASE..=u8::MAX).find(|number| self.channel(*number).is_none() && !taken.contains(number))
+    }
+
+    fn validate(&self) -> Result<()> {
+        for channel in &self.channels {
+            if let Some(selection) = &channel.selection {
+                selection.validate().map_err(|e| anyhow!("channel {}: {}", channel.number, e))?;
+                if self.channels.iter().filter(|c| c.number == channel.number).count() > 1 {
+                    return Err(anyhow!("personal channel {} shares its number with another channel", channel.number));
+                }
+            }
+            if let Some(hours) = &channel.broadcast_hours {
+                let sign_on = parse_clock(&hours.sign_on)
+                    .ok_or_else(|| anyhow!("channel {}: unrecognised sign-on time '{}'", channel.number, hours.sign_on))?;
+                let sign_off = parse_clock(&hours.sign_off)
+                    .ok_or_else(|| anyhow!("channel {}: unrecognised sign-off time '{}'", channel.number, hours.sign_off))?;
+                if sign_on % MINUTES_PER_DAY == sign_off % MINUTES_PER_DAY {
+                    return Err(anyhow!("channel {}: sign-on and sign-off are the same time", channel.number));
+                }
+            }
+            for daypart in &channel.dayparts {
+                daypart.day_indexes().map_err(|e| anyhow!("channel {} daypart '{}': {}", channel.number, daypart.name, e))?;
+                for time in [&daypart.start, &daypart.end] {
+                    parse_clock(time).ok_or_else(|| {
+                        anyhow!("channel {} daypart '{}': unrecognised time '{}'", channel.number, daypart.name, time)
+                    })?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Daypart {
+    // Indexes into DAYS (Monday = 0) that this daypart airs on
+    pub fn day_indexes(&self) -> Result<Vec<u32>, String> {
+        if self.days.is_empty() {
+            return Ok((0..7).collect());
+        }
+        let mut indexes = Vec::new();
+        for day in &self.days {
+            let lower = day.trim().to_lowercase();
+            match lower.as_str() {
+                "daily" | "everyday" => indexes.extend(0..7),
+                "weekdays" => indexes.extend(0..5),
+                "weekends" => indexes.extend(5..7),
+                _ => {
+                    let index = DAYS
+                        .iter()
+                        .position(|d| d.to_lowercase() == lower || d[..3].to_lowercase() == lower)
+                        .ok_or_else(|| format!("unrecognised day '{}'", day))?;
+                    indexes.push(index as u32);
+                }
+            }
+        }
+        indexes.sort_unstable();
+        indexes.dedup();
+        Ok(indexes)
+    }
+
+    pub fn matches_category(&self, category: &str) -> bool {
+        self.categories.iter().any(|c| c.eq_ignore_ascii_case(category))
+    }
+}
+
+// Parse "7:00 AM", "11 PM", "19:30" or "7" into minutes after midnight
+pub fn parse_clock(time: &str) -> Option<u32> {
+    let lower = time.trim().to_lowercase();
+    let (clock, meridiem) = if let Some(rest) = lower.strip_suffix("am") {
+        (rest.trim(), Some(false))
+    } else if let Some(rest) = lower.strip_suffix("pm") {
+        (rest.trim(), Some(true))
+    } else {
+        (lower.as_str(), None)
+    };
+
+    let (hour, minute) = match clock.split_once(':') {
+        Some((h, m)) => (h.parse::<u32>().ok()?, m.parse::<u32>().ok()?),
+        None => (clock.parse::<u32>().ok()?, 0),
+    };
+    if minute >= 60 {
+        return None;
+    }
+
+    let hour = match meridiem {
+        Some(pm) => {
+            if hour == 0 || hour > 12 {
+                return None;
+            }
+            (hour % 12) + if pm { 12 } else { 0 }
+        }
+        None if hour <= 24 => hour,
+        None => return None,
+    };
+
+    let minutes = hour * 60 + minute;
+    if minutes > MINUTES_PER_DAY {
+        None
+    } else {
+        Some(minutes)
+    }
+}
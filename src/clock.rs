@@ -0,0 +1,152 @@
+use chrono::{DateTime, Duration, Utc};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use crate::local_time::TimeSettings;
+
+// Source of the current time for the scheduler, server and guide, so the
+// lineup can be shown at any moment instead of only "now"
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+}
+
+pub type SharedClock = Arc<dyn Clock>;
+
+// The host's real time
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+// Fastest simulation allowed; a week passes in about a minute
+pub const MAX_SPEED: f64 = 10_000.0;
+
+// A clock that starts at a chosen moment and runs at `speed` times real time.
+// A speed of 0 freezes it; it only moves when advanced or set.
+#[derive(Debug)]
+pub struct SimulatedClock {
+    // Simulated time at the anchor instant
+    anchor: Mutex<(DateTime<Utc>, Instant)>,
+    speed: f64,
+}
+
+impl SimulatedClock {
+    pub fn new(start: DateTime<Utc>, speed: f64) -> Self {
+        Self {
+            anchor: Mutex::new((start, Instant::now())),
+            speed: speed.clamp(0.0, MAX_SPEED),
+        }
+    }
+
+    // A clock that stays at `start` until moved
+    pub fn frozen(start: DateTime<Utc>) -> Self {
+        Self::new(start, 0.0)
+    }
+
+    pub fn speed(&self) -> f64 {
+        self.speed
+    }
+
+    pub fn set(&self, time: DateTime<Utc>) {
+        *self.anchor.lock().unwrap() = (time, Instant::now());
+    }
+
+    pub fn advance(&self, by: Duration) {
+        let now = self.now();
+        let limit = if by < Duration::zero() { DateTime::<Utc>::MIN_UTC } else { DateTime::<Utc>::MAX_UTC };
+        self.set(now.checked_add_signed(by).unwrap_or(limit));
+    }
+}
+
+impl Clock for SimulatedClock {
+    fn now(&self) -> DateTime<Utc> {
+        let (start, anchored_at) = *self.anchor.lock().unwrap();
+        let elapsed = anchored_at.elapsed().as_secs_f64() * self.speed;
+        // Clamp rather than overflow when a simulation runs past the end of time
+        Duration::try_milliseconds((elapsed * 1000.0) as i64)
+            .and_then(|elapsed| start.checked_add_signed(elapsed))
+            .unwrap_or(DateTime::<Utc>::MAX_UTC)
+    }
+}
+
+// The clock for a binary: the system clock, or a simulation starting at a
+// local time such as "1994-10-17T19:30" and running at `speed`
+pub fn from_options(time: &TimeSettings, simulate_at: Option<&str>, speed: f64) -> Result<SharedClock, String> {
+    if !speed.is_finite() || !(0.0..=MAX_SPEED).contains(&speed) {
+        return Err(format!("speed must be between 0 and {}, got {}", MAX_SPEED, speed));
+    }
+    match simulate_at {
+        Some(text) => {
+            let start = time.parse_local(text)?;
+            Ok(Arc::new(SimulatedClock::new(start, speed)))
+        }
+        None => Ok(Arc::new(SystemClock)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::local_time::ClockFormat;
+    use chrono::TimeZone;
+
+    fn new_york() -> TimeSettings {
+        TimeSettings { timezone: chrono_tz::America::New_York, clock_format: ClockFormat::TwelveHour }
+    }
+
+    #[test]
+    fn frozen_clock_only_moves_when_told() {
+        let start = Utc.with_ymd_and_hms(1994, 10, 17, 23, 30, 0).unwrap();
+        let clock = SimulatedClock::frozen(start);
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert_eq!(clock.now(), start);
+
+        clock.advance(Duration::minutes(90));
+        assert_eq!(clock.now(), start + Duration::minutes(90));
+
+        let later = Utc.with_ymd_and_hms(1994, 12, 25, 12, 0, 0).unwrap();
+        clock.set(later);
+        assert_eq!(clock.now(), later);
+    }
+
+    #[test]
+    fn running_clock_moves_forward_from_its_start() {
+        let start = Utc.with_ymd_and_hms(1994, 10, 17, 23, 30, 0).unwrap();
+        let clock = SimulatedClock::new(start, 60.0);
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        assert!(clock.now() > start);
+    }
+
+    #[test]
+    fn options_start_at_local_time() {
+        let clock = from_options(&new_york(), Some("1994-10-17T19:30"), 0.0).unwrap();
+        // 7:30 PM EDT
+        assert_eq!(clock.now(), Utc.with_ymd_and_hms(1994, 10, 17, 23, 30, 0).unwrap());
+
+        assert!(from_options(&new_york(), Some("next tuesday"), 0.0).is_err());
+        assert!(from_options(&new_york(), None, -1.0).is_err());
+        assert!(from_options(&new_york(), None, f64::NAN).is_err());
+        assert!(from_options(&new_york(), None, MAX_SPEED).is_ok());
+        assert!(from_options(&new_york(), None, MAX_SPEED * 2.0).is_err());
+        assert!(from_options(&new_york(), None, f64::INFINITY).is_err());
+    }
+
+    #[test]
+    fn clocks_stop_at_the_end_of_time_instead_of_overflowing() {
+        let clock = SimulatedClock::new(DateTime::<Utc>::MAX_UTC - Duration::seconds(1), f64::MAX);
+        assert_eq!(clock.speed(), MAX_SPEED);
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert_eq!(clock.now(), DateTime::<Utc>::MAX_UTC);
+
+        let clock = SimulatedClock::frozen(DateTime::<Utc>::MAX_UTC - Duration::days(1));
+        clock.advance(Duration::days(2));
+        assert_eq!(clock.now(), DateTime::<Utc>::MAX_UTC);
+        clock.set(DateTime::<Utc>::MIN_UTC + Duration::days(1));
+        clock.advance(Duration::days(-2));
+        assert_eq!(clock.now(), DateTime::<Utc>::MIN_UTC);
+    }
+}
@@ -0,0 +1,454 @@
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::auth::AuthConfig;
+use crate::channels::DEFAULT_CHANNEL_MAP;
+use crate::history::HistorySettings;
+use crate::parental::ParentalConfig;
+use crate::queue::DEFAULT_QUEUE_FILE;
+use crate::storage::StorageSettings;
+use crate::trash::{Trash, TRASH_DIR_NAME};
+use crate::subscriptions::DEFAULT_SUBSCRIPTIONS_FILE;
+use crate::local_time::{ClockFormat, TimeSettings, CLOCK_FORMAT_ENV, TIMEZONE_ENV};
+
+// Default location of the configuration file, relative to the working directory
+pub const DEFAULT_CONFIG_PATH: &str = "./channel-surfer.json";
+
+// Environment variables read by Config::load, on top of the time settings' own
+pub const CONFIG_ENV: &str = "CHANNEL_SURFER_CONFIG";
+pub const LIBRARY_ENV: &str = "CHANNEL_SURFER_LIBRARY";
+pub const BIND_ENV: &str = "CHANNEL_SURFER_BIND";
+pub const PORT_ENV: &str = "CHANNEL_SURFER_PORT";
+pub const PLAYER_ENV: &str = "CHANNEL_SURFER_PLAYER";
+pub const CHANNEL_MAP_ENV: &str = "CHANNEL_SURFER_CHANNEL_MAP";
+pub const DOWNLOAD_DIR_ENV: &str = "CHANNEL_SURFER_DOWNLOAD_DIR";
+
+// Settings shared by the server and the menu. Values come from the config
+// file, then CHANNEL_SURFER_* environment variables, then command-line flags,
+// each overriding the one before.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    // Directories scanned for videos and sidecars
+    pub library_roots: Vec<PathBuf>,
+    pub static_dir: PathBuf,
+    pub bind_address: IpAddr,
+    pub port: u16,
+    // mpv, or a drop-in replacement that speaks its JSON IPC protocol
+    pub player: String,
+    // IANA name; the host's timezone when unset
+    pub timezone: Option<String>,
+    pub clock_format: Option<ClockFormat>,
+    pub channel_map: PathBuf,
+    // Messages buffered for each SSE subscriber before a slow one starts missing them
+    pub event_capacity: usize,
+    // Port of the Vite development server the menu starts
+    pub dev_server_port: u16,
+    pub downloads: DownloadSettings,
+    pub storage: StorageSettings,
+    pub auth: AuthConfig,
+    pub parental: ParentalConfig,
+    pub history: HistorySettings,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct DownloadSettings {
+    // Where new downloads are saved; the first library root when unset
+    pub directory: Option<PathBuf>,
+    // Downloads allowed to run at once; the rest wait their turn
+    pub max_concurrent: usize,
+    // Saved searches rerun by the menu to download new items
+    pub subscriptions_file: PathBuf,
+    // Downloads queued by imports, kept until they finish
+    pub queue_file: PathBuf,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            library_roots: vec![PathBuf::from("./videos")],
+            static_dir: PathBuf::from("./static"),
+            bind_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            port: 3030,
+            player: "mpv".to_string(),
+            timezone: None,
+            clock_format: None,
+            channel_map: PathBuf::from(DEFAULT_CHANNEL_MAP),
+            event_capacity: 10,
+            dev_server_port: 5173,
+            downloads: DownloadSettings::default(),
+            storage: StorageSettings::default(),
+            auth: AuthConfig::default(),
+            parental: ParentalConfig::default(),
+            history: HistorySettings::default(),
+        }
+    }
+}
+
+impl Default for DownloadSettings {
+    fn default() -> Self {
+        Self {
+            directory: None,
+            max_concurrent: 2,
+            subscriptions_file: PathBuf::from(DEFAULT_SUBSCRIPTIONS_FILE),
+            queue_file: PathBuf::from(DEFAULT_QUEUE_FILE),
+        }
+    }
+}
+
+// Command-line flags that override the config file and environment
+#[derive(clap::Args, Debug, Clone, Default)]
+pub struct ConfigArgs {
+    /// Configuration file [default: ./channel-surfer.json]
+    #[arg(long = "config", global = true)]
+    pub config_path: Option<PathBuf>,
+
+    /// Video library directory; repeat for several
+    #[arg(long = "library", global = true)]
+    pub library_roots: Vec<PathBuf>,
+
+    /// Player command, e.g. "mpv" or a full path
+    #[arg(long, global = true)]
+    pub player: Option<String>,
+
+    /// IANA timezone the schedule is laid out in, e.g. "America/New_York"
+    #[arg(long, global = true)]
+    pub timezone: Option<String>,
+
+    /// Channel map file
+    #[arg(long, global = true)]
+    pub channel_map: Option<PathBuf>,
+
+    // Set from the server's own --bind and --port flags
+    #[arg(skip)]
+    pub bind_address: Option<IpAddr>,
+    #[arg(skip)]
+    pub port: Option<u16>,
+}
+
+impl ConfigArgs {
+    // The config file to read: --config, then CHANNEL_SURFER_CONFIG, then the default
+    pub fn path(&self) -> PathBuf {
+        self.config_path
+            .clone()
+            .or_else(|| std::env::var_os(CONFIG_ENV).map(PathBuf::from))
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_PATH))
+    }
+
+    fn apply(&self, config: &mut Config) {
+        if !self.library_roots.is_empty() {
+            config.library_roots = self.library_roots.clone();
+        }
+        if let Some(player) = &self.player {
+            config.player = player.clone();
+        }
+        if let Some(timezone) = &self.timezone {
+            config.timezone = Some(timezone.clone());
+        }
+        if let Some(channel_map) = &self.channel_map {
+            config.channel_map = channel_map.clone();
+        }
+        if let Some(bind_address) = self.bind_address {
+            config.bind_address = bind_address;
+        }
+        if let Some(port) = self.port {
+            config.port = port;
+        }
+    }
+}
+
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.trim().is_empty())
+}
+
+impl Config {
+    // Load the file, environment and flags. A missing default file is fine;
+    // a missing file that was asked for by name is not.
+    pub fn load(args: &ConfigArgs) -> Result<Self> {
+        let path = args.path();
+        let mut config = if path.exists() {
+            let content = fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read config {}", path.display()))?;
+            serde_json::from_str(&content)
+                .with_context(|| format!("Failed to parse config {}", path.display()))?
+        } else if args.config_path.is_some() || std::env::var_os(CONFIG_ENV).is_some() {
+            bail!("Config file {} does not exist", path.display());
+        } else {
+            Config::default()
+        };
+
+        config.apply_env()?;
+        args.apply(&mut config);
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn apply_env(&mut self) -> Result<()> {
+        if let Some(roots) = std::env::var_os(LIBRARY_ENV).filter(|roots| !roots.is_empty()) {
+            self.library_roots = std::env::split_paths(&roots).collect();
+        }
+        if let Some(bind) = env_var(BIND_ENV) {
+            self.bind_address = bind.parse().map_err(|_| anyhow!("{}: '{}' is not an IP address", BIND_ENV, bind))?;
+        }
+        if let Some(port) = env_var(PORT_ENV) {
+            self.port = port.parse().map_err(|_| anyhow!("{}: '{}' is not a port number", PORT_ENV, port))?;
+        }
+        if let Some(player) = env_var(PLAYER_ENV) {
+            self.player = player;
+        }
+        if let Some(timezone) = env_var(TIMEZONE_ENV) {
+            self.timezone = Some(timezone);
+        }
+        if let Some(format) = env_var(CLOCK_FORMAT_ENV) {
+            self.clock_format = Some(format.parse().map_err(|e: String| anyhow!("{}: {}", CLOCK_FORMAT_ENV, e))?);
+        }
+        if let Some(channel_map) = env_var(CHANNEL_MAP_ENV) {
+            self.channel_map = PathBuf::from(channel_map);
+        }
+        if let Some(directory) = env_var(DOWNLOAD_DIR_ENV) {
+            self.downloads.directory = Some(PathBuf::from(directory));
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.library_roots.is_empty() {
+            bail!("at least one library root is required");
+        }
+        if self.player.trim().is_empty() {
+            bail!("player must not be empty");
+        }
+        if self.event_capacity == 0 {
+            bail!("event_capacity must be at least 1");
+        }
+        if self.downloads.max_concurrent == 0 {
+            bail!("downloads.max_concurrent must be at least 1");
+        }
+        self.auth.validate().map_err(|e| anyhow!(e))?;
+        self.parental.validate().map_err(|e| anyhow!(e))?;
+        self.time_settings()?;
+        Ok(())
+    }
+
+    pub fn time_settings(&self) -> Result<TimeSettings> {
+        let mut time = TimeSettings::default();
+        if let Some(name) = &self.timezone {
+            time.timezone = name.parse().map_err(|_| anyhow!("unknown timezone '{}'", name))?;
+        }
+        if let Some(format) = self.clock_format {
+            time.clock_format = format;
+        }
+        Ok(time)
+    }
+
+    pub fn download_dir(&self) -> &Path {
+        self.downloads.directory.as_deref().unwrap_or(&self.library_roots[0])
+    }
+
+    pub fn trash(&self) -> Trash {
+        Trash::new(self.storage.trash_directory.clone().unwrap_or_else(|| self.download_dir().join(TRASH_DIR_NAME)))
+    }
+
+    // Settings that only take effect when the program starts
+    fn restart_required(&self, other: &Config) -> Vec<&'static str> {
+        let mut fields = Vec::new();
+        if self.bind_address != other.bind_address {
+            fields.push("bind_address");
+        }
+        if self.port != other.port {
+            fields.push("port");
+        }
+        if self.static_dir != other.static_dir {
+            fields.push("static_dir");
+        }
+        if self.event_capacity != other.event_capacity {
+            fields.push("event_capacity");
+        }
+        if self.downloads.max_concurrent != other.downloads.max_concurrent {
+            fields.push("downloads.max_concurrent");
+        }
+        fields
+    }
+
+    fn keep_startup_settings(&mut self, running: &Config) {
+        self.bind_address = running.bind_address;
+        self.port = running.port;
+        self.static_dir = running.static_dir.clone();
+        self.event_capacity = running.event_capacity;
+        self.downloads.max_concurrent = running.downloads.max_concurrent;
+    }
+}
+
+// A configuration change picked up from disk
+pub struct Reload {
+    pub config: Config,
+    // Settings that changed on disk but keep their old value until a restart
+    pub restart_required: Vec<&'static str>,
+}
+
+// Keeps the configuration current by checking the config file and channel map
+// for changes. Everything except the listening address, static files, SSE
+// buffer size and download concurrency is applied without a restart, so
+// revoking a token or changing a password takes effect straight away.
+pub struct ConfigWatcher {
+    args: ConfigArgs,
+    config: Config,
+    // As last read, before startup settings were put back, so a pending
+    // restart is only reported when those settings change again
+    loaded: Config,
+    modified: Vec<Option<SystemTime>>,
+}
+
+fn modified_times(args: &ConfigArgs, config: &Config) -> Vec<Option<SystemTime>> {
+    [args.path(), config.channel_map.clone()]
+        .iter()
+        .map(|path| fs::metadata(path).and_then(|metadata| metadata.modified()).ok())
+        .collect()
+}
+
+impl ConfigWatcher {
+    pub fn new(args: ConfigArgs) -> Result<Self> {
+        let config = Config::load(&args)?;
+        let modified = modified_times(&args, &config);
+        Ok(Self { args, loaded: config.clone(), config, modified })
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    // Reload if either file changed since the last check. A broken file is
+    // reported once and the running configuration kept.
+    pub fn poll(&mut self) -> Option<Result<Reload>> {
+        let modified = modified_times(&self.args, &self.config);
+        if modified == self.modified {
+            return None;
+        }
+        self.modified = modified;
+
+        let mut config = match Config::load(&self.args) {
+            Ok(config) => config,
+            Err(e) => return Some(Err(e)),
+        };
+        // Changed since the last read, and still different from what is running
+        let pending = self.config.restart_required(&config);
+        let restart_required = self.loaded.restart_required(&config).into_iter().filter(|field| pending.contains(field)).collect();
+        self.loaded = config.clone();
+        config.keep_startup_settings(&self.config);
+        // A new channel map path has its own modification time to track
+        self.modified = modified_times(&self.args, &config);
+        self.config = config.clone();
+        Some(Ok(Reload { config, restart_required }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    // Tests that read CHANNEL_SURFER_* variables take turns, since the environment is shared
+    static ENV: Mutex<()> = Mutex::new(());
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // Write a file and move its modification time on, so a watcher sees it change
+    fn write(path: &Path, content: &str, tick: u64) {
+        fs::write(path, content).unwrap();
+        let time = SystemTime::UNIX_EPOCH + Duration::from_secs(800_000_000 + tick);
+        fs::File::options().write(true).open(path).unwrap().set_modified(time).unwrap();
+    }
+
+    fn args(path: &Path) -> ConfigArgs {
+        ConfigArgs { config_path: Some(path.to_path_buf()), ..Default::default() }
+    }
+
+    #[test]
+    fn flags_override_environment_which_overrides_the_file() {
+        let _env = ENV.lock().unwrap();
+        let dir = temp_dir("config-precedence");
+        let path = dir.join("channel-surfer.json");
+        write(
+            &path,
+            r#"{"library_roots": ["/file/videos"], "player": "file-player", "port": 4000, "timezone": "America/Chicago"}"#,
+            0,
+        );
+
+        std::env::set_var(PLAYER_ENV, "env-player");
+        std::env::set_var(PORT_ENV, "5000");
+        let flags = ConfigArgs { player: Some("flag-player".to_string()), ..args(&path) };
+        let config = Config::load(&flags);
+        std::env::set_var(PORT_ENV, "not-a-port");
+        let bad_port = Config::load(&flags);
+        std::env::remove_var(PLAYER_ENV);
+        std::env::remove_var(PORT_ENV);
+
+        let config = config.unwrap();
+        assert_eq!(config.player, "flag-player");
+        assert_eq!(config.port, 5000);
+        assert_eq!(config.timezone.as_deref(), Some("America/Chicago"));
+        assert_eq!(config.library_roots, vec![PathBuf::from("/file/videos")]);
+        assert_eq!(config.event_capacity, Config::default().event_capacity);
+        assert!(bad_port.is_err());
+
+        assert!(Config::load(&args(&dir.join("missing.json"))).is_err());
+        write(&path, r#"{"players": "typo"}"#, 1);
+        assert!(Config::load(&args(&path)).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn reloads_apply_changes_and_report_restarts_once() {
+        let _env = ENV.lock().unwrap();
+        let dir = temp_dir("config-reload");
+        let path = dir.join("channel-surfer.json");
+        let channel_map = dir.join("channels.json");
+        let content = |player: &str, port: u16| {
+            format!(r#"{{"player": "{}", "port": {}, "channel_map": "{}"}}"#, player, port, channel_map.display())
+        };
+        write(&path, &content("mpv", 4000), 0);
+        let mut watcher = ConfigWatcher::new(args(&path)).unwrap();
+        assert!(watcher.poll().is_none());
+
+        write(&path, &content("vlc", 4001), 1);
+        let reload = watcher.poll().unwrap().unwrap();
+        assert_eq!(reload.config.player, "vlc");
+        assert_eq!(reload.config.port, 4000);
+        assert_eq!(reload.restart_required, vec!["port"]);
+        assert!(watcher.poll().is_none());
+
+        // The port is still waiting for a restart, but that was already reported
+        write(&path, &content("mplayer", 4001), 2);
+        let reload = watcher.poll().unwrap().unwrap();
+        assert_eq!((reload.config.player.as_str(), reload.config.port), ("mplayer", 4000));
+        assert!(reload.restart_required.is_empty());
+
+        // Putting the port back needs no restart
+        write(&path, &content("mplayer", 4000), 3);
+        assert!(watcher.poll().unwrap().unwrap().restart_required.is_empty());
+
+        // A new channel map is noticed as well
+        write(&channel_map, "{}", 4);
+        assert!(watcher.poll().unwrap().unwrap().restart_required.is_empty());
+
+        // A broken file keeps the running configuration
+        write(&path, "{", 5);
+        assert!(watcher.poll().unwrap().is_err());
+        assert_eq!(watcher.config().player, "mplayer");
+        assert!(watcher.poll().is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,262 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sha1::{Digest, Sha1};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::library::LibraryItem;
+
+// Points through a video, as fractions of its length, where fingerprint frames are taken
+const FINGERPRINT_POINTS: [f64; 5] = [0.1, 0.3, 0.5, 0.7, 0.9];
+
+// Differing bits allowed per 64-bit frame hash for two frames to count as the same picture
+const FRAME_DISTANCE: u32 = 10;
+
+// Lengths further apart than this fraction can't be the same recording
+const DURATION_TOLERANCE: f64 = 0.02;
+
+// Why programs were grouped as duplicates, strongest first
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum DuplicateReason {
+    // Downloaded from the same Internet Archive item
+    SameIdentifier,
+    // Byte-for-byte the same file
+    SameContent,
+    // The same picture at the same points, e.g. two items mirroring one tape
+    LooksAlike,
+}
+
+impl fmt::Display for DuplicateReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DuplicateReason::SameIdentifier => "same Internet Archive item",
+            DuplicateReason::SameContent => "identical files",
+            DuplicateReason::LooksAlike => "same picture",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct DuplicateFile {
+    pub video_path: PathBuf,
+    pub title: String,
+    pub original_id: String,
+    pub download_date: DateTime<Utc>,
+}
+
+// Programs that are copies of each other. The first, downloaded earliest, is the one to keep.
+#[derive(Serialize, Debug, Clone)]
+pub struct DuplicateGroup {
+    pub reason: DuplicateReason,
+    pub files: Vec<DuplicateFile>,
+}
+
+impl DuplicateGroup {
+    // Everything except the copy to keep
+    pub fn extras(&self) -> &[DuplicateFile] {
+        &self.files[1..]
+    }
+}
+
+// SHA-1 of a file's contents, as lowercase hex
+pub fn content_sha1(path: &Path) -> Result<String> {
+    let mut file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let mut hasher = Sha1::new();
+    let mut buffer = vec![0; 1024 * 1024];
+    loop {
+        let read = file.read(&mut buffer).with_context(|| format!("Failed to read {}", path.display()))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+// A perceptual fingerprint: an average hash of a tiny grayscale frame at each
+// of FINGERPRINT_POINTS, so re-encodes of the same recording still match
+#[derive(Debug, Clone)]
+pub struct Fingerprint {
+    pub duration_seconds: u32,
+    pub frames: Vec<u64>,
+}
+
+// Make sure ffmpeg runs before fingerprinting a whole library with it
+pub fn check_ffmpeg(ffmpeg: &str) -> Result<()> {
+    let output = Command::new(ffmpeg)
+        .arg("-version")
+        .output()
+        .with_context(|| format!("Failed to run {}; fingerprints need ffmpeg", ffmpeg))?;
+    if !output.status.success() {
+        bail!("{} -version failed; fingerprints need ffmpeg", ffmpeg);
+    }
+    Ok(())
+}
+
+impl Fingerprint {
+    // Take the frames with ffmpeg, which has to be installed
+    pub fn take(ffmpeg: &str, path: &Path, duration_seconds: u32) -> Result<Self> {
+        let mut frames = Vec::new();
+        for point in FINGERPRINT_POINTS {
+            let at = format!("{:.1}", duration_seconds as f64 * point);
+            let output = Command::new(ffmpeg)
+                .args(["-v", "error", "-ss", &at, "-i"])
+                .arg(path)
+                .args(["-frames:v", "1", "-vf", "scale=8:8,format=gray", "-f", "rawvideo", "-"])
+                .output()
+                .with_context(|| format!("Failed to run {}", ffmpeg))?;
+            if !output.status.success() || output.stdout.len() != 64 {
+                bail!("{} could not read a frame of {}: {}", ffmpeg, path.display(), String::from_utf8_lossy(&output.stderr).trim());
+            }
+            let mean = output.stdout.iter().map(|&pixel| pixel as u32).sum::<u32>() / 64;
+            let hash = output.stdout.iter().enumerate().filter(|(_, &pixel)| pixel as u32 > mean).fold(0u64, |hash, (i, _)| hash | 1 << i);
+            frames.push(hash);
+        }
+        Ok(Self { duration_seconds, frames })
+    }
+
+    pub fn looks_like(&self, other: &Fingerprint) -> bool {
+        let longer = self.duration_seconds.max(other.duration_seconds) as f64;
+        let difference = self.duration_seconds.abs_diff(other.duration_seconds) as f64;
+        difference <= longer * DURATION_TOLERANCE
+            && self.frames.len() == other.frames.len()
+            && self.frames.iter().zip(&other.frames).all(|(a, b)| (a ^ b).count_ones() <= FRAME_DISTANCE)
+    }
+}
+
+fn duplicate_file(item: &LibraryItem) -> DuplicateFile {
+    DuplicateFile {
+        video_path: item.video_path.clone(),
+        title: item.metadata.title.clone(),
+        original_id: item.metadata.original_id.clone(),
+        download_date: item.metadata.download_date,
+    }
+}
+
+// Group items that are copies of each other: same original_id, same
+// content_sha1 in their sidecars, or matching fingerprints when given.
+// Each item lands in at most one group, under the strongest reason.
+pub fn find_duplicates(items: &[LibraryItem], fingerprints: &HashMap<PathBuf, Fingerprint>) -> Vec<DuplicateGroup> {
+    // Group number of each item, by index
+    let mut grouped: Vec<Option<usize>> = vec![None; items.len()];
+    let mut groups: Vec<(DuplicateReason, Vec<usize>)> = Vec::new();
+
+    let mut add = |reason: DuplicateReason, members: Vec<usize>, grouped: &mut Vec<Option<usize>>| {
+        let members: Vec<usize> = members.into_iter().filter(|&i| grouped[i].is_none()).collect();
+        if members.len() > 1 {
+            for &i in &members {
+                grouped[i] = Some(groups.len());
+            }
+            groups.push((reason, members));
+        }
+    };
+
+    let mut by_id: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    let mut by_hash: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    for (i, item) in items.iter().enumerate() {
+        if !item.metadata.original_id.is_empty() {
+            by_id.entry(item.metadata.original_id.as_str()).or_default().push(i);
+        }
+        if let Some(hash) = &item.metadata.content_sha1 {
+            by_hash.entry(hash.as_str()).or_default().push(i);
+        }
+    }
+    for members in by_id.into_values() {
+        add(DuplicateReason::SameIdentifier, members, &mut grouped);
+    }
+    for members in by_hash.into_values() {
+        add(DuplicateReason::SameContent, members, &mut grouped);
+    }
+    for i in 0..items.len() {
+        let Some(fingerprint) = fingerprints.get(&items[i].video_path) else { continue };
+        let members: Vec<usize> = std::iter::once(i)
+            .chain((i + 1..items.len()).filter(|&j| fingerprints.get(&items[j].video_path).is_some_and(|other| fingerprint.looks_like(other))))
+            .collect();
+        add(DuplicateReason::LooksAlike, members, &mut grouped);
+    }
+
+    groups
+        .into_iter()
+        .map(|(reason, members)| {
+            let mut files: Vec<DuplicateFile> = members.into_iter().map(|i| duplicate_file(&items[i])).collect();
+            files.sort_by(|a, b| (a.download_date, &a.video_path).cmp(&(b.download_date, &b.video_path)));
+            DuplicateGroup { reason, files }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sidecar::test_program;
+    use chrono::{Duration, TimeZone};
+
+    // An item downloaded `day` days after the first
+    fn item(file: &str, original_id: &str, sha1: Option<&str>, day: i64) -> LibraryItem {
+        let mut metadata = test_program(original_id, "TV Show", 3, "0:30:00");
+        metadata.content_sha1 = sha1.map(str::to_string);
+        metadata.download_date = Utc.timestamp_opt(1_700_000_000, 0).unwrap() + Duration::days(day);
+        LibraryItem {
+            video_path: PathBuf::from(format!("/videos/{}.mp4", file)),
+            sidecar_path: PathBuf::from(format!("/videos/{}.json", file)),
+            metadata,
+        }
+    }
+
+    fn files(group: &DuplicateGroup) -> Vec<&str> {
+        group.files.iter().map(|file| file.video_path.file_stem().unwrap().to_str().unwrap()).collect()
+    }
+
+    #[test]
+    fn hashes_file_contents() {
+        let path = std::env::temp_dir().join(format!("dedupe-sha1-{}.mp4", std::process::id()));
+        std::fs::write(&path, b"abc").unwrap();
+        assert_eq!(content_sha1(&path).unwrap(), "a9993e364706816aba3e25717850c26c9cd0d89d");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn groups_by_identifier_then_content_keeping_the_earliest_download() {
+        let items = vec![
+            item("news-copy", "news", Some("aaa"), 2),
+            item("news", "news", None, 0),
+            item("mirror", "mirror", Some("aaa"), 1),
+            item("other-mirror", "other-mirror", Some("aaa"), 3),
+            item("unique", "unique", Some("bbb"), 0),
+        ];
+        let groups = find_duplicates(&items, &HashMap::new());
+        assert_eq!(groups.len(), 2);
+
+        assert_eq!(groups[0].reason, DuplicateReason::SameIdentifier);
+        assert_eq!(files(&groups[0]), ["news", "news-copy"]);
+        assert_eq!(groups[0].extras().len(), 1);
+
+        // news-copy shares the hash too, but is already grouped under the stronger reason
+        assert_eq!(groups[1].reason, DuplicateReason::SameContent);
+        assert_eq!(files(&groups[1]), ["mirror", "other-mirror"]);
+    }
+
+    #[test]
+    fn groups_matching_fingerprints_of_similar_length() {
+        let items = vec![item("tape", "tape", None, 0), item("reencode", "reencode", None, 1), item("longer", "longer", None, 2)];
+        let fingerprint = |duration_seconds, flipped_bits: u64| Fingerprint { duration_seconds, frames: vec![0xF0F0 ^ flipped_bits; 5] };
+        let fingerprints = HashMap::from([
+            (items[0].video_path.clone(), fingerprint(1800, 0)),
+            (items[1].video_path.clone(), fingerprint(1810, 0b111)),
+            (items[2].video_path.clone(), fingerprint(2000, 0)),
+        ]);
+        let groups = find_duplicates(&items, &fingerprints);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].reason, DuplicateReason::LooksAlike);
+        assert_eq!(files(&groups[0]), ["tape", "reencode"]);
+
+        assert!(!fingerprint(1800, 0).looks_like(&fingerprint(1800, (1 << (FRAME_DISTANCE + 1)) - 1)));
+    }
+}
@@ -0,0 +1,539 @@
+use chrono::{DateTime, Duration, SubsecRound, Timelike, Utc};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use crossterm::{cursor, execute};
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{self, Stdout};
+use tui::backend::CrosstermBackend;
+use tui::buffer::Buffer;
+use tui::layout::{Constraint, Direction, Layout, Rect};
+use tui::style::{Color, Modifier, Style};
+use tui::text::{Line, Span};
+use tui::widgets::{Block, Borders, Paragraph, Wrap};
+use tui::{Frame, Terminal};
+
+use crate::clock::Clock;
+use crate::library::LibraryItem;
+use crate::local_time::TimeSettings;
+use crate::schedule::{Slot, SlotKind, WeeklySchedule};
+use crate::sidecar::TvGuideMetadata;
+
+const HALF_HOUR: i64 = 30 * 60;
+const CHANNEL_COLUMN_WIDTH: u16 = 12;
+// Narrowest a half-hour column gets before fewer columns are shown
+const MIN_COLUMN_WIDTH: u16 = 20;
+const MAX_COLUMNS: u16 = 6;
+// Each channel row is two lines of text with a blank line below
+const ROW_HEIGHT: u16 = 3;
+const CELL_HEIGHT: u16 = 2;
+const DETAIL_HEIGHT: u16 = 8;
+
+// The classic cable guide channel look
+pub const GUIDE_STYLE: Style = Style::new().fg(Color::Yellow).bg(Color::Blue);
+pub const CHANNEL_STYLE: Style = Style::new().fg(Color::White).bg(Color::Magenta);
+pub const SELECTED_STYLE: Style = Style::new().fg(Color::Blue).bg(Color::Yellow);
+pub const WARNING_STYLE: Style = Style::new().fg(Color::Red);
+
+// One airing of a slot, placed on the real timeline
+struct Airing<'a> {
+    slot: &'a Slot,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+}
+
+// Full-screen, scrolling grid of channels against half-hour columns
+pub struct Guide {
+    schedule: WeeklySchedule,
+    channels: Vec<u8>,
+    callsigns: BTreeMap<u8, String>,
+    programs: HashMap<String, TvGuideMetadata>,
+    // original_ids of programs in the watch history
+    watched: HashSet<String>,
+    time: TimeSettings,
+    notices: Vec<(String, bool)>,
+    selected_channel: usize,
+    top_channel: usize,
+    // The selected program is whichever airs on the selected channel at this instant
+    cursor: DateTime<Utc>,
+    // Left edge of the grid, always on a half hour
+    view_start: DateTime<Utc>,
+    // Size of the grid at the last draw, used for scrolling and paging
+    columns: i64,
+    visible_rows: usize,
+}
+
+impl Guide {
+    pub fn new(
+        schedule: WeeklySchedule,
+        items: &[LibraryItem],
+        callsigns: BTreeMap<u8, String>,
+        time: TimeSettings,
+        now: DateTime<Utc>,
+    ) -> Self {
+        let now = now.trunc_subsecs(0);
+        let channels = schedule.channels.keys().copied().collect();
+        let programs = items
+            .iter()
+            .map(|item| (item.metadata.original_id.clone(), item.metadata.clone()))
+            .collect();
+        let mut guide = Self {
+            schedule,
+            channels,
+            callsigns,
+            programs,
+            watched: HashSet::new(),
+            time,
+            notices: Vec::new(),
+            selected_channel: 0,
+            top_channel: 0,
+            cursor: now,
+            view_start: now,
+            columns: 3,
+            visible_rows: 1,
+        };
+        guide.view_start = guide.floor_half_hour(now);
+        guide
+    }
+
+    // Mark these programs as already watched
+    pub fn set_watched(&mut self, watched: HashSet<String>) {
+        self.watched = watched;
+    }
+
+    // A line for the status bar; warnings are shown in red
+    pub fn add_notice(&mut self, text: impl Into<String>, warning: bool) {
+        self.notices.push((text.into(), warning));
+    }
+
+    pub fn selected_channel(&self) -> Option<u8> {
+        self.channels.get(self.selected_channel).copied()
+    }
+
+    fn view_end(&self) -> DateTime<Utc> {
+        self.view_start + Duration::seconds(HALF_HOUR * self.columns)
+    }
+
+    fn floor_half_hour(&self, instant: DateTime<Utc>) -> DateTime<Utc> {
+        let local = self.time.local(instant);
+        instant - Duration::seconds(((local.minute() % 30) * 60 + local.second()) as i64)
+    }
+
+    fn airing_at(&self, channel: u8, at: DateTime<Utc>) -> Option<Airing<'_>> {
+        let slot = self.schedule.slot_at(channel, self.time.second_of_week(at))?;
+        let start = self.time.airing_start(slot.start_second, slot.length_seconds, at).with_timezone(&Utc);
+        Some(Airing {
+            slot,
+            start,
+            end: start + Duration::seconds(slot.length_seconds as i64),
+        })
+    }
+
+    fn selected_airing(&self) -> Option<Airing<'_>> {
+        self.airing_at(self.selected_channel()?, self.cursor)
+    }
+
+    // Handle a key press; returns false when the guide should close
+    pub fn handle_key(&mut self, key: KeyCode, now: DateTime<Utc>) -> bool {
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Up => self.select_channel(self.selected_channel.saturating_sub(1)),
+            KeyCode::Down => self.select_channel(self.selected_channel + 1),
+            KeyCode::PageUp => self.select_channel(self.selected_channel.saturating_sub(self.visible_rows)),
+            KeyCode::PageDown => self.select_channel(self.selected_channel + self.visible_rows),
+            KeyCode::Left => self.previous_program(),
+            KeyCode::Right => self.next_program(),
+            KeyCode::Home | KeyCode::Char('n') => {
+                self.cursor = now.trunc_subsecs(0);
+                self.view_start = self.floor_half_hour(self.cursor);
+            }
+            _ => {}
+        }
+        true
+    }
+
+    fn select_channel(&mut self, index: usize) {
+        if self.channels.is_empty() {
+            return;
+        }
+        self.selected_channel = index.min(self.channels.len() - 1);
+        if self.selected_channel < self.top_channel {
+            self.top_channel = self.selected_channel;
+        } else if self.selected_channel >= self.top_channel + self.visible_rows {
+            self.top_channel = self.selected_channel + 1 - self.visible_rows;
+        }
+    }
+
+    // Long programs scroll a half hour at a time before the selection moves on
+    fn next_program(&mut self) {
+        let end = match self.selected_airing() {
+            Some(airing) => airing.end,
+            None => self.cursor + Duration::seconds(HALF_HOUR),
+        };
+        if end <= self.view_end() {
+            self.cursor = end;
+        } else {
+            self.view_start += Duration::seconds(HALF_HOUR);
+            self.cursor = self.cursor.max(self.view_start);
+        }
+        self.keep_cursor_visible();
+    }
+
+    fn previous_program(&mut self) {
+        let start = match self.selected_airing() {
+            Some(airing) => airing.start,
+            None => self.cursor - Duration::seconds(HALF_HOUR),
+        };
+        if start < self.view_start {
+            self.view_start -= Duration::seconds(HALF_HOUR);
+            self.cursor = start.max(self.view_start);
+        } else {
+            let target = start - Duration::seconds(1);
+            if target < self.view_start {
+                self.view_start -= Duration::seconds(HALF_HOUR);
+            }
+            let previous = self
+                .selected_channel()
+                .and_then(|channel| self.airing_at(channel, target))
+                .map(|airing| airing.start)
+                .unwrap_or(target);
+            self.cursor = previous.max(self.view_start);
+        }
+        self.keep_cursor_visible();
+    }
+
+    fn keep_cursor_visible(&mut self) {
+        if self.cursor < self.view_start {
+            self.view_start = self.floor_half_hour(self.cursor);
+        }
+        while self.cursor >= self.view_end() {
+            self.view_start += Duration::seconds(HALF_HOUR);
+        }
+    }
+
+    pub fn draw(&mut self, frame: &mut Frame, now: DateTime<Utc>) {
+        let notice_lines = self.notices.len().min(3) as u16;
+        let areas = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(1),
+                Constraint::Length(1),
+                Constraint::Min(ROW_HEIGHT),
+                Constraint::Length(DETAIL_HEIGHT),
+                Constraint::Length(1 + notice_lines),
+            ])
+            .split(frame.size());
+
+        let grid_width = areas[2].width.saturating_sub(CHANNEL_COLUMN_WIDTH);
+        self.columns = (grid_width / MIN_COLUMN_WIDTH).clamp(1, MAX_COLUMNS) as i64;
+        self.visible_rows = ((areas[2].height / ROW_HEIGHT) as usize).max(1);
+        self.select_channel(self.selected_channel);
+
+        self.draw_header(frame.buffer_mut(), areas[0], now);
+        self.draw_time_bar(frame.buffer_mut(), areas[1]);
+        self.draw_grid(frame.buffer_mut(), areas[2], now);
+        self.draw_detail(frame, areas[3]);
+        self.draw_footer(frame, areas[4]);
+    }
+
+    fn draw_header(&self, buf: &mut Buffer, area: Rect, now: DateTime<Utc>) {
+        buf.set_style(area, GUIDE_STYLE.add_modifier(Modifier::BOLD));
+        buf.set_stringn(area.x + 1, area.y, "TV GUIDE", area.width as usize, GUIDE_STYLE.add_modifier(Modifier::BOLD));
+        let clock = self.time.format_time(&self.time.local(now));
+        let x = area.right().saturating_sub(clock.len() as u16 + 1);
+        buf.set_stringn(x, area.y, &clock, clock.len(), GUIDE_STYLE.add_modifier(Modifier::BOLD));
+    }
+
+    fn draw_time_bar(&self, buf: &mut Buffer, area: Rect) {
+        let style = Style::new().fg(Color::Black).bg(Color::Cyan);
+        buf.set_style(area, style);
+        let day = self.time.local(self.view_start).format("%a %b %-d").to_string();
+        buf.set_stringn(area.x + 1, area.y, &day, (CHANNEL_COLUMN_WIDTH - 1) as usize, style);
+
+        let grid_x = area.x + CHANNEL_COLUMN_WIDTH;
+        let grid_width = area.width.saturating_sub(CHANNEL_COLUMN_WIDTH);
+        for column in 0..self.columns {
+            let start = self.view_start + Duration::seconds(HALF_HOUR * column);
+            let x = grid_x + (column as u16 * grid_width) / self.columns as u16;
+            let label = self.time.format_time(&self.time.local(start));
+            buf.set_stringn(x, area.y, label, MIN_COLUMN_WIDTH as usize, style);
+        }
+    }
+
+    fn draw_grid(&self, buf: &mut Buffer, area: Rect, now: DateTime<Utc>) {
+        if self.channels.is_empty() {
+            buf.set_stringn(area.x + 1, area.y, "No programs are scheduled", area.width as usize, GUIDE_STYLE);
+            return;
+        }
+        let rows = self.channels.iter().enumerate().skip(self.top_channel).take(self.visible_rows);
+        for (row, (index, channel)) in rows.enumerate() {
+            let row_area = Rect::new(area.x, area.y + row as u16 * ROW_HEIGHT, area.width, ROW_HEIGHT);
+            self.draw_row(buf, row_area, index, *channel, now);
+        }
+    }
+
+    fn draw_row(&self, buf: &mut Buffer, area: Rect, index: usize, channel: u8, now: DateTime<Utc>) {
+        let channel_area = Rect::new(area.x, area.y, CHANNEL_COLUMN_WIDTH - 1, CELL_HEIGHT);
+        buf.set_style(channel_area, CHANNEL_STYLE);
+        let width = channel_area.width as usize;
+        let callsign = self.callsigns.get(&channel).map(String::as_str).unwrap_or("");
+        buf.set_stringn(area.x, area.y, format!("{:^width$}", format!("CH {}", channel)), width, CHANNEL_STYLE.add_modifier(Modifier::BOLD));
+        buf.set_stringn(area.x, area.y + 1, format!("{:^width$}", callsign), width, CHANNEL_STYLE);
+
+        let grid_x = area.x + CHANNEL_COLUMN_WIDTH;
+        let grid_width = area.width.saturating_sub(CHANNEL_COLUMN_WIDTH) as i64;
+        let view_end = self.view_end();
+        let window = (view_end - self.view_start).num_seconds();
+        let column_of = |instant: DateTime<Utc>| grid_x + ((instant - self.view_start).num_seconds() * grid_width / window) as u16;
+
+        let mut position = self.view_start;
+        while position < view_end {
+            let Some(airing) = self.airing_at(channel, position).filter(|airing| airing.end > position) else {
+                position = self.floor_half_hour(position) + Duration::seconds(HALF_HOUR);
+                continue;
+            };
+            let left = column_of(airing.start.max(self.view_start));
+            let right = column_of(airing.end.min(view_end));
+            position = airing.end;
+
+            // Leave a one-column gap so neighbouring cells read as separate boxes
+            let cell_width = right.saturating_sub(left).saturating_sub(1);
+            if cell_width == 0 {
+                continue;
+            }
+            let selected = index == self.selected_channel && airing.start <= self.cursor && self.cursor < airing.end;
+            let mut style = if selected { SELECTED_STYLE } else { GUIDE_STYLE };
+            if airing.start <= now && now < airing.end {
+                style = style.add_modifier(Modifier::BOLD);
+            }
+            buf.set_style(Rect::new(left, area.y, cell_width, CELL_HEIGHT), style);
+
+            let continues_left = if airing.start < self.view_start { "◀ " } else { "" };
+            let continues_right = if airing.end > view_end { " ▶" } else { "" };
+            let title = format!("{}{}{}", continues_left, airing.slot.title, continues_right);
+            buf.set_stringn(left, area.y, title, cell_width as usize, style);
+            buf.set_stringn(left, area.y + 1, self.cell_caption(&airing), cell_width as usize, style);
+        }
+    }
+
+    // Second line of a grid cell
+    fn cell_caption(&self, airing: &Airing) -> String {
+        let minutes = airing.slot.length_seconds / 60;
+        match airing.slot.kind {
+            SlotKind::Program => {
+                let marker = if self.watched.contains(&airing.slot.original_id) { "✓ " } else { "" };
+                match self.programs.get(&airing.slot.original_id) {
+                    Some(program) if !program.category.is_empty() => format!("{}{} · {}m", marker, program.category, minutes),
+                    _ => format!("{}{}m", marker, minutes),
+                }
+            }
+            SlotKind::Interstitial => "Commercial break".to_string(),
+            SlotKind::Filler => format!("{}m", minutes),
+            SlotKind::OffAir => "Off the air".to_string(),
+        }
+    }
+
+    fn draw_detail(&self, frame: &mut Frame, area: Rect) {
+        let channel = self.selected_channel();
+        let callsign = channel.and_then(|c| self.callsigns.get(&c)).map(String::as_str).unwrap_or("");
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(format!(" CH {} {} ", channel.map(|c| c.to_string()).unwrap_or_default(), callsign))
+            .style(GUIDE_STYLE);
+
+        let mut lines = Vec::new();
+        if let Some(airing) = self.selected_airing() {
+            let slot = airing.slot;
+            let program = self.programs.get(&slot.original_id).filter(|_| slot.kind == SlotKind::Program);
+
+            let mut heading = vec![Span::styled(slot.title.clone(), Style::new().add_modifier(Modifier::BOLD))];
+            if let Some(program) = program.filter(|p| !p.year.is_empty()) {
+                heading.push(Span::raw(format!(" ({})", program.year)));
+            }
+            if program.is_some() {
+                let watched = if self.watched.contains(&slot.original_id) { "  ✓ Watched" } else { "  • New" };
+                heading.push(Span::raw(watched));
+            }
+            lines.push(Line::from(heading));
+
+            let mut when = format!(
+                "{} - {} · {} min",
+                self.time.format_day_time(&self.time.local(airing.start)),
+                self.time.format_time(&self.time.local(airing.end)),
+                slot.length_seconds / 60
+            );
+            if let Some(program) = program.filter(|p| !p.category.is_empty()) {
+                when.push_str(&format!(" · {}", program.category));
+            }
+            if let Some(rating) = program.and_then(|p| p.rating) {
+                when.push_str(&format!(" · {}", rating));
+            }
+            lines.push(Line::from(when));
+            lines.push(Line::from(""));
+
+            match program {
+                Some(program) => lines.push(Line::from(program.description.clone())),
+                // Off-air periods and breaks are described by what airs during them
+                None => {
+                    let mut titles: Vec<&str> = Vec::new();
+                    for segment in &slot.segments {
+                        if titles.last() != Some(&segment.title.as_str()) {
+                            titles.push(&segment.title);
+                        }
+                    }
+                    lines.push(Line::from(titles.join(" · ")));
+                }
+            }
+        }
+
+        let detail = Paragraph::new(lines).block(block).wrap(Wrap { trim: true });
+        frame.render_widget(detail, area);
+    }
+
+    fn draw_footer(&self, frame: &mut Frame, area: Rect) {
+        let mut lines = vec![Line::from(
+            "←/→ program  ↑/↓ channel  PgUp/PgDn page  Home now  q back",
+        )];
+        for (text, warning) in self.notices.iter().take(3) {
+            let style = if *warning { WARNING_STYLE } else { Style::new() };
+            lines.push(Line::styled(text.clone(), style));
+        }
+        frame.render_widget(Paragraph::new(lines), area);
+    }
+}
+
+// Restores the terminal even if drawing fails part way
+pub struct TerminalGuard;
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = disable_raw_mode();
+        let _ = execute!(io::stdout(), LeaveAlternateScreen, cursor::Show);
+    }
+}
+
+pub type GuideTerminal = Terminal<CrosstermBackend<Stdout>>;
+
+// Switch to a full-screen raw-mode terminal; dropping the guard switches back
+pub fn enter_terminal() -> io::Result<(GuideTerminal, TerminalGuard)> {
+    enable_raw_mode()?;
+    let guard = TerminalGuard;
+    execute!(io::stdout(), EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(io::stdout()))?;
+    terminal.hide_cursor()?;
+    Ok((terminal, guard))
+}
+
+// Show the guide full screen until the viewer backs out
+pub fn run(guide: &mut Guide, clock: &dyn Clock) -> io::Result<()> {
+    let (mut terminal, _guard) = enter_terminal()?;
+
+    loop {
+        let now = clock.now();
+        terminal.draw(|frame| guide.draw(frame, now))?;
+
+        // Wake up regularly so the header clock keeps ticking
+        if event::poll(std::time::Duration::from_millis(500))? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && !guide.handle_key(key.code, now) {
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::local_time::ClockFormat;
+    use crate::sidecar::test_program;
+    use chrono::TimeZone;
+    use std::path::PathBuf;
+    use tui::backend::TestBackend;
+
+    fn slot(start: &str, minutes: u32, original_id: &str, title: &str) -> Slot {
+        Slot {
+            // Monday, so the position in the week is the time of day
+            start_second: crate::channels::parse_clock(start).unwrap() * 60,
+            length_seconds: minutes * 60,
+            kind: SlotKind::Program,
+            original_id: original_id.to_string(),
+            title: title.to_string(),
+            segments: Vec::new(),
+        }
+    }
+
+    fn guide() -> Guide {
+        let mut schedule = WeeklySchedule::default();
+        schedule.channels.insert(3, vec![slot("19:00", 30, "sitcom", "Family Ties"), slot("19:30", 120, "movie", "Tootsie")]);
+        schedule.channels.insert(5, vec![slot("19:00", 60, "news", "Evening News")]);
+        let items: Vec<LibraryItem> = [("sitcom", "Comedy"), ("movie", "Movie"), ("news", "News")]
+            .into_iter()
+            .map(|(id, category)| LibraryItem {
+                video_path: PathBuf::from(format!("/videos/{}.mp4", id)),
+                sidecar_path: PathBuf::from(format!("/videos/{}.json", id)),
+                metadata: test_program(id, category, 3, "30:00"),
+            })
+            .collect();
+        let callsigns = BTreeMap::from([(3, "WEWS".to_string()), (5, "WJW".to_string())]);
+        let time = TimeSettings { timezone: chrono_tz::UTC, clock_format: ClockFormat::TwelveHour };
+        Guide::new(schedule, &items, callsigns, time, now())
+    }
+
+    // A Monday evening
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(1994, 10, 17, 19, 10, 0).unwrap()
+    }
+
+    fn at(hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(1994, 10, 17, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn arrows_move_between_programs_and_channels() {
+        let mut guide = guide();
+        assert_eq!(guide.view_start, at(19, 0));
+
+        assert!(guide.handle_key(KeyCode::Right, now()));
+        assert_eq!(guide.cursor, at(19, 30));
+        // The movie runs past the right edge, so the grid scrolls a half hour
+        guide.handle_key(KeyCode::Right, now());
+        assert_eq!((guide.view_start, guide.cursor), (at(19, 30), at(19, 30)));
+        guide.handle_key(KeyCode::Left, now());
+        assert_eq!((guide.view_start, guide.cursor), (at(19, 0), at(19, 0)));
+
+        guide.handle_key(KeyCode::Down, now());
+        guide.handle_key(KeyCode::Down, now());
+        assert_eq!(guide.selected_channel(), Some(5));
+        guide.handle_key(KeyCode::Up, now());
+        assert_eq!(guide.selected_channel(), Some(3));
+
+        guide.handle_key(KeyCode::Right, now());
+        guide.handle_key(KeyCode::Home, at(20, 45));
+        assert_eq!((guide.view_start, guide.cursor), (at(20, 30), at(20, 45)));
+
+        assert!(!guide.handle_key(KeyCode::Char('q'), now()));
+        assert!(!guide.handle_key(KeyCode::Esc, now()));
+    }
+
+    #[test]
+    fn draws_the_grid_with_watched_programs_marked() {
+        let mut guide = guide();
+        guide.set_watched(HashSet::from(["sitcom".to_string()]));
+        guide.add_notice("Channel 9 has nothing to air", true);
+        let mut terminal = Terminal::new(TestBackend::new(80, 24)).unwrap();
+        terminal.draw(|frame| guide.draw(frame, now())).unwrap();
+
+        let buffer = terminal.backend().buffer();
+        let lines: Vec<String> = (0..buffer.area.height)
+            .map(|y| (0..buffer.area.width).map(|x| buffer.get(x, y).symbol()).collect())
+            .collect();
+        let screen = lines.join("\n");
+        for expected in ["TV GUIDE", "7:10 PM", "Mon Oct 17", "CH 3", "WEWS", "Family Ties", "✓ Comedy · 30m", "Tootsie ▶", "Movie · 120m", "Evening News", "✓ Watched", "7:00 PM - 7:30 PM · 30 min · Comedy", "Channel 9 has nothing to air"] {
+            assert!(screen.contains(expected), "{} missing from\n{}", expected, screen);
+        }
+        assert_eq!(guide.columns, 3);
+    }
+}
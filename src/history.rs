@@ -0,0 +1,260 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::schedule::ScheduleConfig;
+
+// Default location of the watch history, relative to the working directory
+pub const DEFAULT_HISTORY_FILE: &str = "./history.json";
+
+// Whose history a request is kept under when the caller has no name of their own
+pub const DEFAULT_VIEWER: &str = "household";
+
+// Events kept per viewer; the oldest are dropped first
+const MAX_EVENTS: usize = 500;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct HistorySettings {
+    pub file: PathBuf,
+    // Programs watched within this many days go to the back of the rotation; 0 turns this off
+    pub avoid_reruns_days: u32,
+}
+
+impl HistorySettings {
+    // Schedule settings that push programs watched recently to the back of the rotation
+    pub fn schedule_config(&self, history: &WatchHistory, now: DateTime<Utc>) -> ScheduleConfig {
+        let mut config = ScheduleConfig {
+            favorites: history.favorites(),
+            ..ScheduleConfig::default()
+        };
+        if self.avoid_reruns_days > 0 {
+            config.recently_watched = history.watched_since(now - chrono::Duration::days(self.avoid_reruns_days as i64));
+        }
+        config
+    }
+}
+
+impl Default for HistorySettings {
+    fn default() -> Self {
+        Self {
+            file: PathBuf::from(DEFAULT_HISTORY_FILE),
+            avoid_reruns_days: 0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum WatchKind {
+    // Tuned to a channel and caught whatever was airing
+    Tune,
+    // Played or streamed a video on demand
+    Play,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WatchEvent {
+    pub kind: WatchKind,
+    pub at: DateTime<Utc>,
+    #[serde(default)]
+    pub channel: Option<u8>,
+    // Library ID of the video, when there was one to play
+    #[serde(default)]
+    pub video_id: Option<String>,
+    // Sidecar original_id, when the video has a sidecar
+    #[serde(default)]
+    pub original_id: Option<String>,
+    pub title: String,
+}
+
+impl WatchEvent {
+    // Same thing watched the same way, e.g. a client polling the channel it is on
+    fn repeats(&self, other: &WatchEvent) -> bool {
+        self.kind == other.kind
+            && self.channel == other.channel
+            && self.video_id == other.video_id
+            && self.original_id == other.original_id
+            && self.title == other.title
+    }
+}
+
+// Where a viewer stopped in a video
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ResumePoint {
+    pub position_seconds: u32,
+    #[serde(default)]
+    pub duration_seconds: Option<u32>,
+    // Watched to the end; playback starts from the beginning again
+    #[serde(default)]
+    pub finished: bool,
+    #[serde(default)]
+    pub original_id: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ViewerHistory {
+    // Oldest first
+    #[serde(default)]
+    pub events: Vec<WatchEvent>,
+    // Keyed by library video ID
+    #[serde(default)]
+    pub resume: BTreeMap<String, ResumePoint>,
+    // original_ids of starred programs
+    #[serde(default)]
+    pub favorites: BTreeSet<String>,
+}
+
+// Every viewer's tune-ins, plays, resume points and favorites, stored as one JSON file
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct WatchHistory {
+    #[serde(default)]
+    pub viewers: BTreeMap<String, ViewerHistory>,
+    // Whether events were recorded since take_unsaved was last called
+    #[serde(skip)]
+    unsaved: bool,
+}
+
+impl WatchHistory {
+    // Load the history, treating a missing file as an empty history
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read watch history {}", path.display()))?;
+        serde_json::from_str(&content).with_context(|| format!("Failed to parse watch history {}", path.display()))
+    }
+
+    // Write the history, replacing the existing file atomically
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, json).with_context(|| format!("Failed to write watch history {}", path.display()))?;
+        fs::rename(&tmp_path, path).with_context(|| format!("Failed to write watch history {}", path.display()))?;
+        Ok(())
+    }
+
+    pub fn viewer(&self, viewer: &str) -> Option<&ViewerHistory> {
+        self.viewers.get(viewer)
+    }
+
+    // Add an event; one that repeats the viewer's last event only refreshes its time
+    pub fn record(&mut self, viewer: &str, event: WatchEvent) {
+        let events = &mut self.viewers.entry(viewer.to_string()).or_default().events;
+        match events.last_mut() {
+            Some(last) if last.repeats(&event) => last.at = event.at,
+            _ => events.push(event),
+        }
+        if events.len() > MAX_EVENTS {
+            events.drain(..events.len() - MAX_EVENTS);
+        }
+        self.unsaved = true;
+    }
+
+    // True once after each batch of recorded events, for writers that save them periodically
+    pub fn take_unsaved(&mut self) -> bool {
+        std::mem::take(&mut self.unsaved)
+    }
+
+    pub fn resume_point(&self, viewer: &str, video_id: &str) -> Option<&ResumePoint> {
+        self.viewer(viewer)?.resume.get(video_id)
+    }
+
+    pub fn set_resume_point(&mut self, viewer: &str, video_id: &str, point: ResumePoint) {
+        self.viewers.entry(viewer.to_string()).or_default().resume.insert(video_id.to_string(), point);
+    }
+
+    // Star or unstar a program; returns false when nothing changed
+    pub fn set_favorite(&mut self, viewer: &str, original_id: &str, starred: bool) -> bool {
+        let favorites = &mut self.viewers.entry(viewer.to_string()).or_default().favorites;
+        if starred {
+            favorites.insert(original_id.to_string())
+        } else {
+            favorites.remove(original_id)
+        }
+    }
+
+    // Each viewer's starred original_ids
+    pub fn favorites(&self) -> BTreeMap<String, HashSet<String>> {
+        self.viewers
+            .iter()
+            .filter(|(_, history)| !history.favorites.is_empty())
+            .map(|(viewer, history)| (viewer.clone(), history.favorites.iter().cloned().collect()))
+            .collect()
+    }
+
+    // original_ids of programs anyone has tuned in to, played or stopped part way through
+    pub fn watched(&self) -> HashSet<String> {
+        self.watched_since(DateTime::<Utc>::MIN_UTC)
+    }
+
+    // When each program was last tuned in to, played or stopped part way through
+    pub fn last_watched(&self) -> HashMap<String, DateTime<Utc>> {
+        let mut last: HashMap<String, DateTime<Utc>> = HashMap::new();
+        for history in self.viewers.values() {
+            let events = history.events.iter().filter_map(|event| Some((event.original_id.clone()?, event.at)));
+            let resumed = history.resume.values().filter_map(|point| Some((point.original_id.clone()?, point.updated_at)));
+            for (original_id, at) in events.chain(resumed) {
+                let latest = last.entry(original_id).or_insert(at);
+                *latest = (*latest).max(at);
+            }
+        }
+        last
+    }
+
+    pub fn watched_since(&self, since: DateTime<Utc>) -> HashSet<String> {
+        let mut watched = HashSet::new();
+        for history in self.viewers.values() {
+            let events = history.events.iter().filter(|event| event.at >= since).filter_map(|event| event.original_id.clone());
+            let resumed = history.resume.values().filter(|point| point.updated_at >= since).filter_map(|point| point.original_id.clone());
+            watched.extend(events.chain(resumed));
+        }
+        watched
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Timelike};
+
+    fn tune(channel: u8, minute: u32) -> WatchEvent {
+        WatchEvent {
+            kind: WatchKind::Tune,
+            at: Utc.with_ymd_and_hms(1994, 10, 17, 23, minute, 0).unwrap(),
+            channel: Some(channel),
+            video_id: None,
+            original_id: Some(format!("program-{}", channel)),
+            title: format!("Channel {}", channel),
+        }
+    }
+
+    #[test]
+    fn recorded_events_are_flagged_for_the_next_batch_write() {
+        let mut history = WatchHistory::default();
+        assert!(!history.take_unsaved());
+
+        history.record(DEFAULT_VIEWER, tune(5, 0));
+        history.record(DEFAULT_VIEWER, tune(5, 1));
+        history.record(DEFAULT_VIEWER, tune(3, 2));
+        assert!(history.take_unsaved());
+        assert!(!history.take_unsaved());
+
+        // Polling the same channel only refreshes the last event's time
+        let events = &history.viewer(DEFAULT_VIEWER).unwrap().events;
+        assert_eq!(events.iter().map(|event| (event.channel, event.at.minute())).collect::<Vec<_>>(), vec![(Some(5), 1), (Some(3), 2)]);
+
+        // The flag is not part of the file
+        history.record(DEFAULT_VIEWER, tune(9, 3));
+        let json = serde_json::to_string(&history).unwrap();
+        assert!(!json.contains("unsaved"));
+        let mut reloaded: WatchHistory = serde_json::from_str(&json).unwrap();
+        assert!(!reloaded.take_unsaved());
+        assert_eq!(reloaded.viewer(DEFAULT_VIEWER).unwrap().events.len(), 3);
+    }
+}
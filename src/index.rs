@@ -0,0 +1,211 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::library;
+
+// A playable file, addressed by an opaque ID so clients never send paths
+#[derive(Debug, Clone, Serialize)]
+pub struct VideoEntry {
+    pub id: String,
+    pub filename: String,
+    #[serde(skip)]
+    pub path: PathBuf,
+    #[serde(skip)]
+    root: PathBuf,
+}
+
+// Every video file directly inside the library roots, keyed by ID
+#[derive(Debug, Default)]
+pub struct VideoIndex {
+    entries: Vec<VideoEntry>,
+    by_id: HashMap<String, usize>,
+}
+
+// Why a request could not be matched to a file in the library
+#[derive(Debug, PartialEq, Eq)]
+pub enum LookupError {
+    // Not a bare file name: empty, absolute, or containing separators or ".."
+    InvalidFilename,
+    NotFound,
+    // The file now resolves outside its library root, e.g. through a symlink
+    OutsideLibrary,
+}
+
+impl fmt::Display for LookupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LookupError::InvalidFilename => write!(f, "filename must be a plain file name inside the library"),
+            LookupError::NotFound => write!(f, "no such video in the library"),
+            LookupError::OutsideLibrary => write!(f, "video is outside the library"),
+        }
+    }
+}
+
+impl std::error::Error for LookupError {}
+
+// Stable ID for a canonical path: 64-bit FNV-1a, so IDs survive restarts and rescans
+pub fn video_id(canonical_path: &Path) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in canonical_path.to_string_lossy().as_bytes() {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    format!("{:016x}", hash)
+}
+
+// True for a single normal path component: no separators, "..", "." or drive
+// prefixes, and no NUL bytes, which the OS would cut the name short at
+pub fn is_plain_filename(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    matches!((components.next(), components.next()), (Some(Component::Normal(_)), None))
+        && !name.contains(['/', '\\', '\0'])
+}
+
+impl VideoIndex {
+    // Index every root, collecting roots that could not be read. Files whose
+    // canonical path escapes their root (symlinks pointing elsewhere) are left out.
+    pub fn build(roots: &[PathBuf]) -> (Self, Vec<(PathBuf, io::Error)>) {
+        let mut index = Self::default();
+        let mut errors = Vec::new();
+        for root in roots {
+            if let Err(e) = index.add_root(root) {
+                errors.push((root.clone(), e));
+            }
+        }
+        (index, errors)
+    }
+
+    fn add_root(&mut self, root: &Path) -> io::Result<()> {
+        let root = root.canonicalize()?;
+        let mut paths: Vec<PathBuf> = fs::read_dir(&root)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.is_file() && library::is_video_file(path))
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            let Ok(canonical) = path.canonicalize() else { continue };
+            if !canonical.starts_with(&root) {
+                continue;
+            }
+            let id = video_id(&canonical);
+            if self.by_id.contains_key(&id) {
+                continue;
+            }
+            let filename = path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+            self.by_id.insert(id.clone(), self.entries.len());
+            self.entries.push(VideoEntry { id, filename, path: canonical, root: root.clone() });
+        }
+        Ok(())
+    }
+
+    pub fn entries(&self) -> &[VideoEntry] {
+        &self.entries
+    }
+
+    // Forget an entry, e.g. once its file has been deleted
+    pub fn remove(&mut self, id: &str) -> Option<VideoEntry> {
+        let position = self.by_id.remove(id)?;
+        let entry = self.entries.remove(position);
+        for index in self.by_id.values_mut() {
+            if *index > position {
+                *index -= 1;
+            }
+        }
+        Some(entry)
+    }
+
+    pub fn get(&self, id: &str) -> Option<&VideoEntry> {
+        self.by_id.get(id).map(|&i| &self.entries[i])
+    }
+
+    // The first entry with this file name, for clients that still send names
+    pub fn find_filename(&self, filename: &str) -> Result<&VideoEntry, LookupError> {
+        if !is_plain_filename(filename) {
+            return Err(LookupError::InvalidFilename);
+        }
+        self.entries.iter().find(|entry| entry.filename == filename).ok_or(LookupError::NotFound)
+    }
+
+    // The file to open for an ID, checked again against its root in case it
+    // was swapped for a symlink since the index was built
+    pub fn resolve(&self, id: &str) -> Result<PathBuf, LookupError> {
+        let entry = self.get(id).ok_or(LookupError::NotFound)?;
+        entry.resolve()
+    }
+}
+
+impl VideoEntry {
+    pub fn resolve(&self) -> Result<PathBuf, LookupError> {
+        let canonical = self.path.canonicalize().map_err(|_| LookupError::NotFound)?;
+        if canonical.starts_with(&self.root) && canonical.is_file() {
+            Ok(canonical)
+        } else {
+            Err(LookupError::OutsideLibrary)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A library root with one real video, plus a symlink in it that points at
+    // a video outside the root
+    fn library_with_escape(name: &str) -> (PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let root = dir.join("videos");
+        let outside = dir.join("private");
+        fs::create_dir_all(&root).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(root.join("show.mp4"), b"show").unwrap();
+        fs::write(outside.join("secret.mp4"), b"secret").unwrap();
+        std::os::unix::fs::symlink(outside.join("secret.mp4"), root.join("escape.mp4")).unwrap();
+        (dir, root)
+    }
+
+    #[test]
+    fn only_plain_file_names_are_accepted() {
+        assert!(is_plain_filename("show.mp4"));
+        assert!(is_plain_filename("..show.mp4"));
+        for name in ["", ".", "..", "../show.mp4", "videos/show.mp4", "/etc/passwd", "..\\show.mp4", "C:\\show.mp4", "show.mp4\0.txt", "show\0"] {
+            assert!(!is_plain_filename(name), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn paths_never_resolve_to_files_outside_the_library() {
+        let (dir, root) = library_with_escape("index-escape");
+        let (index, errors) = VideoIndex::build(std::slice::from_ref(&root));
+        assert!(errors.is_empty());
+        // The symlink pointing outside the root is never indexed
+        assert_eq!(index.entries().iter().map(|entry| entry.filename.as_str()).collect::<Vec<_>>(), vec!["show.mp4"]);
+        let escaped = video_id(&dir.join("private/secret.mp4").canonicalize().unwrap());
+        assert_eq!(index.resolve(&escaped), Err(LookupError::NotFound));
+
+        // IDs are opaque, so paths in any spelling are simply unknown
+        for id in ["../private/secret.mp4", "/etc/passwd", "..%2Fprivate%2Fsecret.mp4", "%2Fetc%2Fpasswd", "show.mp4\0", ""] {
+            assert_eq!(index.resolve(id), Err(LookupError::NotFound), "{:?}", id);
+        }
+        for name in ["../private/secret.mp4", "/etc/passwd", "show.mp4\0"] {
+            assert_eq!(index.find_filename(name).unwrap_err(), LookupError::InvalidFilename, "{:?}", name);
+        }
+        assert_eq!(index.find_filename("escape.mp4").unwrap_err(), LookupError::NotFound);
+        assert_eq!(index.find_filename("..%2Fprivate%2Fsecret.mp4").unwrap_err(), LookupError::NotFound);
+
+        // A file swapped for a symlink after indexing is caught when it's opened
+        let id = index.find_filename("show.mp4").unwrap().id.clone();
+        assert_eq!(index.resolve(&id), Ok(root.join("show.mp4").canonicalize().unwrap()));
+        fs::remove_file(root.join("show.mp4")).unwrap();
+        std::os::unix::fs::symlink(dir.join("private/secret.mp4"), root.join("show.mp4")).unwrap();
+        assert_eq!(index.resolve(&id), Err(LookupError::OutsideLibrary));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
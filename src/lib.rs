@@ -0,0 +1,24 @@
+// Shared library code for the Channel Surfer binaries (server, menu and downloader)
+
+pub mod api;
+pub mod auth;
+pub mod channels;
+pub mod clock;
+pub mod config;
+pub mod dedupe;
+pub mod guide;
+pub mod history;
+pub mod index;
+pub mod library;
+pub mod local_time;
+pub mod pack;
+pub mod parental;
+pub mod player;
+pub mod queue;
+pub mod schedule;
+pub mod sidecar;
+pub mod storage;
+pub mod subscriptions;
+pub mod surf;
+pub mod trash;
+pub mod tuner;
@@ -0,0 +1,132 @@
+use crate::channels::ChannelMap;
+use crate::clock::Clock;
+use crate::local_time::TimeSettings;
+use crate::schedule::{self, ScheduleConfig, WeeklySchedule};
+use crate::sidecar::{self, SidecarError, TvGuideMetadata};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Video extensions recognised in the library
+pub const VIDEO_EXTENSIONS: [&str; 6] = ["mp4", "avi", "mkv", "mov", "webm", "flv"];
+
+// Images next to a video with the same name are its thumbnails
+pub const THUMBNAIL_EXTENSIONS: [&str; 4] = ["jpg", "jpeg", "png", "webp"];
+
+// A video in the library together with its sidecar metadata
+#[derive(Debug, Clone)]
+pub struct LibraryItem {
+    pub video_path: PathBuf,
+    pub sidecar_path: PathBuf,
+    pub metadata: TvGuideMetadata,
+}
+
+// Result of scanning a videos directory
+#[derive(Debug, Default)]
+pub struct LibraryScan {
+    pub items: Vec<LibraryItem>,
+    pub problems: Vec<(PathBuf, SidecarError)>,
+    pub migrated: usize,
+}
+
+pub fn is_video_file(path: &Path) -> bool {
+    path.extension()
+        .map(|ext| VIDEO_EXTENSIONS.contains(&ext.to_string_lossy().to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+// A video's sidecar and thumbnails that exist on disk, which are deleted,
+// trashed and restored along with it
+pub fn companion_files(video_path: &Path) -> Vec<PathBuf> {
+    std::iter::once(sidecar::sidecar_path(video_path))
+        .chain(THUMBNAIL_EXTENSIONS.iter().map(|ext| video_path.with_extension(ext)))
+        .filter(|path| path.exists())
+        .collect()
+}
+
+// Load every video that has a sidecar, collecting sidecars that fail to load
+pub fn scan(videos_dir: &Path) -> std::io::Result<LibraryScan> {
+    let mut scan = LibraryScan::default();
+
+    let mut paths: Vec<PathBuf> = fs::read_dir(videos_dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && is_video_file(path))
+        .collect();
+    // Keep scans stable regardless of directory order
+    paths.sort();
+
+    for video_path in paths {
+        let sidecar_path = sidecar::sidecar_path(&video_path);
+        if !sidecar_path.exists() {
+            continue;
+        }
+        match sidecar::load(&sidecar_path) {
+            Ok(loaded) => {
+                if loaded.migrated_from.is_some() {
+                    scan.migrated += 1;
+                }
+                scan.items.push(LibraryItem {
+                    video_path,
+                    sidecar_path,
+                    metadata: loaded.metadata,
+                });
+            }
+            Err(e) => scan.problems.push((sidecar_path, e)),
+        }
+    }
+
+    Ok(scan)
+}
+
+// Scan every library root into one library
+pub fn scan_roots(roots: &[PathBuf]) -> std::io::Result<LibraryScan> {
+    let mut combined = LibraryScan::default();
+    for root in roots {
+        let scan = scan(root).map_err(|e| std::io::Error::new(e.kind(), format!("{}: {}", root.display(), e)))?;
+        combined.items.extend(scan.items);
+        combined.problems.extend(scan.problems);
+        combined.migrated += scan.migrated;
+    }
+    Ok(combined)
+}
+
+// Scan the library, build the weekly grid and record each program's next airing in its sidecar
+pub fn schedule_library(
+    roots: &[PathBuf],
+    config: &ScheduleConfig,
+    channels: &ChannelMap,
+    time: &TimeSettings,
+    clock: &dyn Clock,
+) -> Result<(LibraryScan, WeeklySchedule), SidecarError> {
+    let mut scan = scan_roots(roots)?;
+    let programs: Vec<TvGuideMetadata> = scan.items.iter().map(|item| item.metadata.clone()).collect();
+    let weekly = schedule::build_weekly_schedule(&programs, config, channels);
+    let now = clock.now();
+
+    for item in &mut scan.items {
+        let before = (item.metadata.start_time, item.metadata.end_time);
+        schedule::apply_next_airing(&weekly, &mut item.metadata, time, now);
+        if before != (item.metadata.start_time, item.metadata.end_time) {
+            item.metadata.save(&item.sidecar_path)?;
+        }
+    }
+
+    Ok((scan, weekly))
+}
+
+// Callsign for each channel: the channel map's override, or the first sidecar on that channel
+pub fn callsigns(items: &[LibraryItem], channels: &ChannelMap) -> BTreeMap<u8, String> {
+    let mut callsigns = BTreeMap::new();
+    for item in items {
+        callsigns
+            .entry(item.metadata.channel_number)
+            .or_insert_with(|| item.metadata.station_callsign.clone());
+    }
+    for channel in &channels.channels {
+        if let Some(callsign) = channel.callsign.as_ref().or(channel.name.as_ref()) {
+            callsigns.insert(channel.number, callsign.clone());
+        }
+    }
+    callsigns
+}
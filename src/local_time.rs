@@ -0,0 +1,248 @@
+use chrono::{DateTime, Datelike, Duration, LocalResult, NaiveDateTime, NaiveTime, SubsecRound, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+use crate::schedule::{SECONDS_PER_DAY, SECONDS_PER_WEEK};
+
+// Environment variables for the time settings, read by Config::load
+pub const TIMEZONE_ENV: &str = "CHANNEL_SURFER_TIMEZONE";
+pub const CLOCK_FORMAT_ENV: &str = "CHANNEL_SURFER_CLOCK";
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ClockFormat {
+    #[default]
+    #[serde(rename = "12h")]
+    TwelveHour,
+    #[serde(rename = "24h")]
+    TwentyFourHour,
+}
+
+impl std::str::FromStr for ClockFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "12h" | "12" => Ok(ClockFormat::TwelveHour),
+            "24h" | "24" => Ok(ClockFormat::TwentyFourHour),
+            other => Err(format!("unknown clock format '{}', expected 12h or 24h", other)),
+        }
+    }
+}
+
+// The timezone the weekly grid is laid out in, and how times are displayed.
+// The schedule is a wall-clock week, so a 6:00 PM slot airs at 6:00 PM local
+// time on both sides of a DST change.
+#[derive(Debug, Clone, Copy)]
+pub struct TimeSettings {
+    pub timezone: Tz,
+    pub clock_format: ClockFormat,
+}
+
+impl Default for TimeSettings {
+    fn default() -> Self {
+        Self {
+            timezone: system_timezone(),
+            clock_format: ClockFormat::default(),
+        }
+    }
+}
+
+// The host's IANA timezone, falling back to UTC when it can't be determined
+pub fn system_timezone() -> Tz {
+    std::env::var("TZ")
+        .ok()
+        .and_then(|name| name.parse().ok())
+        .or_else(|| iana_time_zone::get_timezone().ok().and_then(|name| name.parse().ok()))
+        .unwrap_or(Tz::UTC)
+}
+
+impl TimeSettings {
+    pub fn local(&self, instant: DateTime<Utc>) -> DateTime<Tz> {
+        instant.with_timezone(&self.timezone)
+    }
+
+    // Position in the wall-clock schedule week (Monday 00:00 = 0) for an instant
+    pub fn second_of_week(&self, instant: DateTime<Utc>) -> u32 {
+        let local = self.local(instant);
+        local.weekday().num_days_from_monday() * SECONDS_PER_DAY + local.num_seconds_from_midnight()
+    }
+
+    // The next real instant at or after `after` when the wall clock reads the
+    // given position in the schedule week. Times skipped by a spring-forward
+    // change resolve to the first instant after the gap; times repeated by a
+    // fall-back change resolve to their first occurrence.
+    pub fn next_occurrence(&self, second_of_week: u32, after: DateTime<Utc>) -> DateTime<Tz> {
+        let second_of_week = second_of_week % SECONDS_PER_WEEK;
+        let local = self.local(after);
+        let monday = local.date_naive() - Duration::days(local.weekday().num_days_from_monday() as i64);
+        let day = monday + Duration::days((second_of_week / SECONDS_PER_DAY) as i64);
+        let time = NaiveTime::from_num_seconds_from_midnight_opt(second_of_week % SECONDS_PER_DAY, 0)
+            .unwrap_or(NaiveTime::MIN);
+
+        let this_week = self.resolve_local(day.and_time(time));
+        if this_week.with_timezone(&Utc) >= after {
+            this_week
+        } else {
+            self.resolve_local(day.and_time(time) + Duration::days(7))
+        }
+    }
+
+    // Start of the airing of a grid slot that hasn't finished by `now`: the one
+    // in progress if there is one, otherwise the next
+    pub fn airing_start(&self, start_second: u32, length_seconds: u32, now: DateTime<Utc>) -> DateTime<Tz> {
+        let into = (self.second_of_week(now) + SECONDS_PER_WEEK - start_second % SECONDS_PER_WEEK) % SECONDS_PER_WEEK;
+        if into < length_seconds {
+            self.local(now.trunc_subsecs(0) - Duration::seconds(into as i64))
+        } else {
+            self.next_occurrence(start_second, now)
+        }
+    }
+
+    // Parse a timestamp such as "1994-10-17T19:30" as local wall-clock time in
+    // this timezone; RFC 3339 timestamps with an explicit offset are also accepted
+    pub fn parse_local(&self, text: &str) -> Result<DateTime<Utc>, String> {
+        let text = text.trim();
+        if let Ok(dt) = DateTime::parse_from_rfc3339(text) {
+            return Ok(dt.with_timezone(&Utc));
+        }
+        ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"]
+            .iter()
+            .find_map(|format| NaiveDateTime::parse_from_str(text, format).ok())
+            .map(|wall_clock| self.resolve_local(wall_clock).with_timezone(&Utc))
+            .ok_or_else(|| format!("unrecognised time '{}', expected e.g. 1994-10-17T19:30", text))
+    }
+
+    fn resolve_local(&self, wall_clock: NaiveDateTime) -> DateTime<Tz> {
+        match self.timezone.from_local_datetime(&wall_clock) {
+            LocalResult::Single(dt) => dt,
+            LocalResult::Ambiguous(earliest, _) => earliest,
+            // Inside a DST gap: step forward a minute at a time from the start
+            // of the minute, so this lands on the first wall-clock time after it
+            LocalResult::None => {
+                let mut probe = wall_clock.trunc_subsecs(0) - Duration::seconds(wall_clock.second() as i64);
+                loop {
+                    probe += Duration::minutes(1);
+                    if let Some(dt) = self.timezone.from_local_datetime(&probe).earliest() {
+                        break dt;
+                    }
+                }
+            }
+        }
+    }
+
+    // Guide-style time, e.g. "6:30 PM" or "18:30"
+    pub fn format_time(&self, time: &DateTime<Tz>) -> String {
+        match self.clock_format {
+            ClockFormat::TwelveHour => time.format("%-I:%M %p").to_string(),
+            ClockFormat::TwentyFourHour => time.format("%H:%M").to_string(),
+        }
+    }
+
+    // Day and time, e.g. "Sat 7:00 AM"
+    pub fn format_day_time(&self, time: &DateTime<Tz>) -> String {
+        format!("{} {}", time.format("%a"), self.format_time(time))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_york(clock_format: ClockFormat) -> TimeSettings {
+        TimeSettings { timezone: "America/New_York".parse().unwrap(), clock_format }
+    }
+
+    fn utc(text: &str) -> DateTime<Utc> {
+        text.parse().unwrap()
+    }
+
+    fn wall_clock(text: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    const HOUR: u32 = 3600;
+
+    #[test]
+    fn spring_forward_gap_resolves_to_the_end_of_the_gap() {
+        let settings = new_york(ClockFormat::TwelveHour);
+        // Clocks went from 2:00 AM EST to 3:00 AM EDT on 3 April 1994
+        for skipped in ["1994-04-03 02:00:00", "1994-04-03 02:10:00", "1994-04-03 02:59:30"] {
+            let resolved = settings.resolve_local(wall_clock(skipped));
+            assert_eq!(resolved.with_timezone(&Utc), utc("1994-04-03T07:00:00Z"), "{}", skipped);
+            assert_eq!(settings.format_time(&resolved), "3:00 AM");
+        }
+        assert_eq!(settings.resolve_local(wall_clock("1994-04-03 01:59:00")).with_timezone(&Utc), utc("1994-04-03T06:59:00Z"));
+        assert_eq!(settings.resolve_local(wall_clock("1994-04-03 03:15:00")).with_timezone(&Utc), utc("1994-04-03T07:15:00Z"));
+    }
+
+    #[test]
+    fn fall_back_overlap_resolves_to_the_first_occurrence() {
+        let settings = new_york(ClockFormat::TwelveHour);
+        // 1:00 to 2:00 AM happened twice on 30 October 1994, first in EDT
+        let resolved = settings.resolve_local(wall_clock("1994-10-30 01:30:00"));
+        assert_eq!(resolved.with_timezone(&Utc), utc("1994-10-30T05:30:00Z"));
+        assert_eq!(settings.resolve_local(wall_clock("1994-10-30 02:30:00")).with_timezone(&Utc), utc("1994-10-30T07:30:00Z"));
+    }
+
+    #[test]
+    fn next_occurrence_keeps_wall_clock_time_across_dst() {
+        let settings = new_york(ClockFormat::TwelveHour);
+        let monday_six_pm = 18 * HOUR;
+        // The same slot is an hour earlier in UTC once daylight saving starts
+        assert_eq!(settings.next_occurrence(monday_six_pm, utc("1994-03-28T12:00:00Z")).with_timezone(&Utc), utc("1994-03-28T23:00:00Z"));
+        assert_eq!(settings.next_occurrence(monday_six_pm, utc("1994-04-04T12:00:00Z")).with_timezone(&Utc), utc("1994-04-04T22:00:00Z"));
+        // After this week's airing it is next week's
+        assert_eq!(settings.next_occurrence(monday_six_pm, utc("1994-04-04T22:00:01Z")).with_timezone(&Utc), utc("1994-04-11T22:00:00Z"));
+
+        let sunday = 6 * SECONDS_PER_DAY;
+        // A slot in the spring-forward gap airs when the clock jumps to 3:00
+        assert_eq!(settings.next_occurrence(sunday + 2 * HOUR + 30 * 60, utc("1994-04-02T12:00:00Z")).with_timezone(&Utc), utc("1994-04-03T07:00:00Z"));
+        // A slot in the repeated hour airs once, the first time round
+        let repeated = sunday + HOUR + 30 * 60;
+        assert_eq!(settings.next_occurrence(repeated, utc("1994-10-29T12:00:00Z")).with_timezone(&Utc), utc("1994-10-30T05:30:00Z"));
+        assert_eq!(settings.next_occurrence(repeated, utc("1994-10-30T05:30:01Z")).with_timezone(&Utc), utc("1994-11-06T06:30:00Z"));
+    }
+
+    #[test]
+    fn second_of_week_follows_the_local_clock() {
+        let settings = new_york(ClockFormat::TwelveHour);
+        assert_eq!(settings.second_of_week(utc("1994-10-17T23:30:00Z")), 19 * HOUR + 30 * 60);
+        // Both 1:30 AMs of the fall-back night are the same grid position
+        let sunday = 6 * SECONDS_PER_DAY;
+        assert_eq!(settings.second_of_week(utc("1994-10-30T05:30:00Z")), sunday + HOUR + 30 * 60);
+        assert_eq!(settings.second_of_week(utc("1994-10-30T06:30:00Z")), sunday + HOUR + 30 * 60);
+        assert_eq!(settings.second_of_week(utc("1994-04-03T07:30:00Z")), sunday + 3 * HOUR + 30 * 60);
+    }
+
+    #[test]
+    fn airing_start_finds_the_slot_in_progress() {
+        let settings = new_york(ClockFormat::TwelveHour);
+        let now = utc("1994-10-17T23:40:00Z");
+        // Monday 7:30 PM, half an hour long, is ten minutes in
+        let start = settings.airing_start(19 * HOUR + 30 * 60, 1800, now);
+        assert_eq!(start.with_timezone(&Utc), utc("1994-10-17T23:30:00Z"));
+        // Monday 7:00 PM has finished, so its next airing is a week away
+        let start = settings.airing_start(19 * HOUR, 1800, now);
+        assert_eq!(start.with_timezone(&Utc), utc("1994-10-24T23:00:00Z"));
+    }
+
+    #[test]
+    fn formats_local_times() {
+        let settings = new_york(ClockFormat::TwelveHour);
+        let time = settings.local(utc("1994-10-17T23:05:00Z"));
+        assert_eq!(settings.format_day_time(&time), "Mon 7:05 PM");
+        assert_eq!(new_york(ClockFormat::TwentyFourHour).format_time(&time), "19:05");
+        assert_eq!("24".parse(), Ok(ClockFormat::TwentyFourHour));
+        assert!("13h".parse::<ClockFormat>().is_err());
+    }
+
+    #[test]
+    fn parses_local_times() {
+        let settings = new_york(ClockFormat::TwelveHour);
+        assert_eq!(settings.parse_local("1994-10-17T19:30"), Ok(utc("1994-10-17T23:30:00Z")));
+        assert_eq!(settings.parse_local(" 1994-10-17 19:30:15 "), Ok(utc("1994-10-17T23:30:15Z")));
+        assert_eq!(settings.parse_local("1994-10-17T19:30:00+00:00"), Ok(utc("1994-10-17T19:30:00Z")));
+        assert_eq!(settings.parse_local("1994-04-03T02:30"), Ok(utc("1994-04-03T07:00:00Z")));
+        assert!(settings.parse_local("next tuesday").is_err());
+    }
+}
@@ -1,74 +1,759 @@
-use warp::Filter;
+use warp::{Filter, Reply};
 use serde::{Deserialize, Serialize};
-use std::process::Command;
-use tokio::sync::Mutex;
+use std::process::Stdio;
+use tokio::sync::{Mutex, Notify};
 use std::sync::Arc;
-use std::fs;
+use warp::http::Method;
+use warp::hyper::Body;
+use warp::path::FullPath;
 use warp::sse::Event;
 use tokio_stream::wrappers::BroadcastStream;
+use tokio_util::io::ReaderStream;
 use futures::StreamExt;
 use tokio::sync::broadcast;
+use std::net::{IpAddr, SocketAddr};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use chrono::{DateTime, FixedOffset, Utc};
+use clap::Parser;
+use video_server::channels::{BroadcastHours, ChannelConfig, ChannelMap, Daypart, ProgramSelection};
+use video_server::clock::{self, SharedClock};
+use video_server::api::{self, ApiError};
+use video_server::auth::{self, Attempts, Role, Sessions};
+use video_server::config::{Config, ConfigArgs, ConfigWatcher};
+use video_server::history::{self, ResumePoint, WatchEvent, WatchHistory, WatchKind};
+use video_server::index::{self, VideoEntry, VideoIndex};
+use video_server::library;
+use video_server::parental::{self, ParentalConfig, Unlocks};
+use video_server::sidecar::{self, TvGuideMetadata};
+use video_server::subscriptions::{Subscription, SubscriptionList};
+use video_server::local_time::TimeSettings;
+use video_server::schedule::{self, ScheduleConfig, SlotKind, WeeklySchedule};
+use video_server::tuner::{Source, Tuner};
 
+#[derive(Parser)]
+#[command(name = "video_server")]
+#[command(about = "Channel Surfer video server", long_about = None)]
+struct Args {
+    #[command(flatten)]
+    config: ConfigArgs,
+
+    /// Address to listen on [default: 127.0.0.1]
+    #[arg(long)]
+    bind: Option<IpAddr>,
+
+    /// Port to listen on [default: 3030]
+    #[arg(long)]
+    port: Option<u16>,
+
+    /// Pretend the current local time is this moment, e.g. "1994-10-17T19:30"
+    #[arg(long)]
+    simulate_at: Option<String>,
+
+    /// How fast simulated time runs (1 = real time, 0 = frozen, at most 10000)
+    #[arg(long, default_value = "1", requires = "simulate_at")]
+    speed: f64,
+}
+
+// Body of POST /play: the video's library ID, or a bare file name from older clients
 #[derive(Deserialize, Serialize)]
-struct Video {
-    filename: String,
+struct PlayRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    filename: Option<String>,
+    // Start from where this viewer left off
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    resume: bool,
+}
+
+
+// Body of POST /login
+#[derive(Deserialize)]
+struct LoginRequest {
+    password: String,
+    // Name to keep this browser's watch history under
+    #[serde(default)]
+    viewer: Option<String>,
+}
+
+#[derive(Serialize)]
+struct LoginResponse {
+    role: Role,
+}
+
+// Body of POST /unlock
+#[derive(Deserialize)]
+struct UnlockRequest {
+    pin: String,
+}
+
+#[derive(Serialize)]
+struct UnlockResponse {
+    unlocked_seconds: u64,
+}
+
+// Body of PUT /resume/{id}
+#[derive(Deserialize)]
+struct ResumeRequest {
+    position_seconds: u32,
+    #[serde(default)]
+    duration_seconds: Option<u32>,
+    #[serde(default)]
+    finished: bool,
+}
+
+// Where a viewer left off in a video; zero when they haven't started it
+#[derive(Serialize)]
+struct ResumeStatus {
+    id: String,
+    position_seconds: u32,
+    duration_seconds: Option<u32>,
+    finished: bool,
+    updated_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+struct HistoryResponse {
+    viewer: String,
+    // Newest first
+    events: Vec<WatchEvent>,
+    resume: BTreeMap<String, ResumePoint>,
+}
+
+// A starred program
+#[derive(Serialize)]
+struct Favorite {
+    original_id: String,
+    video_id: Option<String>,
+    title: String,
+}
+
+// Body of POST /channels/personal
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PersonalChannelRequest {
+    // The next free number from 100 up when unset
+    #[serde(default)]
+    number: Option<u8>,
+    name: String,
+    selection: ProgramSelection,
+    #[serde(default)]
+    dayparts: Vec<Daypart>,
+    #[serde(default)]
+    broadcast_hours: Option<BroadcastHours>,
+}
+
+// Stopping this close to the end counts as having finished
+const FINISHED_MARGIN_SECONDS: u32 = 30;
+
+// Pause before answering a wrong password or PIN, to slow down guessing
+const LOGIN_FAILURE_DELAY: Duration = Duration::from_millis(500);
+
+// How often tune-ins and plays are written to the history file
+const HISTORY_SAVE_INTERVAL: Duration = Duration::from_secs(5);
+
+// How often the config file and channel map are checked for changes
+const RELOAD_INTERVAL: Duration = Duration::from_secs(2);
+
+// The library's weekly lineup, rebuilt whenever the configuration changes
+struct Lineup {
+    tuner: Tuner,
+    clock: SharedClock,
+    // Sidecar metadata by original_id, and the original_id of each canonical video path
+    programs: HashMap<String, TvGuideMetadata>,
+    files: HashMap<PathBuf, String>,
+}
+
+impl Lineup {
+    // Whether a channel, or what it is airing now, is behind a parental lock
+    fn channel_locked(&self, parental: &ParentalConfig, channel: u8, now: DateTime<Utc>) -> bool {
+        let second_of_week = self.tuner.time().second_of_week(now);
+        parental.channel_locked(channel)
+            || self
+                .tuner
+                .schedule()
+                .slot_at(channel, second_of_week)
+                .is_some_and(|slot| parental.slot_locked(channel, slot, &self.programs))
+    }
+
+    // The program in the slot a channel is airing now, if it is one from the library
+    fn program_at(&self, channel: u8, now: DateTime<Utc>) -> Option<&TvGuideMetadata> {
+        let slot = self.tuner.schedule().slot_at(channel, self.tuner.time().second_of_week(now))?;
+        self.programs.get(&slot.original_id).filter(|_| slot.kind == SlotKind::Program)
+    }
+
+    fn program_for_file(&self, path: &Path) -> Option<&TvGuideMetadata> {
+        self.files.get(path).and_then(|id| self.programs.get(id))
+    }
+
+    fn video_id_for_program(&self, original_id: &str) -> Option<String> {
+        self.files.iter().find(|(_, id)| *id == original_id).map(|(path, _)| index::video_id(path))
+    }
+
+    // Channel numbers the library's sidecars use
+    fn library_channels(&self) -> Vec<u8> {
+        let mut numbers: Vec<u8> = self.programs.values().map(|program| program.channel_number).collect();
+        numbers.sort_unstable();
+        numbers.dedup();
+        numbers
+    }
+
+    // Whether a library file is a locked program or belongs to a locked channel
+    fn file_locked(&self, parental: &ParentalConfig, path: &Path) -> bool {
+        self.program_for_file(path)
+            .is_some_and(|program| parental.channel_locked(program.channel_number) || parental.program_locked(program))
+    }
+}
+
+// What a channel is airing right now
+#[derive(Serialize)]
+struct LiveStatus {
+    channel: u8,
+    callsign: String,
+    status: &'static str,
+    title: String,
+    segment_title: String,
+    kind: SlotKind,
+    filename: Option<String>,
+    video_id: Option<String>,
+    offset_seconds: u32,
+    remaining_seconds: u32,
+    starts_at: DateTime<FixedOffset>,
+    ends_at: DateTime<FixedOffset>,
 }
 
 #[tokio::main]
 async fn main() {
+    let args = Args::parse();
     println!("Starting server...");
 
-    // Read the list of videos from the "videos" directory
-    let video_dir = "./videos";
-    let videos = read_videos_from_directory(video_dir).unwrap_or_else(|err| {
-        eprintln!("Error reading videos directory: {}", err);
-        vec![]
+    let mut config_args = args.config;
+    config_args.bind_address = args.bind;
+    config_args.port = args.port;
+    let mut watcher = ConfigWatcher::new(config_args).unwrap_or_else(|err| {
+        eprintln!("Error loading configuration: {:#}", err);
+        std::process::exit(2);
+    });
+    let config = watcher.config().clone();
+
+    // Read the list of videos from the library
+    let video_list = Arc::new(Mutex::new(index_videos(&config)));
+
+    let time = config.time_settings().unwrap_or_else(|err| {
+        eprintln!("Error reading time settings: {:#}", err);
+        std::process::exit(2);
     });
-    let video_list = Arc::new(Mutex::new(videos));
+    let clock = clock::from_options(&time, args.simulate_at.as_deref(), args.speed).unwrap_or_else(|err| {
+        eprintln!("Error: {}", err);
+        std::process::exit(2);
+    });
+    if !config.auth.enabled() && !config.bind_address.is_loopback() {
+        eprintln!("Warning: listening on {} without authentication; anyone on the network can control playback", config.bind_address);
+    }
+    if args.simulate_at.is_some() {
+        println!("Simulating {} at {}x speed", time.format_day_time(&time.local(clock.now())), args.speed);
+    }
+    let history = WatchHistory::load(&config.history.file).unwrap_or_else(|err| {
+        eprintln!("Error loading watch history: {:#}", err);
+        std::process::exit(2);
+    });
+    let lineup = Arc::new(std::sync::RwLock::new(Arc::new(load_lineup(&config, &history, clock.clone()))));
+    let history = Arc::new(Mutex::new(history));
+    // Signalled when favorites, personal channels or the library change and the lineup needs rebuilding
+    let rebuild = Arc::new(Notify::new());
+    let settings = Arc::new(std::sync::RwLock::new(config.clone()));
+
+    // Write tune-ins and plays in batches rather than on every request, and once more at shutdown
+    let unsaved_history = (Arc::clone(&history), Arc::clone(&settings));
+    {
+        let history = Arc::clone(&history);
+        let settings = Arc::clone(&settings);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(HISTORY_SAVE_INTERVAL);
+            loop {
+                interval.tick().await;
+                let mut history = history.lock().await;
+                if history.take_unsaved() {
+                    let config = settings.read().unwrap().clone();
+                    save_history(&history, &config);
+                }
+            }
+        });
+    }
+
+    // Pick up edits to the config file and channel map without a restart
+    {
+        let lineup = Arc::clone(&lineup);
+        let settings = Arc::clone(&settings);
+        let video_list = Arc::clone(&video_list);
+        let history = Arc::clone(&history);
+        let rebuild = Arc::clone(&rebuild);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(RELOAD_INTERVAL);
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    _ = rebuild.notified() => {
+                        let config = settings.read().unwrap().clone();
+                        let snapshot = history.lock().await.clone();
+                        let new_lineup = load_lineup(&config, &snapshot, clock.clone());
+                        *lineup.write().unwrap() = Arc::new(new_lineup);
+                        continue;
+                    }
+                }
+                match watcher.poll() {
+                    None => {}
+                    Some(Err(err)) => eprintln!("Error reloading configuration, keeping the current one: {:#}", err),
+                    Some(Ok(reload)) => {
+                        for field in &reload.restart_required {
+                            eprintln!("Configuration: {} changed; restart the server to apply it", field);
+                        }
+                        *video_list.lock().await = index_videos(&reload.config);
+                        let snapshot = history.lock().await.clone();
+                        let new_lineup = load_lineup(&reload.config, &snapshot, clock.clone());
+                        *lineup.write().unwrap() = Arc::new(new_lineup);
+                        *settings.write().unwrap() = reload.config;
+                        println!("Configuration reloaded");
+                    }
+                }
+            }
+        });
+    }
+
+    let lineup_filter = warp::any().map(move || Arc::clone(&lineup.read().unwrap()));
+    let settings_filter = warp::any().map(move || settings.read().unwrap().clone());
+    let sessions = Arc::new(Sessions::default());
+    let sessions_filter = warp::any().map(move || Arc::clone(&sessions));
+    let attempts = Arc::new(Attempts::default());
+    let attempts_filter = warp::any().map(move || Arc::clone(&attempts));
+    // Address wrong guesses are counted against; clients with no known address share one count
+    let client_filter = warp::addr::remote()
+        .map(|remote: Option<SocketAddr>| remote.map_or(IpAddr::from([0, 0, 0, 0]), |remote| remote.ip()));
+    // Whose watch history a request belongs to: the API token's name, the name
+    // given at login, or the household as a whole
+    let viewer_filter = warp::header::optional::<String>("authorization")
+        .and(warp::cookie::optional::<String>(auth::SESSION_COOKIE))
+        .and(settings_filter.clone())
+        .and(sessions_filter.clone())
+        .map(|authorization: Option<String>, session: Option<String>, config: Config, sessions: Arc<Sessions>| {
+            let token_name = authorization
+                .as_deref()
+                .and_then(|value| value.strip_prefix("Bearer "))
+                .and_then(|token| config.auth.token_name(token.trim()).map(str::to_string));
+            token_name
+                .or_else(|| session.and_then(|id| sessions.viewer(&id)))
+                .unwrap_or_else(|| history::DEFAULT_VIEWER.to_string())
+        });
+    let history_filter = warp::any().map(move || Arc::clone(&history));
+    let rebuild_filter = warp::any().map(move || Arc::clone(&rebuild));
+    let unlocks = Arc::new(Unlocks::default());
+    let unlocks_filter = warp::any().map(move || Arc::clone(&unlocks));
+    // True while this client's PIN unlock window is open
+    let unlocked_filter = warp::cookie::optional::<String>(parental::UNLOCK_COOKIE)
+        .and(unlocks_filter.clone())
+        .map(|cookie: Option<String>, unlocks: Arc<Unlocks>| cookie.is_some_and(|id| unlocks.is_unlocked(&id)));
+
+    // Every request passes through here before reaching a route: work out who
+    // is calling and refuse anything their role does not allow
+    let authorize = warp::method()
+        .and(warp::path::full())
+        .and(warp::header::optional::<String>("authorization"))
+        .and(warp::cookie::optional::<String>(auth::SESSION_COOKIE))
+        .and(settings_filter.clone())
+        .and(sessions_filter.clone())
+        .and_then(
+            |method: Method, path: FullPath, authorization: Option<String>, session: Option<String>, config: Config, sessions: Arc<Sessions>| async move {
+                let required = match auth::required_role(&method, path.as_str()) {
+                    Some(required) if config.auth.enabled() => required,
+                    _ => return Ok::<_, warp::Rejection>(()),
+                };
+                let role = match authorization.as_deref().and_then(|value| value.strip_prefix("Bearer ")) {
+                    Some(token) => Some(config.auth.token_role(token.trim()).ok_or(ApiError::Unauthorized)?),
+                    None => session.and_then(|id| sessions.role(&id)),
+                };
+                match role {
+                    None => Err(ApiError::Unauthorized.into()),
+                    Some(role) if role < required => Err(ApiError::Forbidden.into()),
+                    Some(_) => Ok(()),
+                }
+            },
+        )
+        .untuple_one();
+
+    let login = warp::path!("login")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(settings_filter.clone())
+        .and(sessions_filter.clone())
+        .and(attempts_filter.clone())
+        .and(client_filter)
+        .and_then(|request: LoginRequest, config: Config, sessions: Arc<Sessions>, attempts: Arc<Attempts>, client: IpAddr| async move {
+            if !config.auth.enabled() {
+                return Ok::<_, warp::Rejection>(warp::reply::json(&LoginResponse { role: Role::Admin }).into_response());
+            }
+            if let Some(wait) = attempts.locked_out(client, Instant::now()) {
+                return Err(ApiError::TooManyAttempts(wait.as_secs().max(1)).into());
+            }
+            let Some(role) = config.auth.password_role(&request.password) else {
+                attempts.fail(client, Instant::now());
+                tokio::time::sleep(LOGIN_FAILURE_DELAY).await;
+                return Err(ApiError::InvalidCredentials.into());
+            };
+            attempts.succeed(client);
+            let length = config.auth.session_length();
+            let viewer = request.viewer.map(|name| name.trim().to_string()).filter(|name| !name.is_empty());
+            let id = sessions.create(role, viewer, length);
+            let cookie = format!(
+                "{}={}; Path=/; HttpOnly; SameSite=Strict; Max-Age={}",
+                auth::SESSION_COOKIE,
+                id,
+                length.as_secs()
+            );
+            Ok(warp::reply::with_header(warp::reply::json(&LoginResponse { role }), "set-cookie", cookie).into_response())
+        });
+
+    let logout = warp::path!("logout")
+        .and(warp::post())
+        .and(warp::cookie::optional::<String>(auth::SESSION_COOKIE))
+        .and(sessions_filter)
+        .map(|session: Option<String>, sessions: Arc<Sessions>| {
+            if let Some(id) = session {
+                sessions.remove(&id);
+            }
+            let cookie = format!("{}=; Path=/; HttpOnly; SameSite=Strict; Max-Age=0", auth::SESSION_COOKIE);
+            warp::reply::with_header(warp::reply::json(&serde_json::json!({})), "set-cookie", cookie)
+        });
+
+    let unlock = warp::path!("unlock")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(settings_filter.clone())
+        .and(unlocks_filter.clone())
+        .and(attempts_filter)
+        .and(client_filter)
+        .and_then(|request: UnlockRequest, config: Config, unlocks: Arc<Unlocks>, attempts: Arc<Attempts>, client: IpAddr| async move {
+            let length = config.parental.unlock_length();
+            if !config.parental.enabled() {
+                return Ok::<_, warp::Rejection>(warp::reply::json(&UnlockResponse { unlocked_seconds: length.as_secs() }).into_response());
+            }
+            if let Some(wait) = attempts.locked_out(client, Instant::now()) {
+                return Err(ApiError::TooManyAttempts(wait.as_secs().max(1)).into());
+            }
+            if !config.parental.check_pin(&request.pin) {
+                attempts.fail(client, Instant::now());
+                tokio::time::sleep(LOGIN_FAILURE_DELAY).await;
+                return Err(ApiError::InvalidPin.into());
+            }
+            attempts.succeed(client);
+            let id = unlocks.create(length);
+            let cookie = format!(
+                "{}={}; Path=/; HttpOnly; SameSite=Strict; Max-Age={}",
+                parental::UNLOCK_COOKIE,
+                id,
+                length.as_secs()
+            );
+            let body = warp::reply::json(&UnlockResponse { unlocked_seconds: length.as_secs() });
+            Ok(warp::reply::with_header(body, "set-cookie", cookie).into_response())
+        });
+
+    // Close the unlock window early
+    let lock = warp::path!("lock")
+        .and(warp::post())
+        .and(warp::cookie::optional::<String>(parental::UNLOCK_COOKIE))
+        .and(unlocks_filter)
+        .map(|cookie: Option<String>, unlocks: Arc<Unlocks>| {
+            if let Some(id) = cookie {
+                unlocks.remove(&id);
+            }
+            let cookie = format!("{}=; Path=/; HttpOnly; SameSite=Strict; Max-Age=0", parental::UNLOCK_COOKIE);
+            warp::reply::with_header(warp::reply::json(&serde_json::json!({})), "set-cookie", cookie)
+        });
 
     // Create a broadcast channel for SSE
-    let (tx, _) = broadcast::channel(10);
+    let (tx, _) = broadcast::channel(config.event_capacity);
     let tx_filter = warp::any().map(move || tx.clone());
 
     let video_list_filter = warp::any().map(move || Arc::clone(&video_list));
 
-    let list_videos = warp::path("videos")
+    let list_videos = warp::path!("videos")
         .and(warp::get())
         .and(video_list_filter.clone())
-        .and_then(|videos: Arc<Mutex<Vec<String>>>| async move {
-            let videos = videos.lock().await;
-            println!("Listing videos...");
-            Ok::<_, warp::Rejection>(warp::reply::json(&*videos))
-        });
+        .and(lineup_filter.clone())
+        .and(settings_filter.clone())
+        .and(unlocked_filter.clone())
+        .and_then(list_videos);
 
-    let play_video = warp::path("play")
+    // Stream a video by ID; nothing outside the library roots can be reached
+    let stream_video = warp::path!("videos" / String)
+        .and(warp::get())
+        .and(video_list_filter.clone())
+        .and(lineup_filter.clone())
+        .and(settings_filter.clone())
+        .and(unlocked_filter.clone())
+        .and(viewer_filter.clone())
+        .and(history_filter.clone())
+        .and_then(stream_video);
+
+    let play_video = warp::path!("play")
         .and(warp::post())
         .and(warp::body::json())
         .and(video_list_filter.clone())
+        .and(settings_filter.clone())
+        .and(lineup_filter.clone())
+        .and(unlocked_filter.clone())
+        .and(viewer_filter.clone())
+        .and(history_filter.clone())
+        .and_then(play_video)
         .and(tx_filter.clone())
-        .and_then(move |video: Video, _videos: Arc<Mutex<Vec<String>>>, tx: broadcast::Sender<String>| {
-            let video_dir = video_dir.to_string();
-            async move {
-                let filename = format!("{}/{}", video_dir, video.filename); // Include directory path
-                println!("Playing video: {}", filename);
-
-                let tx_clone = tx.clone();
-                let video_filename = video.filename.clone();
-                tokio::spawn(async move {
-                    let output = Command::new("mpv")
-                        .arg(&filename) // Use the filename with the directory path
-                        .output()
-                        .expect("failed to execute process");
-
-                    println!("{:?}", output);
-
-                    tx_clone.send(video_filename).unwrap();
-                });
-
-                Ok::<_, warp::Rejection>(warp::reply::json(&video))
+        .map(|(entry, child): (VideoEntry, tokio::process::Child), tx: broadcast::Sender<String>| {
+            announce_player_exit(child, tx, entry.filename.clone());
+            warp::reply::json(&entry)
+        });
+
+    // Admin only: move a video to the trash with its sidecar and thumbnails,
+    // and drop it from the index
+    let delete_video = warp::path!("videos" / String)
+        .and(warp::delete())
+        .and(video_list_filter.clone())
+        .and(lineup_filter.clone())
+        .and(settings_filter.clone())
+        .and(rebuild_filter.clone())
+        .and_then(|id: String, videos: Arc<Mutex<VideoIndex>>, lineup: Arc<Lineup>, config: Config, rebuild: Arc<Notify>| async move {
+            let mut videos = videos.lock().await;
+            let path = videos.resolve(&id).map_err(ApiError::from)?;
+            let (title, original_id) = match sidecar::load(&sidecar::sidecar_path(&path)) {
+                Ok(loaded) => (loaded.metadata.title, loaded.metadata.original_id),
+                Err(_) => (path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default(), String::new()),
+            };
+            config
+                .trash()
+                .put(&path, &title, &original_id, lineup.clock.now())
+                .map_err(|e| ApiError::Internal(format!("could not delete video: {:#}", e)))?;
+            println!("Moved video to the trash: {}", path.display());
+            let entry = videos.remove(&id);
+            // Take it off the air now rather than at the next config change
+            rebuild.notify_one();
+            Ok::<_, warp::Rejection>(warp::reply::json(&entry))
+        });
+
+    let get_channels = warp::path!("channels")
+        .and(warp::get())
+        .and(settings_filter.clone())
+        .and_then(|config: Config| async move {
+            let map = ChannelMap::load(&config.channel_map).map_err(|e| ApiError::Internal(format!("{:#}", e)))?;
+            Ok::<_, warp::Rejection>(warp::reply::json(&map))
+        });
+
+    // Admin only: replace the channel map; the lineup picks it up on the next reload check
+    let put_channels = warp::path!("channels")
+        .and(warp::put())
+        .and(warp::body::json())
+        .and(settings_filter.clone())
+        .and_then(|map: ChannelMap, config: Config| async move {
+            map.save(&config.channel_map).map_err(|e| ApiError::InvalidChannelMap(format!("{:#}", e)))?;
+            println!("Channel map updated");
+            Ok::<_, warp::Rejection>(warp::reply::json(&map))
+        });
+
+    let favorites = warp::path!("favorites")
+        .and(warp::get())
+        .and(lineup_filter.clone())
+        .and(viewer_filter.clone())
+        .and(history_filter.clone())
+        .and_then(|lineup: Arc<Lineup>, viewer: String, history: Arc<Mutex<WatchHistory>>| async move {
+            let history = history.lock().await;
+            Ok::<_, warp::Rejection>(warp::reply::json(&favorite_list(&lineup, &history, &viewer)))
+        });
+
+    // Star (PUT) or unstar (DELETE) a program by its video ID
+    let set_favorite = warp::path!("favorites" / String)
+        .and(warp::put().map(|| true).or(warp::delete().map(|| false)).unify())
+        .and(video_list_filter.clone())
+        .and(lineup_filter.clone())
+        .and(settings_filter.clone())
+        .and(viewer_filter.clone())
+        .and(history_filter.clone())
+        .and(rebuild_filter.clone())
+        .and_then(|id: String, starred: bool, videos: Arc<Mutex<VideoIndex>>, lineup: Arc<Lineup>, config: Config, viewer: String, history: Arc<Mutex<WatchHistory>>, rebuild: Arc<Notify>| async move {
+            let path = videos.lock().await.resolve(&id).map_err(ApiError::from)?;
+            let program = lineup.program_for_file(&path).ok_or(ApiError::NoMetadata)?;
+            let mut history = history.lock().await;
+            if history.set_favorite(&viewer, &program.original_id, starred) {
+                save_history(&history, &config);
+                // Personal channels made from favorites change with them
+                rebuild.notify_one();
             }
+            Ok::<_, warp::Rejection>(warp::reply::json(&favorite_list(&lineup, &history, &viewer)))
         });
 
+    // Any viewer can add a channel of their own to the channel map
+    let add_personal_channel = warp::path!("channels" / "personal")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(lineup_filter.clone())
+        .and(settings_filter.clone())
+        .and(viewer_filter.clone())
+        .and(rebuild_filter.clone())
+        .and_then(|request: PersonalChannelRequest, lineup: Arc<Lineup>, config: Config, viewer: String, rebuild: Arc<Notify>| async move {
+            let mut map = ChannelMap::load(&config.channel_map).map_err(|e| ApiError::Internal(format!("{:#}", e)))?;
+            let taken = lineup.library_channels();
+            let number = match request.number {
+                Some(number) if map.channel(number).is_some() || taken.contains(&number) => {
+                    return Err(ApiError::ChannelTaken(number).into());
+                }
+                Some(number) => number,
+                None => map.free_personal_number(&taken).ok_or(ApiError::NoFreeChannel)?,
+            };
+            let channel = ChannelConfig {
+                number,
+                callsign: None,
+                dayparts: request.dayparts,
+                broadcast_hours: request.broadcast_hours,
+                name: Some(request.name),
+                selection: Some(request.selection),
+                owner: Some(viewer),
+            };
+            map.channels.push(channel.clone());
+            map.save(&config.channel_map).map_err(|e| ApiError::InvalidChannelMap(format!("{:#}", e)))?;
+            rebuild.notify_one();
+            println!("Added personal channel {}", number);
+            Ok::<_, warp::Rejection>(warp::reply::with_status(warp::reply::json(&channel), warp::http::StatusCode::CREATED))
+        });
+
+    let remove_personal_channel = warp::path!("channels" / "personal" / u8)
+        .and(warp::delete())
+        .and(settings_filter.clone())
+        .and(viewer_filter.clone())
+        .and(rebuild_filter)
+        .and_then(|number: u8, config: Config, viewer: String, rebuild: Arc<Notify>| async move {
+            let mut map = ChannelMap::load(&config.channel_map).map_err(|e| ApiError::Internal(format!("{:#}", e)))?;
+            let position = map
+                .channels
+                .iter()
+                .position(|channel| channel.number == number && channel.selection.is_some())
+                .ok_or(ApiError::ChannelNotFound(number))?;
+            if map.channels[position].owner.as_deref().is_some_and(|owner| owner != viewer) {
+                return Err(ApiError::NotOwner.into());
+            }
+            let channel = map.channels.remove(position);
+            map.save(&config.channel_map).map_err(|e| ApiError::InvalidChannelMap(format!("{:#}", e)))?;
+            rebuild.notify_one();
+            println!("Removed personal channel {}", number);
+            Ok::<_, warp::Rejection>(warp::reply::json(&channel))
+        });
+
+    let subscriptions = warp::path!("subscriptions")
+        .and(warp::get())
+        .and(settings_filter.clone())
+        .and_then(|config: Config| async move {
+            let list = SubscriptionList::load(&config.downloads.subscriptions_file).map_err(|e| ApiError::Internal(format!("{:#}", e)))?;
+            Ok::<_, warp::Rejection>(warp::reply::json(&list.subscriptions))
+        });
+
+    // Admin only: save a search for the menu to rerun; the server itself never downloads
+    let add_subscription = warp::path!("subscriptions")
+        .and(warp::post())
+        .and(warp::body::json())
+        .and(settings_filter.clone())
+        .and_then(|subscription: Subscription, config: Config| async move {
+            let path = &config.downloads.subscriptions_file;
+            let mut list = SubscriptionList::load(path).map_err(|e| ApiError::Internal(format!("{:#}", e)))?;
+            subscription.validate().map_err(ApiError::InvalidSubscription)?;
+            if list.get(&subscription.name).is_some() {
+                return Err(ApiError::SubscriptionExists(subscription.name).into());
+            }
+            list.subscriptions.push(subscription.clone());
+            list.save(path).map_err(|e| ApiError::Internal(format!("{:#}", e)))?;
+            println!("Added subscription {}", subscription.name);
+            Ok::<_, warp::Rejection>(warp::reply::with_status(warp::reply::json(&subscription), warp::http::StatusCode::CREATED))
+        });
+
+    let remove_subscription = warp::path!("subscriptions" / String)
+        .and(warp::delete())
+        .and(settings_filter.clone())
+        .and_then(|name: String, config: Config| async move {
+            // Names may contain spaces, which arrive percent-encoded
+            let name = percent_encoding::percent_decode_str(&name).decode_utf8_lossy().into_owned();
+            let path = &config.downloads.subscriptions_file;
+            let mut list = SubscriptionList::load(path).map_err(|e| ApiError::Internal(format!("{:#}", e)))?;
+            let removed = list.remove(&name).ok_or(ApiError::SubscriptionNotFound(name))?;
+            list.save(path).map_err(|e| ApiError::Internal(format!("{:#}", e)))?;
+            println!("Removed subscription {}", removed.name);
+            Ok::<_, warp::Rejection>(warp::reply::json(&removed))
+        });
+
+    let live = warp::path!("live" / u8)
+        .and(warp::get())
+        .and(lineup_filter.clone())
+        .and(settings_filter.clone())
+        .and(unlocked_filter)
+        .and(viewer_filter.clone())
+        .and(history_filter.clone())
+        .and_then(|channel: u8, lineup: Arc<Lineup>, config: Config, unlocked: bool, viewer: String, history: Arc<Mutex<WatchHistory>>| async move {
+            let now = lineup.clock.now();
+            if !unlocked && lineup.channel_locked(&config.parental, channel, now) {
+                return Err(ApiError::Locked.into());
+            }
+            let status = live_status(&lineup, channel, now).ok_or(ApiError::ChannelNotFound(channel))?;
+            let event = WatchEvent {
+                kind: WatchKind::Tune,
+                at: now,
+                channel: Some(channel),
+                video_id: status.video_id.clone(),
+                original_id: lineup.program_at(channel, now).map(|program| program.original_id.clone()),
+                title: status.title.clone(),
+            };
+            record_watch(&history, &viewer, event).await;
+            Ok::<_, warp::Rejection>(warp::reply::json(&status))
+        });
+
+    let watch_history = warp::path!("history")
+        .and(warp::get())
+        .and(viewer_filter.clone())
+        .and(history_filter.clone())
+        .and_then(|viewer: String, history: Arc<Mutex<WatchHistory>>| async move {
+            let history = history.lock().await;
+            let viewer_history = history.viewer(&viewer).cloned().unwrap_or_default();
+            let response = HistoryResponse {
+                viewer,
+                events: viewer_history.events.into_iter().rev().collect(),
+                resume: viewer_history.resume,
+            };
+            Ok::<_, warp::Rejection>(warp::reply::json(&response))
+        });
+
+    let get_resume = warp::path!("resume" / String)
+        .and(warp::get())
+        .and(video_list_filter.clone())
+        .and(viewer_filter.clone())
+        .and(history_filter.clone())
+        .and_then(|id: String, videos: Arc<Mutex<VideoIndex>>, viewer: String, history: Arc<Mutex<WatchHistory>>| async move {
+            videos.lock().await.get(&id).ok_or(ApiError::VideoNotFound)?;
+            let history = history.lock().await;
+            let point = history.resume_point(&viewer, &id);
+            let status = ResumeStatus {
+                position_seconds: point.map(|p| p.position_seconds).unwrap_or(0),
+                duration_seconds: point.and_then(|p| p.duration_seconds),
+                finished: point.is_some_and(|p| p.finished),
+                updated_at: point.map(|p| p.updated_at),
+                id,
+            };
+            Ok::<_, warp::Rejection>(warp::reply::json(&status))
+        });
+
+    // Clients report where playback stopped so it can pick up there next time
+    let put_resume = warp::path!("resume" / String)
+        .and(warp::put())
+        .and(warp::body::json())
+        .and(video_list_filter.clone())
+        .and(lineup_filter.clone())
+        .and(settings_filter.clone())
+        .and(viewer_filter)
+        .and(history_filter)
+        .and_then(put_resume);
+
     let sse_video = warp::path("sse")
         .and(warp::get())
         .and(tx_filter)
@@ -83,31 +768,490 @@ async fn main() {
         });
 
     let static_files = warp::path::end()
-        .and(warp::fs::dir("./static"));
+        .and(warp::fs::dir(config.static_dir.clone()));
 
-    let routes = list_videos.or(play_video).or(sse_video).or(static_files);
+    let routes = authorize
+        .and(
+            login
+                .or(logout)
+                .or(unlock)
+                .or(lock)
+                .or(list_videos)
+                .or(stream_video)
+                .or(delete_video)
+                .or(play_video)
+                .or(get_channels)
+                .or(put_channels)
+                .or(add_personal_channel)
+                .or(remove_personal_channel)
+                .or(subscriptions)
+                .or(add_subscription)
+                .or(remove_subscription)
+                .or(favorites)
+                .or(set_favorite)
+                .or(live)
+                .or(watch_history)
+                .or(get_resume)
+                .or(put_resume)
+                .or(sse_video)
+                .or(static_files),
+        )
+        .recover(api::recover);
+
+    let address = SocketAddr::new(config.bind_address, config.port);
+    println!("Server running on http://{}", address);
+
+    let (_, server) = warp::serve(routes).bind_with_graceful_shutdown(address, async {
+        tokio::signal::ctrl_c().await.ok();
+    });
+    server.await;
 
-    println!("Server running on http://localhost:3030");
+    let (history, settings) = unsaved_history;
+    let mut history = history.lock().await;
+    if history.take_unsaved() {
+        let config = settings.read().unwrap().clone();
+        save_history(&history, &config);
+    }
+}
 
-    warp::serve(routes).run(([127, 0, 0, 1], 3030)).await;
+// PUT /resume/{id}: record where a viewer stopped playing a video
+async fn put_resume(
+    id: String,
+    request: ResumeRequest,
+    videos: Arc<Mutex<VideoIndex>>,
+    lineup: Arc<Lineup>,
+    config: Config,
+    viewer: String,
+    history: Arc<Mutex<WatchHistory>>,
+) -> Result<impl Reply, warp::Rejection> {
+    let path = videos.lock().await.resolve(&id).map_err(ApiError::from)?;
+    // Positions come from the client, so they may be anywhere up to u32::MAX
+    let finished = request.finished
+        || request.duration_seconds.is_some_and(|duration| request.position_seconds.saturating_add(FINISHED_MARGIN_SECONDS) >= duration);
+    let point = ResumePoint {
+        position_seconds: request.position_seconds,
+        duration_seconds: request.duration_seconds,
+        finished,
+        original_id: lineup.program_for_file(&path).map(|program| program.original_id.clone()),
+        updated_at: lineup.clock.now(),
+    };
+    let mut history = history.lock().await;
+    history.set_resume_point(&viewer, &id, point);
+    save_history(&history, &config);
+    let point = history.resume_point(&viewer, &id);
+    Ok(warp::reply::json(&point))
 }
 
-fn read_videos_from_directory(dir: &str) -> Result<Vec<String>, std::io::Error> {
-    let mut videos = Vec::new();
-    for entry in fs::read_dir(dir)? {
-        let entry = entry?;
-        let path = entry.path();
-        if path.is_file() {
-            if let Some(extension) = path.extension() {
-                if extension == "mp4" || extension == "avi" || extension == "mkv" {
-                    if let Some(filename) = path.file_name() {
-                        if let Some(filename_str) = filename.to_str() {
-                            videos.push(filename_str.to_string());
-                        }
-                    }
-                }
-            }
+// GET /videos: every library video, leaving out locked ones until the PIN is entered
+async fn list_videos(
+    videos: Arc<Mutex<VideoIndex>>,
+    lineup: Arc<Lineup>,
+    config: Config,
+    unlocked: bool,
+) -> Result<impl Reply, warp::Rejection> {
+    let videos = videos.lock().await;
+    println!("Listing videos...");
+    let entries: Vec<&VideoEntry> = videos
+        .entries()
+        .iter()
+        .filter(|entry| unlocked || !lineup.file_locked(&config.parental, &entry.path))
+        .collect();
+    Ok(warp::reply::json(&entries))
+}
+
+// GET /videos/{id}: stream a video by ID; nothing outside the library roots can be reached
+async fn stream_video(
+    id: String,
+    videos: Arc<Mutex<VideoIndex>>,
+    lineup: Arc<Lineup>,
+    config: Config,
+    unlocked: bool,
+    viewer: String,
+    history: Arc<Mutex<WatchHistory>>,
+) -> Result<warp::reply::Response, warp::Rejection> {
+    let path = videos.lock().await.resolve(&id).map_err(ApiError::from)?;
+    if !unlocked && lineup.file_locked(&config.parental, &path) {
+        return Err(ApiError::Locked.into());
+    }
+    let event = play_event(&lineup, &id, &path, lineup.clock.now());
+    record_watch(&history, &viewer, event).await;
+    let file = tokio::fs::File::open(&path).await.map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => ApiError::VideoNotFound,
+        _ => ApiError::Internal(format!("could not open video: {}", e)),
+    })?;
+    let mut response = warp::reply::Response::new(Body::wrap_stream(ReaderStream::new(file)));
+    response.headers_mut().insert(
+        warp::http::header::CONTENT_TYPE,
+        warp::http::HeaderValue::from_static(content_type(&path)),
+    );
+    Ok(response)
+}
+
+// POST /play: start the player on a library file, by ID or by plain file name
+async fn play_video(
+    request: PlayRequest,
+    videos: Arc<Mutex<VideoIndex>>,
+    config: Config,
+    lineup: Arc<Lineup>,
+    unlocked: bool,
+    viewer: String,
+    history: Arc<Mutex<WatchHistory>>,
+) -> Result<(VideoEntry, tokio::process::Child), warp::Rejection> {
+    // Only files from the index are ever handed to the player
+    let entry = {
+        let videos = videos.lock().await;
+        match (&request.id, &request.filename) {
+            (Some(id), _) => videos.get(id).ok_or(ApiError::VideoNotFound)?.clone(),
+            (None, Some(filename)) => videos.find_filename(filename).map_err(ApiError::from)?.clone(),
+            (None, None) => return Err(ApiError::MissingId.into()),
+        }
+    };
+    let path = entry.resolve().map_err(ApiError::from)?;
+    if !unlocked && lineup.file_locked(&config.parental, &path) {
+        return Err(ApiError::Locked.into());
+    }
+    println!("Playing video: {}", path.display());
+
+    let start = match history.lock().await.resume_point(&viewer, &entry.id) {
+        Some(point) if request.resume && !point.finished => point.position_seconds,
+        _ => 0,
+    };
+    let mut command = tokio::process::Command::new(&config.player);
+    if start > 0 {
+        command.arg(format!("--start={}", start));
+    }
+
+    // Starting the player here lets a missing player be reported to the client
+    let child = command
+        .arg(&path)
+        .stdin(Stdio::null())
+        .spawn()
+        .map_err(|e| ApiError::PlayerUnavailable(format!("{}: {}", config.player, e)))?;
+
+    let event = play_event(&lineup, &entry.id, &path, lineup.clock.now());
+    record_watch(&history, &viewer, event).await;
+    Ok((entry, child))
+}
+
+// Tell SSE listeners which video finished once the player exits
+fn announce_player_exit(mut child: tokio::process::Child, tx: broadcast::Sender<String>, video_filename: String) {
+    tokio::spawn(async move {
+        match child.wait().await {
+            Ok(status) => println!("Player exited: {}", status),
+            Err(e) => eprintln!("Error waiting for the player: {}", e),
+        }
+        // Nobody listening is fine
+        let _ = tx.send(video_filename);
+    });
+}
+
+// Index every library root, logging roots that can't be read
+fn index_videos(config: &Config) -> VideoIndex {
+    let (index, errors) = VideoIndex::build(&config.library_roots);
+    for (root, err) in errors {
+        eprintln!("Error reading videos directory {}: {}", root.display(), err);
+    }
+    index
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().map(|ext| ext.to_string_lossy().to_lowercase()).as_deref() {
+        Some("mp4") => "video/mp4",
+        Some("webm") => "video/webm",
+        Some("mkv") => "video/x-matroska",
+        Some("mov") => "video/quicktime",
+        Some("avi") => "video/x-msvideo",
+        Some("flv") => "video/x-flv",
+        _ => "application/octet-stream",
+    }
+}
+
+// A play of a library file, with its program's details when it has a sidecar
+fn play_event(lineup: &Lineup, id: &str, path: &Path, now: DateTime<Utc>) -> WatchEvent {
+    let program = lineup.program_for_file(path);
+    WatchEvent {
+        kind: WatchKind::Play,
+        at: now,
+        channel: None,
+        video_id: Some(id.to_string()),
+        original_id: program.map(|program| program.original_id.clone()),
+        title: match program {
+            Some(program) => program.title.clone(),
+            None => path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default(),
+        },
+    }
+}
+
+fn favorite_list(lineup: &Lineup, history: &WatchHistory, viewer: &str) -> Vec<Favorite> {
+    let starred = history.viewer(viewer).map(|history| history.favorites.clone()).unwrap_or_default();
+    starred
+        .into_iter()
+        .map(|original_id| Favorite {
+            video_id: lineup.video_id_for_program(&original_id),
+            title: lineup.programs.get(&original_id).map(|program| program.title.clone()).unwrap_or_default(),
+            original_id,
+        })
+        .collect()
+}
+
+async fn record_watch(history: &Mutex<WatchHistory>, viewer: &str, event: WatchEvent) {
+    history.lock().await.record(viewer, event);
+}
+
+// A history that can't be written is logged rather than failing the request
+fn save_history(history: &WatchHistory, config: &Config) {
+    if let Err(err) = history.save(&config.history.file) {
+        eprintln!("Error saving watch history: {:#}", err);
+    }
+}
+
+// The lineup for the current configuration, or an empty one if the library can't be loaded
+fn load_lineup(config: &Config, history: &WatchHistory, clock: SharedClock) -> Lineup {
+    let time = config.time_settings().unwrap_or_default();
+    let channel_map = ChannelMap::load(&config.channel_map).unwrap_or_else(|err| {
+        eprintln!("Error loading channel map: {:#}", err);
+        ChannelMap::default()
+    });
+    let schedule_config = config.history.schedule_config(history, clock.now());
+    build_lineup(&config.library_roots, &channel_map, &schedule_config, time, clock.clone()).unwrap_or_else(|err| {
+        eprintln!("Error building schedule: {}", err);
+        Lineup {
+            tuner: Tuner::new(WeeklySchedule::default(), &[], &channel_map, time),
+            clock,
+            programs: HashMap::new(),
+            files: HashMap::new(),
         }
+    })
+}
+
+fn build_lineup(roots: &[PathBuf], channel_map: &ChannelMap, schedule_config: &ScheduleConfig, time: TimeSettings, clock: SharedClock) -> Result<Lineup, std::io::Error> {
+    let scan = library::scan_roots(roots)?;
+    let programs: Vec<_> = scan.items.iter().map(|item| item.metadata.clone()).collect();
+    let schedule = schedule::build_weekly_schedule(&programs, schedule_config, channel_map);
+
+    let programs = scan.items.iter().map(|item| (item.metadata.original_id.clone(), item.metadata.clone())).collect();
+    let files = scan
+        .items
+        .iter()
+        .filter_map(|item| Some((item.video_path.canonicalize().ok()?, item.metadata.original_id.clone())))
+        .collect();
+
+    Ok(Lineup { tuner: Tuner::new(schedule, &scan.items, channel_map, time), clock, programs, files })
+}
+
+fn live_status(lineup: &Lineup, channel: u8, now: DateTime<Utc>) -> Option<LiveStatus> {
+    let tuning = lineup.tuner.tune(channel, now)?;
+    let timezone = lineup.tuner.time().timezone;
+    let (filename, video_id) = match &tuning.source {
+        Source::File(path) => (
+            path.file_name().map(|name| name.to_string_lossy().to_string()),
+            path.canonicalize().ok().map(|path| index::video_id(&path)),
+        ),
+        Source::Pattern(_) => (None, None),
+    };
+
+    Some(LiveStatus {
+        channel,
+        callsign: tuning.callsign,
+        status: if tuning.off_air { "OFF AIR" } else { "ON AIR" },
+        title: tuning.title,
+        segment_title: tuning.segment_title,
+        kind: tuning.kind,
+        filename,
+        video_id,
+        offset_seconds: tuning.offset_seconds,
+        remaining_seconds: tuning.remaining_seconds,
+        starts_at: tuning.starts_at.with_timezone(&timezone).fixed_offset(),
+        ends_at: tuning.ends_at.with_timezone(&timezone).fixed_offset(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use video_server::clock::SimulatedClock;
+
+    #[tokio::test]
+    async fn put_resume_handles_positions_near_u32_max() {
+        let dir = std::env::temp_dir().join(format!("resume-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("show.mp4"), b"").unwrap();
+
+        let config = Config {
+            library_roots: vec![dir.clone()],
+            history: history::HistorySettings { file: dir.join("history.json"), ..Default::default() },
+            channel_map: dir.join("channels.json"),
+            ..Default::default()
+        };
+        let clock: SharedClock = Arc::new(SimulatedClock::frozen(Utc.with_ymd_and_hms(1994, 10, 17, 23, 30, 0).unwrap()));
+        let history = Arc::new(Mutex::new(WatchHistory::default()));
+        let lineup = Arc::new(load_lineup(&config, &WatchHistory::default(), clock));
+        let videos = Arc::new(Mutex::new(index_videos(&config)));
+        let id = videos.lock().await.entries()[0].id.clone();
+
+        let route = warp::path!("resume" / String)
+            .and(warp::put())
+            .and(warp::body::json())
+            .and(warp::any().map(move || Arc::clone(&videos)))
+            .and(warp::any().map(move || Arc::clone(&lineup)))
+            .and(warp::any().map(move || config.clone()))
+            .and(warp::any().map(|| "tablet".to_string()))
+            .and(warp::any().map(move || Arc::clone(&history)))
+            .and_then(put_resume);
+
+        for (position, duration, finished) in [(u32::MAX, 100, true), (u32::MAX - 10, u32::MAX, true), (10, 100, false)] {
+            let response = warp::test::request()
+                .method("PUT")
+                .path(&format!("/resume/{}", id))
+                .json(&serde_json::json!({"position_seconds": position, "duration_seconds": duration}))
+                .reply(&route)
+                .await;
+            assert_eq!(response.status(), 200);
+            let point: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+            assert_eq!(point["position_seconds"], position);
+            assert_eq!(point["finished"], finished, "position {} of {}", position, duration);
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    // Library root with one video and a symlink in it pointing outside the root
+    fn escape_library(name: &str) -> (PathBuf, Config) {
+        let dir = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let root = dir.join("videos");
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::create_dir_all(dir.join("private")).unwrap();
+        std::fs::write(root.join("show.mp4"), b"show").unwrap();
+        std::fs::write(dir.join("private/secret.mp4"), b"secret").unwrap();
+        std::os::unix::fs::symlink(dir.join("private/secret.mp4"), root.join("escape.mp4")).unwrap();
+        let config = Config {
+            library_roots: vec![root],
+            history: history::HistorySettings { file: dir.join("history.json"), ..Default::default() },
+            channel_map: dir.join("channels.json"),
+            player: "/nonexistent/player".to_string(),
+            ..Default::default()
+        };
+        (dir, config)
+    }
+
+    #[tokio::test]
+    async fn file_routes_reject_paths_outside_the_library() {
+        let (dir, config) = escape_library("routes-escape");
+        let clock: SharedClock = Arc::new(SimulatedClock::frozen(Utc.with_ymd_and_hms(1994, 10, 17, 23, 30, 0).unwrap()));
+        let history = Arc::new(Mutex::new(WatchHistory::default()));
+        let lineup = Arc::new(load_lineup(&config, &WatchHistory::default(), clock));
+        let videos = Arc::new(Mutex::new(index_videos(&config)));
+        let id = videos.lock().await.find_filename("show.mp4").unwrap().id.clone();
+
+        let shared = {
+            let config = config.clone();
+            warp::any()
+                .map(move || Arc::clone(&videos))
+                .and(warp::any().map(move || Arc::clone(&lineup)))
+                .and(warp::any().map(move || config.clone()))
+                .and(warp::any().map(|| false))
+                .and(warp::any().map(|| "tablet".to_string()))
+                .and(warp::any().map(move || Arc::clone(&history)))
+        };
+        let stream = warp::path!("videos" / String).and(warp::get()).and(shared.clone()).and_then(stream_video);
+        let play = warp::path!("play")
+            .and(warp::post())
+            .and(warp::body::json())
+            .and(shared)
+            .and_then(|request, videos, lineup: Arc<Lineup>, config, unlocked, viewer, history| {
+                play_video(request, videos, config, lineup, unlocked, viewer, history)
+            })
+            .map(|(entry, _child): (VideoEntry, tokio::process::Child)| warp::reply::json(&entry));
+        let routes = stream.or(play).recover(api::recover);
+
+        let response = warp::test::request().path(&format!("/videos/{}", id)).reply(&routes).await;
+        assert_eq!((response.status().as_u16(), response.body().as_ref()), (200, b"show".as_ref()));
+
+        for path in ["/videos/..%2Fprivate%2Fsecret.mp4", "/videos/%2Fetc%2Fpasswd", "/videos/escape.mp4", "/videos/show.mp4%00"] {
+            let response = warp::test::request().path(path).reply(&routes).await;
+            assert_eq!(response.status(), 404, "{}", path);
+        }
+
+        for (body, status, code) in [
+            (serde_json::json!({"filename": "../private/secret.mp4"}), 400, "invalid_filename"),
+            (serde_json::json!({"filename": "/etc/passwd"}), 400, "invalid_filename"),
+            (serde_json::json!({"filename": "show.mp4\u{0}"}), 400, "invalid_filename"),
+            (serde_json::json!({"filename": "..%2Fprivate%2Fsecret.mp4"}), 404, "video_not_found"),
+            (serde_json::json!({"filename": "escape.mp4"}), 404, "video_not_found"),
+            (serde_json::json!({"id": "../private/secret.mp4"}), 404, "video_not_found"),
+        ] {
+            let response = warp::test::request().method("POST").path("/play").json(&body).reply(&routes).await;
+            let error: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+            assert_eq!((response.status().as_u16(), error["code"].as_str().unwrap()), (status, code), "{}", body);
+        }
+
+        // A library file swapped for a symlink after indexing is refused on both routes
+        let root = &config.library_roots[0];
+        std::fs::remove_file(root.join("show.mp4")).unwrap();
+        std::os::unix::fs::symlink(dir.join("private/secret.mp4"), root.join("show.mp4")).unwrap();
+        let response = warp::test::request().path(&format!("/videos/{}", id)).reply(&routes).await;
+        assert_eq!(response.status(), 403);
+        let response = warp::test::request().method("POST").path("/play").json(&serde_json::json!({"id": id})).reply(&routes).await;
+        assert_eq!(response.status(), 403);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn locked_videos_are_listed_only_once_unlocked() {
+        let dir = std::env::temp_dir().join(format!("list-locked-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, category) in [("news", "News"), ("film", "Movie")] {
+            std::fs::write(dir.join(format!("{}.mp4", name)), b"").unwrap();
+            let sidecar = serde_json::json!({
+                "schema_version": sidecar::SCHEMA_VERSION,
+                "title": name,
+                "station": "WEWS",
+                "description": "",
+                "year": "1994",
+                "duration": "30:00",
+                "category": category,
+                "channel_number": 5,
+                "start_time": null,
+                "end_time": null,
+                "thumbnail_url": "",
+                "tags": [],
+                "original_id": name,
+                "download_date": "1994-09-18T00:00:00Z",
+                "station_callsign": "WEWS",
+                "is_featured": false
+            });
+            std::fs::write(dir.join(format!("{}.json", name)), sidecar.to_string()).unwrap();
+        }
+        let config = Config {
+            library_roots: vec![dir.clone()],
+            history: history::HistorySettings { file: dir.join("history.json"), ..Default::default() },
+            channel_map: dir.join("channels.json"),
+            parental: ParentalConfig { pin: Some("1234".to_string()), locked_categories: vec!["Movie".to_string()], ..Default::default() },
+            ..Default::default()
+        };
+        let clock: SharedClock = Arc::new(SimulatedClock::frozen(Utc.with_ymd_and_hms(1994, 10, 17, 23, 30, 0).unwrap()));
+        let lineup = Arc::new(load_lineup(&config, &WatchHistory::default(), clock));
+        let videos = Arc::new(Mutex::new(index_videos(&config)));
+
+        let route = warp::path!("videos")
+            .and(warp::get())
+            .and(warp::any().map(move || Arc::clone(&videos)))
+            .and(warp::any().map(move || Arc::clone(&lineup)))
+            .and(warp::any().map(move || config.clone()))
+            .and(warp::header::optional::<bool>("x-unlocked").map(|unlocked: Option<bool>| unlocked.unwrap_or(false)))
+            .and_then(list_videos);
+
+        for (unlocked, expected) in [("false", vec!["news.mp4"]), ("true", vec!["film.mp4", "news.mp4"])] {
+            let response = warp::test::request().path("/videos").header("x-unlocked", unlocked).reply(&route).await;
+            let listed: Vec<serde_json::Value> = serde_json::from_slice(response.body()).unwrap();
+            let filenames: Vec<&str> = listed.iter().map(|entry| entry["filename"].as_str().unwrap()).collect();
+            assert_eq!(filenames, expected);
+        }
+
+        std::fs::remove_dir_all(&dir).unwrap();
     }
-    Ok(videos)
 }
@@ -0,0 +1,242 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs;
+use std::path::Path;
+
+use crate::channels::{ChannelConfig, ChannelMap, FillerConfig, InterstitialConfig};
+use crate::library::LibraryItem;
+
+// Format version written into packs; bumped when older readers would misread a newer pack
+pub const PACK_VERSION: u32 = 1;
+
+// A channel lineup to share: the channel definitions and the Internet Archive
+// items that fill them, without any video files. Importing one queues the
+// downloads that are missing and rebuilds the same channels.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct LineupPack {
+    pub pack_version: u32,
+    // e.g. "Cleveland 1994"
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub created_at: DateTime<Utc>,
+    // Channel map entries, with their dayparts, broadcast hours and selections
+    pub channels: Vec<ChannelConfig>,
+    // The library-wide filler and interstitial rules of the machine it came from
+    pub filler: FillerConfig,
+    pub interstitials: InterstitialConfig,
+    pub programs: Vec<PackedProgram>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PackedProgram {
+    // Internet Archive identifier, the sidecar's original_id
+    pub identifier: String,
+    // File name in the library it was packed from
+    pub file: String,
+    pub title: String,
+    // Channel its sidecar files it under; imported downloads are pinned here
+    pub channel: u8,
+}
+
+impl LineupPack {
+    // Pack the given channels, or every channel in the map and the library
+    // when none are given. Programs come along when their sidecar names a
+    // packed channel, a packed personal channel selects them or a packed
+    // channel uses them as its sign-on or sign-off clip.
+    pub fn build(name: &str, description: &str, channels: &[u8], map: &ChannelMap, items: &[LibraryItem], now: DateTime<Utc>) -> Self {
+        let numbers: BTreeSet<u8> = if channels.is_empty() {
+            map.channels.iter().map(|channel| channel.number).chain(items.iter().map(|item| item.metadata.channel_number)).collect()
+        } else {
+            channels.iter().copied().collect()
+        };
+        let packed: Vec<ChannelConfig> = map.channels.iter().filter(|channel| numbers.contains(&channel.number)).cloned().collect();
+
+        let clips: HashSet<&str> = packed
+            .iter()
+            .filter_map(|channel| channel.broadcast_hours.as_ref())
+            .flat_map(|hours| [hours.sign_on_clip.as_deref(), hours.sign_off_clip.as_deref()])
+            .flatten()
+            .collect();
+        // Favorites belong to the viewers on this machine, so they don't travel
+        let no_favorites = BTreeMap::new();
+        let programs = items
+            .iter()
+            .filter(|item| {
+                let metadata = &item.metadata;
+                numbers.contains(&metadata.channel_number)
+                    || clips.contains(metadata.original_id.as_str())
+                    || packed.iter().any(|channel| channel.selection.as_ref().is_some_and(|selection| selection.matches(metadata, &no_favorites)))
+            })
+            .map(|item| PackedProgram {
+                identifier: item.metadata.original_id.clone(),
+                file: item.video_path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default(),
+                title: item.metadata.title.clone(),
+                channel: item.metadata.channel_number,
+            })
+            .collect();
+
+        Self {
+            pack_version: PACK_VERSION,
+            name: name.to_string(),
+            description: description.to_string(),
+            created_at: now,
+            channels: packed,
+            filler: map.filler.clone(),
+            interstitials: map.interstitials.clone(),
+            programs,
+        }
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read lineup pack {}", path.display()))?;
+        let pack: LineupPack = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse lineup pack {}", path.display()))?;
+        if pack.pack_version > PACK_VERSION {
+            bail!("lineup pack {} is version {}; this version reads up to {}", path.display(), pack.pack_version, PACK_VERSION);
+        }
+        Ok(pack)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path, json).with_context(|| format!("Failed to write lineup pack {}", path.display()))
+    }
+
+    // Channels defined in both the pack and `map`, whose local definitions an import replaces
+    pub fn conflicts(&self, map: &ChannelMap) -> Vec<u8> {
+        self.channels.iter().map(|channel| channel.number).filter(|number| map.channel(*number).is_some()).collect()
+    }
+
+    // Programs whose identifiers are not in the library
+    pub fn missing(&self, library: &HashSet<String>) -> Vec<&PackedProgram> {
+        self.programs.iter().filter(|program| !library.contains(&program.identifier)).collect()
+    }
+
+    // Add the pack's channels to `map`, replacing any with the same numbers.
+    // The filler and interstitial rules affect every channel, so they are only
+    // taken when asked for.
+    pub fn apply(&self, map: &mut ChannelMap, with_rules: bool) {
+        map.channels.retain(|channel| self.channels.iter().all(|packed| packed.number != channel.number));
+        map.channels.extend(self.channels.iter().cloned());
+        if with_rules {
+            map.filler = self.filler.clone();
+            map.interstitials = self.interstitials.clone();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channels::{BroadcastHours, OffAirPattern, ProgramSelection};
+    use crate::sidecar::test_program;
+    use chrono::TimeZone;
+    use std::path::PathBuf;
+
+    fn channel(number: u8, callsign: &str) -> ChannelConfig {
+        ChannelConfig {
+            number,
+            callsign: Some(callsign.to_string()),
+            dayparts: Vec::new(),
+            broadcast_hours: None,
+            name: None,
+            selection: None,
+            owner: None,
+        }
+    }
+
+    fn item(original_id: &str, channel_number: u8) -> LibraryItem {
+        LibraryItem {
+            video_path: PathBuf::from(format!("/videos/{}.mp4", original_id)),
+            sidecar_path: PathBuf::from(format!("/videos/{}.json", original_id)),
+            metadata: test_program(original_id, "TV Show", channel_number, "0:30:00"),
+        }
+    }
+
+    // Channel 3 signs on with a clip filed under channel 9, and personal
+    // channel 100 picks a program filed under channel 7
+    fn source_map() -> ChannelMap {
+        let mut news = channel(3, "WEWS");
+        news.broadcast_hours = Some(BroadcastHours {
+            sign_on: "06:00".to_string(),
+            sign_off: "02:00".to_string(),
+            sign_on_clip: Some("signon-3".to_string()),
+            sign_off_clip: None,
+            pattern: OffAirPattern::Bars,
+        });
+        let mut personal = channel(100, "MINE");
+        personal.selection = Some(ProgramSelection { programs: vec!["picked".to_string()], ..ProgramSelection::default() });
+        let mut map = ChannelMap { channels: vec![news, channel(5, "WJW"), personal], ..ChannelMap::default() };
+        map.filler.title = "Test Pattern".to_string();
+        map
+    }
+
+    fn library() -> Vec<LibraryItem> {
+        vec![item("news", 3), item("movie", 5), item("signon-3", 9), item("picked", 7), item("other", 8)]
+    }
+
+    fn now() -> DateTime<Utc> {
+        Utc.timestamp_opt(1_700_000_000, 0).unwrap()
+    }
+
+    fn identifiers(pack: &LineupPack) -> Vec<&str> {
+        pack.programs.iter().map(|program| program.identifier.as_str()).collect()
+    }
+
+    #[test]
+    fn packs_bring_their_channels_programs_clips_and_selections() {
+        let pack = LineupPack::build("Cleveland 1994", "", &[3, 100], &source_map(), &library(), now());
+        let numbers: Vec<u8> = pack.channels.iter().map(|channel| channel.number).collect();
+        assert_eq!(numbers, [3, 100]);
+        assert_eq!(identifiers(&pack), ["news", "signon-3", "picked"]);
+        let clip = &pack.programs[1];
+        assert_eq!((clip.file.as_str(), clip.channel), ("signon-3.mp4", 9));
+        assert_eq!(pack.filler.title, "Test Pattern");
+
+        // With no channels named, everything in the map and the library goes
+        let everything = LineupPack::build("All", "", &[], &source_map(), &library(), now());
+        assert_eq!(everything.channels.len(), 3);
+        assert_eq!(everything.programs.len(), 5);
+    }
+
+    #[test]
+    fn packs_survive_a_round_trip_through_a_file() {
+        let path = std::env::temp_dir().join(format!("pack-round-trip-{}.json", std::process::id()));
+        let pack = LineupPack::build("Cleveland 1994", "Fall lineup", &[3], &source_map(), &library(), now());
+        pack.save(&path).unwrap();
+        let loaded = LineupPack::load(&path).unwrap();
+        assert_eq!((loaded.name.as_str(), loaded.description.as_str()), ("Cleveland 1994", "Fall lineup"));
+        assert_eq!(loaded.created_at, now());
+        assert_eq!(loaded.channels[0].callsign.as_deref(), Some("WEWS"));
+        assert_eq!(identifiers(&loaded), identifiers(&pack));
+
+        // A pack from a newer version is refused rather than misread
+        LineupPack { pack_version: PACK_VERSION + 1, ..pack }.save(&path).unwrap();
+        assert!(LineupPack::load(&path).is_err());
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn importing_replaces_conflicting_channels_and_keeps_the_rest() {
+        let pack = LineupPack::build("Cleveland 1994", "", &[3, 100], &source_map(), &library(), now());
+        let mut map = ChannelMap { channels: vec![channel(3, "KYW"), channel(4, "WKYC")], ..ChannelMap::default() };
+        assert_eq!(pack.conflicts(&map), [3]);
+
+        let library: HashSet<String> = ["news".to_string()].into();
+        let missing: Vec<&str> = pack.missing(&library).iter().map(|program| program.identifier.as_str()).collect();
+        assert_eq!(missing, ["signon-3", "picked"]);
+
+        pack.apply(&mut map, false);
+        let callsigns: Vec<_> = map.channels.iter().map(|channel| (channel.number, channel.callsign.as_deref().unwrap())).collect();
+        assert_eq!(callsigns, [(4, "WKYC"), (3, "WEWS"), (100, "MINE")]);
+        assert_eq!(map.filler.title, "Paid Programming");
+
+        pack.apply(&mut map, true);
+        assert_eq!(map.channels.len(), 3);
+        assert_eq!(map.filler.title, "Test Pattern");
+    }
+}
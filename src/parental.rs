@@ -0,0 +1,303 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::auth;
+use crate::library::LibraryItem;
+use crate::schedule::{Slot, SlotKind, WeeklySchedule};
+use crate::sidecar::TvGuideMetadata;
+
+// Name of the cookie that carries a PIN unlock
+pub const UNLOCK_COOKIE: &str = "channel_surfer_unlock";
+
+// Title shown in place of anything behind a parental lock
+pub const LOCKED_TITLE: &str = "LOCKED";
+
+// US TV parental guidelines, mildest first. Film ratings are accepted as their
+// nearest TV equivalent.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Rating {
+    #[serde(rename = "TV-Y")]
+    TvY,
+    #[serde(rename = "TV-Y7")]
+    TvY7,
+    #[serde(rename = "TV-G", alias = "G")]
+    TvG,
+    #[serde(rename = "TV-PG", alias = "PG")]
+    TvPg,
+    #[serde(rename = "TV-14", alias = "PG-13")]
+    Tv14,
+    #[serde(rename = "TV-MA", alias = "R", alias = "NC-17")]
+    TvMa,
+}
+
+// Subjects that suggest a rating when an item has none of its own
+const MATURE_SUBJECTS: [&str; 9] = ["horror", "erotica", "adult", "exploitation", "sexploitation", "gore", "nudity", "grindhouse", "x-rated"];
+const TEEN_SUBJECTS: [&str; 5] = ["crime", "violence", "thriller", "war", "noir"];
+const CHILDREN_SUBJECTS: [&str; 4] = ["children", "kids", "preschool", "educational television"];
+const CARTOON_SUBJECTS: [&str; 3] = ["cartoon", "cartoons", "animation"];
+
+impl Rating {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Rating::TvY => "TV-Y",
+            Rating::TvY7 => "TV-Y7",
+            Rating::TvG => "TV-G",
+            Rating::TvPg => "TV-PG",
+            Rating::Tv14 => "TV-14",
+            Rating::TvMa => "TV-MA",
+        }
+    }
+
+    // A rating written out in a subject, e.g. "TV-PG" or "Rated R"
+    pub fn from_label(label: &str) -> Option<Rating> {
+        let label = label.trim().to_uppercase();
+        match label.strip_prefix("RATED ").unwrap_or(&label).trim() {
+            "TV-Y" => Some(Rating::TvY),
+            "TV-Y7" => Some(Rating::TvY7),
+            "TV-G" | "G" => Some(Rating::TvG),
+            "TV-PG" | "PG" => Some(Rating::TvPg),
+            "TV-14" | "PG-13" => Some(Rating::Tv14),
+            "TV-MA" | "R" | "NC-17" => Some(Rating::TvMa),
+            _ => None,
+        }
+    }
+
+    // Best guess from Internet Archive subjects and our category. A rating
+    // spelled out in the subjects wins; otherwise the most restrictive keyword
+    // does, and items with no telling subjects stay unrated.
+    pub fn from_subjects(subjects: &[String], category: &str) -> Option<Rating> {
+        if let Some(rating) = subjects.iter().find_map(|subject| Rating::from_label(subject)) {
+            return Some(rating);
+        }
+        let subjects: Vec<String> = subjects.iter().map(|subject| subject.trim().to_lowercase()).collect();
+        let any = |keywords: &[&str]| subjects.iter().any(|subject| keywords.contains(&subject.as_str()));
+        if any(&MATURE_SUBJECTS) {
+            Some(Rating::TvMa)
+        } else if any(&TEEN_SUBJECTS) {
+            Some(Rating::Tv14)
+        } else if any(&CHILDREN_SUBJECTS) {
+            Some(Rating::TvY)
+        } else if any(&CARTOON_SUBJECTS) || category.eq_ignore_ascii_case("Cartoon") {
+            Some(Rating::TvY7)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Rating {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.label())
+    }
+}
+
+// Channels and programs hidden behind a PIN. With no locks nothing is hidden.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ParentalConfig {
+    // Digits entered to unlock; required once anything is locked
+    pub pin: Option<String>,
+    pub locked_channels: Vec<u8>,
+    // Library categories locked on every channel, e.g. "Movie"
+    pub locked_categories: Vec<String>,
+    // Programs rated above this are locked; unrated programs are not
+    pub max_rating: Option<Rating>,
+    // How long the PIN unlocks for; an hour when unset
+    pub unlock_minutes: Option<u64>,
+}
+
+const DEFAULT_UNLOCK_MINUTES: u64 = 60;
+
+impl ParentalConfig {
+    pub fn enabled(&self) -> bool {
+        !self.locked_channels.is_empty() || !self.locked_categories.is_empty() || self.max_rating.is_some()
+    }
+
+    pub fn unlock_length(&self) -> Duration {
+        Duration::from_secs(self.unlock_minutes.unwrap_or(DEFAULT_UNLOCK_MINUTES) * 60)
+    }
+
+    pub fn check_pin(&self, pin: &str) -> bool {
+        self.pin.as_deref().is_some_and(|expected| auth::constant_time_eq(expected.as_bytes(), pin.trim().as_bytes()))
+    }
+
+    pub fn channel_locked(&self, channel: u8) -> bool {
+        self.locked_channels.contains(&channel)
+    }
+
+    pub fn program_locked(&self, program: &TvGuideMetadata) -> bool {
+        self.locked_categories.iter().any(|category| category.eq_ignore_ascii_case(&program.category))
+            || matches!((program.rating, self.max_rating), (Some(rating), Some(max)) if rating > max)
+    }
+
+    // Whether a guide entry is locked, either by its channel or by the program airing in it
+    pub fn slot_locked(&self, channel: u8, slot: &Slot, programs: &HashMap<String, TvGuideMetadata>) -> bool {
+        self.channel_locked(channel)
+            || (slot.kind == SlotKind::Program && programs.get(&slot.original_id).is_some_and(|program| self.program_locked(program)))
+    }
+
+    // Replace every locked entry with a "LOCKED" placeholder that has nothing
+    // to play, so the guide and the tuner show it without its title or video
+    pub fn mask_schedule(&self, schedule: &mut WeeklySchedule, items: &[LibraryItem]) {
+        if !self.enabled() {
+            return;
+        }
+        let programs: HashMap<String, TvGuideMetadata> = items
+            .iter()
+            .map(|item| (item.metadata.original_id.clone(), item.metadata.clone()))
+            .collect();
+        for (channel, slots) in schedule.channels.iter_mut() {
+            for slot in slots.iter_mut() {
+                if !self.slot_locked(*channel, slot, &programs) {
+                    continue;
+                }
+                slot.title = LOCKED_TITLE.to_string();
+                slot.original_id.clear();
+                for segment in &mut slot.segments {
+                    segment.title = LOCKED_TITLE.to_string();
+                    segment.original_id.clear();
+                }
+            }
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        match self.pin.as_deref() {
+            Some(pin) if pin.len() < 4 || !pin.chars().all(|c| c.is_ascii_digit()) => {
+                Err("parental.pin must be at least 4 digits".to_string())
+            }
+            None if self.enabled() => Err("parental locks need a pin".to_string()),
+            _ => Ok(()),
+        }
+    }
+}
+
+// Clients that entered the PIN, kept in memory until their window closes
+#[derive(Default)]
+pub struct Unlocks {
+    windows: Mutex<HashMap<String, Instant>>,
+}
+
+impl Unlocks {
+    pub fn create(&self, length: Duration) -> String {
+        let id = auth::random_id();
+        let mut windows = self.windows.lock().unwrap();
+        let now = Instant::now();
+        windows.retain(|_, expires| *expires > now);
+        windows.insert(id.clone(), now + length);
+        id
+    }
+
+    pub fn is_unlocked(&self, id: &str) -> bool {
+        self.windows.lock().unwrap().get(id).is_some_and(|expires| *expires > Instant::now())
+    }
+
+    pub fn remove(&self, id: &str) {
+        self.windows.lock().unwrap().remove(id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sidecar;
+
+    fn subjects(subjects: &[&str]) -> Vec<String> {
+        subjects.iter().map(|subject| subject.to_string()).collect()
+    }
+
+    fn locks() -> ParentalConfig {
+        ParentalConfig {
+            pin: Some("1234".to_string()),
+            locked_channels: vec![9],
+            locked_categories: vec!["Movie".to_string()],
+            max_rating: Some(Rating::TvPg),
+            unlock_minutes: None,
+        }
+    }
+
+    fn rated(original_id: &str, category: &str, rating: Option<Rating>) -> TvGuideMetadata {
+        TvGuideMetadata { rating, ..sidecar::test_program(original_id, category, 3, "30:00") }
+    }
+
+    fn program_slot(original_id: &str) -> Slot {
+        Slot {
+            start_second: 0,
+            length_seconds: 1800,
+            kind: SlotKind::Program,
+            original_id: original_id.to_string(),
+            title: original_id.to_string(),
+            segments: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn ratings_are_inferred_from_subjects() {
+        for (subjects_, category, expected) in [
+            (subjects(&["Rated R", "comedy"]), "Program", Some(Rating::TvMa)),
+            (subjects(&["TV-PG", "horror"]), "Program", Some(Rating::TvPg)),
+            (subjects(&["pg-13"]), "Movie", Some(Rating::Tv14)),
+            (subjects(&["Horror", "children"]), "Movie", Some(Rating::TvMa)),
+            (subjects(&["film noir", "noir"]), "Movie", Some(Rating::Tv14)),
+            (subjects(&["Kids", "cartoons"]), "Cartoon", Some(Rating::TvY)),
+            (subjects(&["animation"]), "Program", Some(Rating::TvY7)),
+            (subjects(&[]), "Cartoon", Some(Rating::TvY7)),
+            (subjects(&["news", "cleveland"]), "News", None),
+            // Keywords match whole subjects only
+            (subjects(&["war of the worlds"]), "Movie", None),
+        ] {
+            assert_eq!(Rating::from_subjects(&subjects_, category), expected, "{:?} {}", subjects_, category);
+        }
+    }
+
+    #[test]
+    fn locks_apply_by_channel_category_and_rating() {
+        let parental = locks();
+        assert!(parental.channel_locked(9));
+        assert!(!parental.channel_locked(3));
+
+        assert!(parental.program_locked(&rated("film", "movie", None)));
+        assert!(parental.program_locked(&rated("late", "Program", Some(Rating::Tv14))));
+        assert!(!parental.program_locked(&rated("family", "Program", Some(Rating::TvPg))));
+        assert!(!parental.program_locked(&rated("unrated", "Program", None)));
+
+        let programs: HashMap<String, TvGuideMetadata> =
+            [rated("late", "Program", Some(Rating::TvMa)), rated("family", "Program", Some(Rating::TvG))]
+                .into_iter()
+                .map(|program| (program.original_id.clone(), program))
+                .collect();
+        assert!(parental.slot_locked(3, &program_slot("late"), &programs));
+        assert!(!parental.slot_locked(3, &program_slot("family"), &programs));
+        assert!(parental.slot_locked(9, &program_slot("family"), &programs));
+        let interstitial = Slot { kind: SlotKind::Interstitial, ..program_slot("late") };
+        assert!(!parental.slot_locked(3, &interstitial, &programs));
+
+        assert!(!ParentalConfig::default().enabled());
+        assert!(ParentalConfig::default().validate().is_ok());
+        assert!(parental.validate().is_ok());
+        assert!(ParentalConfig { pin: None, ..locks() }.validate().is_err());
+        assert!(ParentalConfig { pin: Some("12a4".to_string()), ..locks() }.validate().is_err());
+    }
+
+    #[test]
+    fn pins_unlock_until_the_window_closes() {
+        let parental = locks();
+        assert!(parental.check_pin(" 1234 "));
+        assert!(!parental.check_pin("1235"));
+        assert!(!ParentalConfig::default().check_pin(""));
+        assert_eq!(parental.unlock_length(), Duration::from_secs(3600));
+
+        let unlocks = Unlocks::default();
+        let open = unlocks.create(Duration::from_secs(60));
+        let expired = unlocks.create(Duration::ZERO);
+        assert!(unlocks.is_unlocked(&open));
+        assert!(!unlocks.is_unlocked(&expired));
+        assert!(!unlocks.is_unlocked("not-an-unlock"));
+
+        unlocks.remove(&open);
+        assert!(!unlocks.is_unlocked(&open));
+    }
+}
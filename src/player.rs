@@ -0,0 +1,117 @@
+use serde_json::{json, Value};
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::tuner::Source;
+
+// How long to wait for a freshly started mpv to open its control socket
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+// A long-running mpv window controlled over its JSON IPC socket, so changing
+// channels swaps the video without reopening the player
+pub struct Player {
+    child: Child,
+    socket_path: PathBuf,
+    stream: UnixStream,
+}
+
+impl Player {
+    // Start `command` (mpv or something that speaks its IPC protocol) with an empty window
+    pub fn launch(command: &str) -> io::Result<Self> {
+        let socket_path = std::env::temp_dir().join(format!("channel-surfer-mpv-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&socket_path);
+
+        let mut child = Command::new(command)
+            .arg("--idle=yes")
+            .arg("--force-window=yes")
+            .arg("--really-quiet")
+            .arg(format!("--input-ipc-server={}", socket_path.display()))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        let started = Instant::now();
+        let stream = loop {
+            match UnixStream::connect(&socket_path) {
+                Ok(stream) => break stream,
+                Err(e) => {
+                    if let Some(status) = child.try_wait()? {
+                        return Err(io::Error::other(format!("mpv exited early ({})", status)));
+                    }
+                    if started.elapsed() > STARTUP_TIMEOUT {
+                        let _ = child.kill();
+                        return Err(e);
+                    }
+                    thread::sleep(Duration::from_millis(100));
+                }
+            }
+        };
+
+        // mpv pushes replies and events down the socket; drain them so it never blocks
+        let reader = stream.try_clone()?;
+        thread::spawn(move || {
+            for line in BufReader::new(reader).lines() {
+                if line.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Ok(Self { child, socket_path, stream })
+    }
+
+    fn command(&mut self, args: Value) -> io::Result<()> {
+        let mut message = json!({ "command": args }).to_string();
+        message.push('\n');
+        self.stream.write_all(message.as_bytes())
+    }
+
+    // Replace whatever is playing, starting `offset_seconds` in
+    pub fn play(&mut self, source: &Source, offset_seconds: u32) -> io::Result<()> {
+        match source {
+            Source::File(path) => {
+                self.command(json!(["change-list", "audio-files", "clr", ""]))?;
+                self.command(json!(["set_property", "start", format!("+{}", offset_seconds)]))?;
+                self.command(json!(["loadfile", path.to_string_lossy(), "replace"]))
+            }
+            Source::Pattern(pattern) => {
+                for (property, value) in pattern.mpv_properties() {
+                    self.command(json!(["set_property", property, value]))?;
+                }
+                self.command(json!(["set_property", "start", "+0"]))?;
+                self.command(json!(["loadfile", pattern.mpv_video(), "replace"]))
+            }
+        }
+    }
+
+    // On-screen text over the video, like a cable box's channel banner
+    pub fn show_text(&mut self, text: &str, duration: Duration) -> io::Result<()> {
+        self.command(json!(["show-text", text, duration.as_millis() as u64]))
+    }
+
+    // False once the viewer has closed the mpv window
+    pub fn is_running(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(None))
+    }
+}
+
+impl Drop for Player {
+    fn drop(&mut self) {
+        let _ = self.command(json!(["quit"]));
+        let started = Instant::now();
+        while started.elapsed() < Duration::from_secs(1) {
+            if !self.is_running() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        let _ = std::fs::remove_file(&self.socket_path);
+    }
+}
@@ -0,0 +1,89 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+// Default location of the download queue, relative to the working directory
+pub const DEFAULT_QUEUE_FILE: &str = "./download-queue.json";
+
+// A download waiting its turn. It leaves the queue once the item is in the library.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QueuedDownload {
+    pub identifier: String,
+    // Channel the item is filed under instead of the one its category suggests
+    #[serde(default)]
+    pub channel: Option<u8>,
+    // What queued it, e.g. "collection:classic_tv_commercials"
+    pub source: String,
+    pub added_at: DateTime<Utc>,
+    // Why the last attempt failed
+    #[serde(default)]
+    pub error: Option<String>,
+}
+
+// Downloads still to do, kept on disk so an interrupted import picks up where it stopped
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DownloadQueue {
+    #[serde(default)]
+    pub items: Vec<QueuedDownload>,
+}
+
+impl DownloadQueue {
+    // Load the queue, treating a missing file as an empty queue
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read download queue {}", path.display()))?;
+        serde_json::from_str(&content).with_context(|| format!("Failed to parse download queue {}", path.display()))
+    }
+
+    // Write the queue, replacing the existing file atomically
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, json).with_context(|| format!("Failed to write download queue {}", path.display()))?;
+        fs::rename(&tmp_path, path).with_context(|| format!("Failed to write download queue {}", path.display()))?;
+        Ok(())
+    }
+
+    pub fn contains(&self, identifier: &str) -> bool {
+        self.items.iter().any(|item| item.identifier == identifier)
+    }
+
+    // Add an item unless it is already waiting; returns whether it was added
+    pub fn enqueue(&mut self, identifier: &str, channel: Option<u8>, source: &str, now: DateTime<Utc>) -> bool {
+        if self.contains(identifier) {
+            return false;
+        }
+        self.items.push(QueuedDownload {
+            identifier: identifier.to_string(),
+            channel,
+            source: source.to_string(),
+            added_at: now,
+            error: None,
+        });
+        true
+    }
+
+    // Drop items that made it into the library, e.g. by a download the
+    // queue never heard back from. Returns how many were dropped.
+    pub fn prune(&mut self, library: &HashSet<String>) -> usize {
+        let before = self.items.len();
+        self.items.retain(|item| !library.contains(&item.identifier));
+        before - self.items.len()
+    }
+
+    pub fn complete(&mut self, identifier: &str) {
+        self.items.retain(|item| item.identifier != identifier);
+    }
+
+    pub fn fail(&mut self, identifier: &str, error: String) {
+        if let Some(item) = self.items.iter_mut().find(|item| item.identifier == identifier) {
+            item.error = Some(error);
+        }
+    }
+}
@@ -0,0 +1,1088 @@
+use crate::channels::{parse_clock, BroadcastHours, ChannelConfig, ChannelMap, Daypart, InterstitialConfig, OffAirPattern};
+use crate::sidecar::TvGuideMetadata;
+use crate::local_time::TimeSettings;
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+
+pub const MINUTES_PER_DAY: u32 = 24 * 60;
+pub const SECONDS_PER_DAY: u32 = MINUTES_PER_DAY * 60;
+pub const SECONDS_PER_WEEK: u32 = 7 * SECONDS_PER_DAY;
+
+pub const DAYS: [&str; 7] = ["Monday", "Tuesday", "Wednesday", "Thursday", "Friday", "Saturday", "Sunday"];
+
+// Library categories reserved for the ends of the broadcast day
+pub const SIGN_ON_CATEGORY: &str = "Sign On";
+pub const SIGN_OFF_CATEGORY: &str = "Sign Off";
+
+// Gaps shorter than this are not worth cutting a spot for
+const MIN_SPOT_SECONDS: u32 = 10;
+
+// Settings for building the weekly grid
+#[derive(Debug, Clone)]
+pub struct ScheduleConfig {
+    // Programs are rounded up to a whole number of blocks
+    pub block_minutes: u32,
+    // Changes the running order without changing which programs air
+    pub seed: u64,
+    // original_ids placed after everything else in the running order
+    pub recently_watched: HashSet<String>,
+    // Each viewer's starred original_ids, for personal channels made from favorites
+    pub favorites: BTreeMap<String, HashSet<String>>,
+}
+
+impl Default for ScheduleConfig {
+    fn default() -> Self {
+        Self {
+            block_minutes: 30,
+            seed: 0,
+            recently_watched: HashSet::new(),
+            favorites: BTreeMap::new(),
+        }
+    }
+}
+
+impl ScheduleConfig {
+    fn block_seconds(&self) -> u32 {
+        self.block_minutes.max(1).saturating_mul(60)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SlotKind {
+    Program,
+    // Commercials, station IDs and bumpers between and inside programs
+    Interstitial,
+    // Fills time nothing else fits; original_id is empty for generated filler
+    Filler,
+    // Sign-off, test pattern and sign-on between broadcast days
+    OffAir,
+}
+
+// What actually plays: a piece of one video, starting offset_seconds into it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Segment {
+    pub start_second: u32,
+    pub length_seconds: u32,
+    pub kind: SlotKind,
+    pub original_id: String,
+    pub title: String,
+    pub offset_seconds: u32,
+}
+
+// A guide entry on a channel, made of the segments that air during it.
+// Times are seconds counted from Monday 00:00.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Slot {
+    pub start_second: u32,
+    pub length_seconds: u32,
+    pub kind: SlotKind,
+    pub original_id: String,
+    pub title: String,
+    pub segments: Vec<Segment>,
+}
+
+impl Slot {
+    pub fn end_second(&self) -> u32 {
+        self.start_second + self.length_seconds
+    }
+
+    pub fn segment_at(&self, second_of_week: u32) -> Option<&Segment> {
+        self.segments
+            .iter()
+            .find(|segment| segment.start_second <= second_of_week && second_of_week < segment.start_second + segment.length_seconds)
+    }
+}
+
+// A full week of programming, one non-overlapping run of slots per channel
+#[derive(Debug, Clone, Default)]
+pub struct WeeklySchedule {
+    pub channels: BTreeMap<u8, Vec<Slot>>,
+    // Programs that never made it onto the grid
+    pub unscheduled: Vec<String>,
+}
+
+impl WeeklySchedule {
+    // The slot airing on a channel at the given second of the week
+    pub fn slot_at(&self, channel: u8, second_of_week: u32) -> Option<&Slot> {
+        let second = second_of_week % SECONDS_PER_WEEK;
+        self.channels
+            .get(&channel)?
+            .iter()
+            .find(|slot| slot.start_second <= second && second < slot.end_second())
+    }
+
+    // Every slot in the week in which a program airs
+    pub fn airings<'a>(&'a self, original_id: &'a str) -> impl Iterator<Item = &'a Slot> + 'a {
+        self.channels.values().flatten().filter(move |slot| {
+            slot.original_id == original_id || slot.segments.iter().any(|segment| segment.original_id == original_id)
+        })
+    }
+}
+
+// Parse the duration strings found in sidecars ("00:30:00", "1:23:45", "28 min", "1800.5") into seconds.
+// Anything that isn't a real length of at most a week, such as "inf", "-5" or
+// "0:00", is rejected rather than clamped.
+pub fn parse_duration_seconds(duration: &str) -> Option<u32> {
+    let duration = duration.trim();
+
+    let seconds = if duration.contains(':') {
+        let mut seconds = 0f64;
+        for part in duration.split(':') {
+            let part = part.trim().parse::<f64>().ok()?;
+            if part.is_sign_negative() {
+                return None;
+            }
+            seconds = seconds * 60.0 + part;
+        }
+        seconds
+    } else {
+        let number = duration.split_whitespace().next()?;
+        let value = number.parse::<f64>().ok()?;
+        let lower = duration.to_lowercase();
+        if lower.contains("min") {
+            value * 60.0
+        } else if lower.contains("sec") || number.contains('.') || value > 600.0 {
+            // IA "length" fields are seconds, usually with a fractional part
+            value
+        } else {
+            value * 60.0
+        }
+    };
+
+    if seconds.is_finite() && seconds > 0.0 && seconds <= SECONDS_PER_WEEK as f64 {
+        Some(seconds.ceil() as u32)
+    } else {
+        None
+    }
+}
+
+pub fn parse_duration_minutes(duration: &str) -> Option<u32> {
+    parse_duration_seconds(duration).map(|seconds| seconds.div_ceil(60))
+}
+
+// Round a running time up to whole blocks, never shorter than one block
+pub fn round_to_blocks(seconds: u32, block_seconds: u32) -> u32 {
+    let block = block_seconds.max(1);
+    seconds.max(1).div_ceil(block).saturating_mul(block)
+}
+
+// FNV-1a mixed with the seed so the running order is stable for a library and seed
+fn order_key(seed: u64, original_id: &str) -> u64 {
+    let mut hash = 0xcbf2_9ce4_8422_2325u64 ^ seed;
+    for byte in original_id.bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+    hash
+}
+
+// What a stretch of a channel's week is reserved for
+#[derive(Clone, Copy)]
+enum WindowKind {
+    General,
+    // Index into the channel's dayparts
+    Daypart(usize),
+    // Whether this piece of the off-air period opens with the sign-off and ends with the sign-on
+    OffAir { sign_off: bool, sign_on: bool },
+}
+
+struct Window {
+    start: u32,
+    end: u32,
+    kind: WindowKind,
+}
+
+// Expand a daily clock range into pieces across the week, splitting the
+// Sunday night range where it wraps to Monday morning. Each piece records
+// whether it contains the range's own start and end.
+fn weekly_ranges(days: &[u32], start_minute: u32, end_minute: u32) -> Vec<(u32, u32, bool, bool)> {
+    let (start, end) = (start_minute * 60, end_minute * 60);
+    // An end at or before the start runs past midnight
+    let length = if end > start { end - start } else { end + SECONDS_PER_DAY - start };
+    let mut ranges = Vec::new();
+    for day in days {
+        let range_start = day * SECONDS_PER_DAY + start;
+        let range_end = range_start + length;
+        if range_end > SECONDS_PER_WEEK {
+            ranges.push((range_start, SECONDS_PER_WEEK, true, false));
+            ranges.push((0, range_end - SECONDS_PER_WEEK, false, true));
+        } else {
+            ranges.push((range_start, range_end, true, true));
+        }
+    }
+    ranges
+}
+
+// Lay out a channel's week: off-air periods first, then daypart windows in
+// whatever time is left, and general programming windows covering the rest.
+// Where dayparts overlap, the one that starts first keeps the shared time and
+// the other gets only what is left after it; list order only decides between
+// dayparts that start together.
+fn channel_windows(dayparts: &[Daypart], hours: Option<&BroadcastHours>) -> Vec<Window> {
+    let mut off_air = Vec::new();
+    if let Some(hours) = hours {
+        if let (Some(sign_on), Some(sign_off)) = (parse_clock(&hours.sign_on), parse_clock(&hours.sign_off)) {
+            for (start, end, sign_off, sign_on) in weekly_ranges(&(0..7).collect::<Vec<_>>(), sign_off, sign_on) {
+                off_air.push(Window { start, end, kind: WindowKind::OffAir { sign_off, sign_on } });
+            }
+        }
+    }
+
+    let mut reserved: Vec<Window> = Vec::new();
+    for (index, daypart) in dayparts.iter().enumerate() {
+        let (Some(start), Some(end)) = (parse_clock(&daypart.start), parse_clock(&daypart.end)) else {
+            continue;
+        };
+        for (start, end, _, _) in weekly_ranges(&daypart.day_indexes().unwrap_or_default(), start, end) {
+            reserved.push(Window { start, end, kind: WindowKind::Daypart(index) });
+        }
+    }
+    // Cut the off-air periods out of the dayparts
+    for period in &off_air {
+        reserved = reserved
+            .into_iter()
+            .flat_map(|window| {
+                let mut pieces = Vec::new();
+                if window.start < period.start.min(window.end) {
+                    pieces.push(Window { start: window.start, end: period.start.min(window.end), kind: window.kind });
+                }
+                if window.end > period.end.max(window.start) {
+                    pieces.push(Window { start: period.end.max(window.start), end: window.end, kind: window.kind });
+                }
+                pieces
+            })
+            .collect();
+    }
+    reserved.extend(off_air);
+    // Stable sort keeps earlier dayparts first when two start together
+    reserved.sort_by_key(|window| window.start);
+
+    let mut windows = Vec::new();
+    let mut cursor = 0;
+    for window in reserved {
+        let start = window.start.max(cursor);
+        if start >= window.end {
+            continue;
+        }
+        if start > cursor {
+            windows.push(Window { start: cursor, end: start, kind: WindowKind::General });
+        }
+        cursor = window.end;
+        windows.push(Window { start, ..window });
+    }
+    if cursor < SECONDS_PER_WEEK {
+        windows.push(Window { start: cursor, end: SECONDS_PER_WEEK, kind: WindowKind::General });
+    }
+    windows
+}
+
+// A program ready to place: its running time, where it breaks for commercials
+// and the length of the block it occupies once padded
+#[derive(Clone)]
+struct Entry<'a> {
+    program: &'a TvGuideMetadata,
+    runtime: u32,
+    break_points: Vec<u32>,
+    block_length: u32,
+}
+
+fn plan_entry<'a>(program: &'a TvGuideMetadata, config: &ScheduleConfig, breaks: Option<&InterstitialConfig>) -> Entry<'a> {
+    let runtime = parse_duration_seconds(&program.duration).unwrap_or(config.block_seconds()).min(SECONDS_PER_WEEK);
+    let mut break_points: Vec<u32> = breaks
+        .map(|b| {
+            let mut points: Vec<u32> = b
+                .break_points_minutes
+                .iter()
+                .map(|minutes| minutes.saturating_mul(60))
+                .filter(|&point| point > 0 && point < runtime)
+                .collect();
+            points.sort_unstable();
+            points.dedup();
+            points
+        })
+        .unwrap_or_default();
+    let break_seconds = breaks.map(|b| b.break_seconds).unwrap_or(0);
+    // Breaks that would stretch the program past a week are dropped and it airs straight through
+    let mut breaks_length = (break_points.len() as u32).saturating_mul(break_seconds);
+    if breaks_length > SECONDS_PER_WEEK - runtime {
+        break_points.clear();
+        breaks_length = 0;
+    }
+    let block_length = round_to_blocks(runtime + breaks_length, config.block_seconds()).min(SECONDS_PER_WEEK);
+    Entry { program, runtime, break_points, block_length }
+}
+
+// Programs eligible for a window, in running order, with a rotating cursor
+struct Pool<'a> {
+    entries: Vec<Entry<'a>>,
+    cursor: usize,
+}
+
+impl<'a> Pool<'a> {
+    fn new(entries: Vec<Entry<'a>>) -> Self {
+        Self { entries, cursor: 0 }
+    }
+
+    // Next program in rotation whose block fits in the remaining time
+    fn next_fitting(&mut self, remaining: u32) -> Option<Entry<'a>> {
+        let len = self.entries.len();
+        for offset in 0..len {
+            let index = (self.cursor + offset) % len;
+            if self.entries[index].block_length <= remaining {
+                self.cursor = index + 1;
+                return Some(self.entries[index].clone());
+            }
+        }
+        None
+    }
+}
+
+fn running_order<'a>(mut programs: Vec<&'a TvGuideMetadata>, config: &ScheduleConfig) -> Vec<&'a TvGuideMetadata> {
+    programs.sort_by(|a, b| {
+        let watched = |program: &TvGuideMetadata| config.recently_watched.contains(&program.original_id);
+        watched(a)
+            .cmp(&watched(b))
+            .then_with(|| order_key(config.seed, &a.original_id).cmp(&order_key(config.seed, &b.original_id)))
+            .then_with(|| a.original_id.cmp(&b.original_id))
+    });
+    programs
+}
+
+// Rotating supply of commercials, station IDs and bumpers. Clips longer than
+// the reel threshold are treated as ad reels and played a piece at a time.
+struct Interstitials<'a> {
+    spots: Vec<(&'a TvGuideMetadata, u32)>,
+    reel_offsets: Vec<u32>,
+    cursor: usize,
+    last_id: Option<&'a str>,
+    reel_threshold: u32,
+    placeholder_title: String,
+}
+
+impl<'a> Interstitials<'a> {
+    fn new(spots: Vec<(&'a TvGuideMetadata, u32)>, start: usize, config: &InterstitialConfig, placeholder_title: &str) -> Self {
+        let cursor = if spots.is_empty() { 0 } else { start % spots.len() };
+        Self {
+            reel_offsets: vec![0; spots.len()],
+            spots,
+            cursor,
+            last_id: None,
+            reel_threshold: config.reel_threshold_seconds,
+            placeholder_title: placeholder_title.to_string(),
+        }
+    }
+
+    // Pick the next spot that fits, never repeating the previous one when there is a choice
+    fn next_spot(&mut self, remaining: u32) -> Option<(&'a TvGuideMetadata, u32, u32)> {
+        let len = self.spots.len();
+        for offset in 0..len {
+            let index = (self.cursor + offset) % len;
+            let (spot, runtime) = self.spots[index];
+            if len > 1 && self.last_id == Some(spot.original_id.as_str()) {
+                continue;
+            }
+
+            let (offset_seconds, length) = if runtime > self.reel_threshold {
+                let reel_offset = self.reel_offsets[index];
+                let piece = remaining.min(runtime - reel_offset);
+                let next = reel_offset + piece;
+                self.reel_offsets[index] = if next >= runtime { 0 } else { next };
+                (reel_offset, piece)
+            } else if runtime <= remaining {
+                (0, runtime)
+            } else {
+                continue;
+            };
+
+            self.cursor = index + 1;
+            self.last_id = Some(spot.original_id.as_str());
+            return Some((spot, offset_seconds, length));
+        }
+        None
+    }
+
+    // Cover a gap with spots, leaving a placeholder for whatever cannot be filled
+    fn fill_gap(&mut self, start: u32, length: u32, segments: &mut Vec<Segment>) {
+        let end = start + length;
+        let mut cursor = start;
+        while cursor < end {
+            let remaining = end - cursor;
+            let spot = if remaining >= MIN_SPOT_SECONDS { self.next_spot(remaining) } else { None };
+            match spot {
+                Some((spot, offset_seconds, length)) => {
+                    segments.push(Segment {
+                        start_second: cursor,
+                        length_seconds: length,
+                        kind: SlotKind::Interstitial,
+                        original_id: spot.original_id.clone(),
+                        title: spot.title.clone(),
+                        offset_seconds,
+                    });
+                    cursor += length;
+                }
+                None => {
+                    segments.push(Segment {
+                        start_second: cursor,
+                        length_seconds: remaining,
+                        kind: SlotKind::Filler,
+                        original_id: String::new(),
+                        title: self.placeholder_title.clone(),
+                        offset_seconds: 0,
+                    });
+                    cursor = end;
+                }
+            }
+        }
+    }
+}
+
+// Lay a program out inside its block: content split at the break points,
+// commercial breaks between the pieces and interstitials padding out the block
+fn place_entry(start: u32, entry: &Entry, kind: SlotKind, break_seconds: u32, interstitials: &mut Interstitials) -> Slot {
+    let mut segments = Vec::new();
+    let mut cursor = start;
+    let mut content_offset = 0;
+
+    let boundaries = entry.break_points.iter().copied().chain(std::iter::once(entry.runtime));
+    for (i, boundary) in boundaries.enumerate() {
+        if i > 0 && break_seconds > 0 {
+            interstitials.fill_gap(cursor, break_seconds, &mut segments);
+            cursor += break_seconds;
+        }
+        segments.push(Segment {
+            start_second: cursor,
+            length_seconds: boundary - content_offset,
+            kind,
+            original_id: entry.program.original_id.clone(),
+            title: entry.program.title.clone(),
+            offset_seconds: content_offset,
+        });
+        cursor += boundary - content_offset;
+        content_offset = boundary;
+    }
+
+    let end = start + entry.block_length;
+    if cursor < end {
+        interstitials.fill_gap(cursor, end - cursor, &mut segments);
+    }
+
+    Slot {
+        start_second: start,
+        length_seconds: entry.block_length,
+        kind,
+        original_id: entry.program.original_id.clone(),
+        title: entry.program.title.clone(),
+        segments,
+    }
+}
+
+// Fill one window with programs from its pool, falling back to filler
+fn fill_window(
+    window: &Window,
+    pool: &mut Pool,
+    filler: &mut Pool,
+    channels: &ChannelMap,
+    interstitials: &mut Interstitials,
+    slots: &mut Vec<Slot>,
+) {
+    let break_seconds = channels.interstitials.break_seconds;
+    let mut cursor = window.start;
+    while cursor < window.end {
+        let remaining = window.end - cursor;
+        let slot = if let Some(entry) = pool.next_fitting(remaining) {
+            place_entry(cursor, &entry, SlotKind::Program, break_seconds, interstitials)
+        } else if let Some(entry) = filler.next_fitting(remaining) {
+            place_entry(cursor, &entry, SlotKind::Filler, 0, interstitials)
+        } else {
+            let mut segments = Vec::new();
+            interstitials.fill_gap(cursor, remaining, &mut segments);
+            Slot {
+                start_second: cursor,
+                length_seconds: remaining,
+                kind: SlotKind::Filler,
+                original_id: String::new(),
+                title: channels.filler.title.clone(),
+                segments,
+            }
+        };
+        cursor = slot.end_second();
+        slots.push(slot);
+    }
+}
+
+// Guide title for every off-air period
+pub const OFF_AIR_TITLE: &str = "OFF AIR";
+
+// The sign-on or sign-off clip for a channel: the one named in its broadcast
+// hours, else one of the right category from the channel, else from anywhere
+fn off_air_clip<'a>(programs: &'a [TvGuideMetadata], named: Option<&str>, category: &str, channel: u8) -> Option<(&'a TvGuideMetadata, u32)> {
+    let candidate = match named {
+        Some(id) => programs.iter().find(|p| p.original_id == id),
+        None => {
+            let mut matching = programs.iter().filter(|p| p.category.eq_ignore_ascii_case(category));
+            matching.clone().find(|p| p.channel_number == channel).or_else(|| matching.next())
+        }
+    }?;
+    parse_duration_seconds(&candidate.duration).map(|runtime| (candidate, runtime))
+}
+
+// Sign-off clip, test pattern, then sign-on clip timed to end as the channel returns
+fn fill_off_air(
+    window: &Window,
+    sign_off: Option<(&TvGuideMetadata, u32)>,
+    sign_on: Option<(&TvGuideMetadata, u32)>,
+    pattern: OffAirPattern,
+) -> Slot {
+    let clip_segment = |clip: &TvGuideMetadata, start: u32, length: u32| Segment {
+        start_second: start,
+        length_seconds: length,
+        kind: SlotKind::OffAir,
+        original_id: clip.original_id.clone(),
+        title: clip.title.clone(),
+        offset_seconds: 0,
+    };
+
+    let mut segments = Vec::new();
+    let mut start = window.start;
+    let mut end = window.end;
+    if let Some((clip, runtime)) = sign_off.filter(|&(_, runtime)| runtime <= end - start) {
+        segments.push(clip_segment(clip, start, runtime));
+        start += runtime;
+    }
+    let mut closing = None;
+    if let Some((clip, runtime)) = sign_on.filter(|&(_, runtime)| runtime <= end - start) {
+        end -= runtime;
+        closing = Some(clip_segment(clip, end, runtime));
+    }
+    if end > start {
+        segments.push(Segment {
+            start_second: start,
+            length_seconds: end - start,
+            kind: SlotKind::OffAir,
+            original_id: String::new(),
+            title: pattern.title().to_string(),
+            offset_seconds: 0,
+        });
+    }
+    segments.extend(closing);
+
+    Slot {
+        start_second: window.start,
+        length_seconds: window.end - window.start,
+        kind: SlotKind::OffAir,
+        original_id: String::new(),
+        title: OFF_AIR_TITLE.to_string(),
+        segments,
+    }
+}
+
+// Build every channel's week. Daypart windows draw on programs of their
+// categories; the rest of the week rotates through the channel's other
+// programs (or all of them when every category is claimed by a daypart).
+// Programs never overlap, commercials and bumpers pad each program out to its
+// block and fill its breaks, and time nothing fits into becomes filler.
+// Outside a channel's broadcast hours it signs off and shows a test pattern.
+pub fn build_weekly_schedule(programs: &[TvGuideMetadata], config: &ScheduleConfig, channels: &ChannelMap) -> WeeklySchedule {
+    let is_filler = |program: &TvGuideMetadata| {
+        channels.filler.categories.iter().any(|c| c.eq_ignore_ascii_case(&program.category))
+    };
+    let is_interstitial = |program: &TvGuideMetadata| channels.interstitials.matches_category(&program.category);
+    let is_off_air_clip = |program: &TvGuideMetadata| {
+        program.category.eq_ignore_ascii_case(SIGN_ON_CATEGORY) || program.category.eq_ignore_ascii_case(SIGN_OFF_CATEGORY)
+    };
+
+    let mut by_channel: BTreeMap<u8, Vec<&TvGuideMetadata>> = BTreeMap::new();
+    for channel in &channels.channels {
+        by_channel.entry(channel.number).or_default();
+    }
+    // Personal channels carry copies of programs from their home channels
+    let personal: Vec<&ChannelConfig> = channels.channels.iter().filter(|c| c.selection.is_some()).collect();
+    // Programs whose home channel number was taken by a personal channel have nowhere of their own to air
+    let mut homeless = Vec::new();
+    for program in programs.iter().filter(|p| !is_filler(p) && !is_interstitial(p) && !is_off_air_clip(p)) {
+        if channels.is_personal(program.channel_number) {
+            homeless.push(program.original_id.clone());
+        } else {
+            by_channel.entry(program.channel_number).or_default().push(program);
+        }
+        for channel in &personal {
+            if channel.selection.as_ref().is_some_and(|selection| selection.matches(program, &config.favorites)) {
+                by_channel.entry(channel.number).or_default().push(program);
+            }
+        }
+    }
+
+    let filler_entries: Vec<Entry> = running_order(programs.iter().filter(|p| is_filler(p)).collect(), config)
+        .into_iter()
+        .map(|program| plan_entry(program, config, None))
+        .collect();
+    let spots: Vec<(&TvGuideMetadata, u32)> = running_order(programs.iter().filter(|p| is_interstitial(p)).collect(), config)
+        .into_iter()
+        .filter_map(|spot| parse_duration_seconds(&spot.duration).map(|runtime| (spot, runtime)))
+        .filter(|&(_, runtime)| runtime > 0)
+        .collect();
+
+    let mut schedule = WeeklySchedule { unscheduled: homeless, ..WeeklySchedule::default() };
+
+    for (index, (channel, channel_programs)) in by_channel.into_iter().enumerate() {
+        let channel_config = channels.channel(channel);
+        let dayparts = channel_config.map(|c| c.dayparts.as_slice()).unwrap_or(&[]);
+        let hours = channel_config.and_then(|c| c.broadcast_hours.as_ref());
+        let sign_off_clip = hours.and_then(|h| off_air_clip(programs, h.sign_off_clip.as_deref(), SIGN_OFF_CATEGORY, channel));
+        let sign_on_clip = hours.and_then(|h| off_air_clip(programs, h.sign_on_clip.as_deref(), SIGN_ON_CATEGORY, channel));
+        let pattern = hours.map(|h| h.pattern).unwrap_or_default();
+        let entries: Vec<Entry> = running_order(channel_programs, config)
+            .into_iter()
+            .map(|program| plan_entry(program, config, Some(&channels.interstitials)))
+            .collect();
+
+        let mut daypart_pools: Vec<Pool> = dayparts
+            .iter()
+            .map(|daypart| {
+                Pool::new(entries.iter().filter(|e| daypart.matches_category(&e.program.category)).cloned().collect())
+            })
+            .collect();
+        let unclaimed: Vec<Entry> = entries
+            .iter()
+            .filter(|e| !dayparts.iter().any(|d| d.matches_category(&e.program.category)))
+            .cloned()
+            .collect();
+        let mut general_pool = Pool::new(if unclaimed.is_empty() { entries.clone() } else { unclaimed });
+        let mut filler_pool = Pool::new(filler_entries.clone());
+        // Start each channel at a different point in the rotation
+        let mut interstitials = Interstitials::new(spots.clone(), index, &channels.interstitials, &channels.filler.title);
+
+        let mut slots = Vec::new();
+        for window in channel_windows(dayparts, hours) {
+            let pool = match window.kind {
+                WindowKind::OffAir { sign_off, sign_on } => {
+                    let sign_off = if sign_off { sign_off_clip } else { None };
+                    let sign_on = if sign_on { sign_on_clip } else { None };
+                    slots.push(fill_off_air(&window, sign_off, sign_on, pattern));
+                    continue;
+                }
+                WindowKind::Daypart(index) => &mut daypart_pools[index],
+                WindowKind::General => &mut general_pool,
+            };
+            fill_window(&window, pool, &mut filler_pool, channels, &mut interstitials, &mut slots);
+        }
+
+        // A program left off a personal channel still airs on its home channel
+        for entry in entries.iter().filter(|_| !channels.is_personal(channel)) {
+            if !slots.iter().any(|slot| slot.original_id == entry.program.original_id) {
+                schedule.unscheduled.push(entry.program.original_id.clone());
+            }
+        }
+
+        schedule.channels.insert(channel, slots);
+    }
+
+    schedule
+}
+
+// Record a program's current or next airing in its sidecar fields
+pub fn apply_next_airing(schedule: &WeeklySchedule, metadata: &mut TvGuideMetadata, time: &TimeSettings, now: DateTime<Utc>) {
+    let next = schedule
+        .airings(&metadata.original_id)
+        .map(|slot| (time.airing_start(slot.start_second, slot.length_seconds, now), slot.length_seconds))
+        .min_by_key(|(start, _)| *start);
+
+    match next {
+        Some((start, length)) => {
+            metadata.start_time = Some(start.fixed_offset());
+            metadata.end_time = Some((start + Duration::seconds(length as i64)).fixed_offset());
+        }
+        None => {
+            metadata.start_time = None;
+            metadata.end_time = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channels::{FillerConfig, ProgramSelection};
+    use std::collections::BTreeSet;
+    use crate::sidecar::test_program;
+
+    const HOUR: u32 = 3600;
+
+    // Every slot follows the one before it with no gap or overlap across the
+    // whole week, and its segments do the same inside it
+    fn assert_covers_week(slots: &[Slot]) {
+        let mut cursor = 0;
+        for slot in slots {
+            assert_eq!(slot.start_second, cursor, "slot {:?} doesn't start where the last one ended", slot.title);
+            let mut inside = slot.start_second;
+            for segment in &slot.segments {
+                assert_eq!(segment.start_second, inside, "segment {:?} of {:?} leaves a gap or overlaps", segment.title, slot.title);
+                assert!(segment.length_seconds > 0);
+                inside += segment.length_seconds;
+            }
+            assert_eq!(inside, slot.end_second(), "segments of {:?} don't fill it", slot.title);
+            cursor = slot.end_second();
+        }
+        assert_eq!(cursor, SECONDS_PER_WEEK);
+    }
+
+    fn library() -> Vec<TvGuideMetadata> {
+        vec![
+            test_program("sitcom-a", "Comedy", 3, "22:30"),
+            test_program("sitcom-b", "Comedy", 3, "00:24:10"),
+            test_program("drama", "Drama", 3, "48 min"),
+            test_program("movie", "Movie", 3, "1:52:00"),
+            test_program("news", "News", 5, "30:00"),
+            test_program("sports", "Sports", 5, "2:10:00"),
+        ]
+    }
+
+    fn channel(number: u8, dayparts: Vec<Daypart>, broadcast_hours: Option<BroadcastHours>) -> ChannelConfig {
+        ChannelConfig { number, callsign: None, dayparts, broadcast_hours, name: None, selection: None, owner: None }
+    }
+
+    fn daypart(days: &[&str], start: &str, end: &str, categories: &[&str]) -> Daypart {
+        Daypart {
+            name: "Test".to_string(),
+            days: days.iter().map(|d| d.to_string()).collect(),
+            start: start.to_string(),
+            end: end.to_string(),
+            categories: categories.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+
+    // Slots that start inside [start, end)
+    fn slots_between(slots: &[Slot], start: u32, end: u32) -> Vec<&Slot> {
+        slots.iter().filter(|slot| slot.start_second >= start && slot.start_second < end).collect()
+    }
+
+    fn segment_ids(slot: &Slot) -> Vec<&str> {
+        slot.segments.iter().map(|segment| if segment.original_id.is_empty() { segment.title.as_str() } else { segment.original_id.as_str() }).collect()
+    }
+
+    fn off_air_hours(sign_on: &str, sign_off: &str, pattern: OffAirPattern) -> BroadcastHours {
+        BroadcastHours { sign_on: sign_on.to_string(), sign_off: sign_off.to_string(), sign_on_clip: None, sign_off_clip: None, pattern }
+    }
+
+    #[test]
+    fn fills_every_channel_for_the_whole_week_without_overlaps() {
+        let schedule = build_weekly_schedule(&library(), &ScheduleConfig::default(), &ChannelMap::default());
+        assert_eq!(schedule.channels.keys().copied().collect::<Vec<_>>(), vec![3, 5]);
+        for slots in schedule.channels.values() {
+            assert_covers_week(slots);
+        }
+        assert!(schedule.unscheduled.is_empty());
+    }
+
+    #[test]
+    fn programs_take_whole_blocks_of_their_real_length() {
+        let config = ScheduleConfig { block_minutes: 15, ..Default::default() };
+        let schedule = build_weekly_schedule(&library(), &config, &ChannelMap::default());
+        for slot in schedule.channels.values().flatten().filter(|slot| slot.kind == SlotKind::Program) {
+            assert_eq!(slot.start_second % (15 * 60), 0);
+            let expected = match slot.original_id.as_str() {
+                "sitcom-a" | "sitcom-b" => 30 * 60,
+                "drama" => HOUR,
+                "movie" => 2 * HOUR,
+                "news" => 30 * 60,
+                "sports" => 2 * HOUR + 15 * 60,
+                other => panic!("unexpected program {}", other),
+            };
+            assert_eq!(slot.length_seconds, expected, "{}", slot.original_id);
+            // The program plays from its start, and the rest of the block is padding
+            let content = &slot.segments[0];
+            assert_eq!((content.kind, content.offset_seconds), (SlotKind::Program, 0));
+        }
+    }
+
+    #[test]
+    fn same_library_and_seed_give_the_same_week() {
+        let channels = ChannelMap::default();
+        let first = build_weekly_schedule(&library(), &ScheduleConfig::default(), &channels);
+        let mut reversed = library();
+        reversed.reverse();
+        let second = build_weekly_schedule(&reversed, &ScheduleConfig::default(), &channels);
+        assert_eq!(first.channels, second.channels);
+
+        let reseeded = build_weekly_schedule(&library(), &ScheduleConfig { seed: 7, ..Default::default() }, &channels);
+        let order = |schedule: &WeeklySchedule| -> Vec<String> { schedule.channels[&3].iter().take(4).map(|slot| slot.original_id.clone()).collect() };
+        assert_ne!(order(&first), order(&reseeded));
+        for slots in reseeded.channels.values() {
+            assert_covers_week(slots);
+        }
+    }
+
+    #[test]
+    fn programs_on_a_personal_channels_number_are_unscheduled() {
+        let personal = ChannelConfig {
+            selection: Some(ProgramSelection { categories: vec!["Comedy".to_string()], ..Default::default() }),
+            ..channel(5, Vec::new(), None)
+        };
+        let channels = ChannelMap { channels: vec![personal], ..Default::default() };
+        let schedule = build_weekly_schedule(&library(), &ScheduleConfig::default(), &channels);
+
+        let airing = |channel: u8| -> BTreeSet<&str> {
+            schedule.channels[&channel].iter().filter(|slot| slot.kind == SlotKind::Program).map(|slot| slot.original_id.as_str()).collect()
+        };
+        assert_eq!(airing(5), BTreeSet::from(["sitcom-a", "sitcom-b"]));
+        assert!(airing(3).contains("sitcom-a"));
+        assert_eq!(schedule.unscheduled, vec!["news", "sports"]);
+    }
+
+    #[test]
+    fn programs_longer_than_their_window_are_unscheduled() {
+        let mut programs = library();
+        programs.push(test_program("marathon", "Marathon", 3, "6:00:00"));
+        let channels = ChannelMap {
+            channels: vec![channel(3, vec![daypart(&["Saturday"], "7:00 AM", "11:00 AM", &["Marathon"])], None)],
+            ..Default::default()
+        };
+        let schedule = build_weekly_schedule(&programs, &ScheduleConfig::default(), &channels);
+        assert_eq!(schedule.unscheduled, vec!["marathon".to_string()]);
+        let airs = |id: &str| schedule.channels[&3].iter().any(|slot| slot.original_id == id);
+        assert!(!airs("marathon") && airs("movie"));
+    }
+
+    #[test]
+    fn bad_durations_get_a_single_block() {
+        let bad = ["inf", "-inf", "NaN", "1e10", "99999999:00:00", "-30", "0:-30", "0:00", "0", "170:00:00"];
+        let mut programs = library();
+        programs.extend(bad.iter().map(|duration| test_program(duration, "Comedy", 3, duration)));
+        let schedule = build_weekly_schedule(&programs, &ScheduleConfig::default(), &ChannelMap::default());
+        assert_covers_week(&schedule.channels[&3]);
+        for duration in bad {
+            let slot = schedule.channels[&3].iter().find(|slot| slot.original_id == duration).unwrap();
+            assert_eq!(slot.length_seconds, 1800, "{}", duration);
+        }
+        assert!(schedule.unscheduled.is_empty());
+    }
+
+    #[test]
+    fn parses_sidecar_durations() {
+        for (text, seconds) in [("00:30:00", Some(1800)), ("1:23:45", Some(5025)), ("28 min", Some(1680)), ("1800.5", Some(1801)), ("22", Some(1320)), ("168:00:00", Some(SECONDS_PER_WEEK)), ("soon", None), ("inf", None), ("NaN", None), ("1e10", None), ("-30", None), ("0", None)] {
+            assert_eq!(parse_duration_seconds(text), seconds, "{}", text);
+        }
+        assert_eq!(round_to_blocks(1, 1800), 1800);
+        assert_eq!(round_to_blocks(1801, 1800), 3600);
+        assert_eq!(round_to_blocks(0, 1800), 1800);
+        assert_eq!(round_to_blocks(u32::MAX, 1800), u32::MAX);
+    }
+
+    #[test]
+    fn dayparts_air_only_their_categories() {
+        let mut programs = library();
+        programs.push(test_program("cartoon-a", "Cartoon", 3, "7:00"));
+        programs.push(test_program("cartoon-b", "Cartoon", 3, "11:00"));
+        let channels = ChannelMap {
+            channels: vec![channel(3, vec![daypart(&["Saturday"], "7:00 AM", "11:00 AM", &["Cartoon"])], None)],
+            ..Default::default()
+        };
+        let schedule = build_weekly_schedule(&programs, &ScheduleConfig::default(), &channels);
+        let slots = &schedule.channels[&3];
+        assert_covers_week(slots);
+
+        let saturday = 5 * SECONDS_PER_DAY;
+        let morning = slots_between(slots, saturday + 7 * HOUR, saturday + 11 * HOUR);
+        assert_eq!(morning.first().unwrap().start_second, saturday + 7 * HOUR);
+        assert_eq!(morning.last().unwrap().end_second(), saturday + 11 * HOUR);
+        assert!(morning.iter().all(|slot| slot.kind == SlotKind::Program && slot.original_id.starts_with("cartoon")));
+        // Outside the daypart the cartoons give way to the rest of the channel
+        let elsewhere: Vec<&Slot> = slots.iter().filter(|slot| !morning.iter().any(|m| m.start_second == slot.start_second)).collect();
+        assert!(elsewhere.iter().all(|slot| !slot.original_id.starts_with("cartoon")));
+    }
+
+    #[test]
+    fn overlapping_dayparts_go_to_the_one_that_starts_first() {
+        let mut programs = library();
+        programs.push(test_program("news-3", "News", 3, "30:00"));
+        let dayparts = vec![
+            daypart(&["Monday"], "8:00 PM", "11:00 PM", &["News"]),
+            daypart(&["Monday"], "6:00 PM", "9:00 PM", &["Comedy"]),
+            // Starts with the News daypart below; listed first, so it wins the shared hour
+            daypart(&["Tuesday"], "12:00 PM", "1:00 PM", &["Comedy"]),
+            daypart(&["Tuesday"], "12:00 PM", "2:00 PM", &["News"]),
+        ];
+        let channels = ChannelMap { channels: vec![channel(3, dayparts, None)], ..Default::default() };
+        let schedule = build_weekly_schedule(&programs, &ScheduleConfig::default(), &channels);
+        let slots = &schedule.channels[&3];
+        assert_covers_week(slots);
+
+        let tuesday = SECONDS_PER_DAY;
+        for (start, end, category) in [
+            (18 * HOUR, 21 * HOUR, "sitcom"),
+            (21 * HOUR, 23 * HOUR, "news"),
+            (tuesday + 12 * HOUR, tuesday + 13 * HOUR, "sitcom"),
+            (tuesday + 13 * HOUR, tuesday + 14 * HOUR, "news"),
+        ] {
+            let window = slots_between(slots, start, end);
+            assert_eq!(window.first().unwrap().start_second, start);
+            assert_eq!(window.last().unwrap().end_second(), end);
+            assert!(window.iter().all(|slot| slot.kind != SlotKind::Program || slot.original_id.starts_with(category)), "{}..{}", start, end);
+            assert!(window.iter().any(|slot| slot.kind == SlotKind::Program), "{}..{}", start, end);
+        }
+    }
+
+    #[test]
+    fn empty_daypart_gets_filler() {
+        let daypart = daypart(&["weekdays"], "6:00 AM", "9:00 AM", &["Exercise"]);
+        let channels = ChannelMap { channels: vec![channel(3, vec![daypart.clone()], None)], ..Default::default() };
+        let schedule = build_weekly_schedule(&library(), &ScheduleConfig::default(), &channels);
+        for day in 0..7 {
+            let start = day * SECONDS_PER_DAY + 6 * HOUR;
+            let morning = slots_between(&schedule.channels[&3], start, start + 3 * HOUR);
+            if day < 5 {
+                assert_eq!(morning.len(), 1);
+                assert_eq!((morning[0].kind, morning[0].title.as_str(), morning[0].length_seconds), (SlotKind::Filler, "Paid Programming", 3 * HOUR));
+            } else {
+                assert!(morning.iter().all(|slot| slot.kind == SlotKind::Program));
+            }
+        }
+
+        // Filler categories from the library are used before the placeholder
+        let mut programs = library();
+        programs.push(test_program("infomercial", "Infomercial", 0, "28:30"));
+        let channels = ChannelMap {
+            channels: vec![channel(3, vec![daypart], None)],
+            filler: FillerConfig { title: "Paid Programming".to_string(), categories: vec!["Infomercial".to_string()] },
+            ..Default::default()
+        };
+        let schedule = build_weekly_schedule(&programs, &ScheduleConfig::default(), &channels);
+        let morning = slots_between(&schedule.channels[&3], 6 * HOUR, 9 * HOUR);
+        assert_eq!(morning.len(), 6);
+        assert!(morning.iter().all(|slot| slot.kind == SlotKind::Filler && slot.original_id == "infomercial"));
+        assert!(!schedule.channels.contains_key(&0));
+    }
+
+    #[test]
+    fn interstitials_fill_breaks_and_padding_without_repeating() {
+        let programs = vec![
+            test_program("sitcom", "Comedy", 3, "22:00"),
+            test_program("ad-a", "Commercial", 0, "0:30"),
+            test_program("ad-b", "Commercial", 0, "0:30"),
+            test_program("station-id", "Station ID", 0, "0:10"),
+            test_program("ad-reel", "Commercial", 0, "10:00"),
+        ];
+        let channels = ChannelMap {
+            interstitials: InterstitialConfig { break_points_minutes: vec![8, 15], break_seconds: 60, ..Default::default() },
+            ..Default::default()
+        };
+        let schedule = build_weekly_schedule(&programs, &ScheduleConfig::default(), &channels);
+        assert_eq!(schedule.channels.keys().copied().collect::<Vec<_>>(), vec![3]);
+        let slots = &schedule.channels[&3];
+        assert_covers_week(slots);
+        assert!(slots.iter().all(|slot| slot.kind == SlotKind::Program && slot.original_id == "sitcom"));
+
+        // The program is cut at its break points, with a minute of spots in each break
+        let first = &slots[0];
+        let content: Vec<(u32, u32, u32)> = first
+            .segments
+            .iter()
+            .filter(|segment| segment.kind == SlotKind::Program)
+            .map(|segment| (segment.start_second, segment.length_seconds, segment.offset_seconds))
+            .collect();
+        assert_eq!(content, vec![(0, 480, 0), (540, 420, 480), (1020, 420, 900)]);
+        assert!(first.segments.iter().filter(|segment| segment.kind != SlotKind::Program).all(|segment| segment.kind == SlotKind::Interstitial));
+
+        let spots: Vec<&Segment> = slots.iter().flat_map(|slot| &slot.segments).filter(|segment| segment.kind == SlotKind::Interstitial).collect();
+        for pair in spots.windows(2) {
+            if pair[0].start_second + pair[0].length_seconds == pair[1].start_second {
+                assert_ne!(pair[0].original_id, pair[1].original_id, "same spot back to back at {}", pair[1].start_second);
+            }
+        }
+        for id in ["ad-a", "ad-b", "station-id", "ad-reel"] {
+            assert!(spots.iter().any(|segment| segment.original_id == id), "{} never aired", id);
+        }
+        // A long reel airs in pieces, each carrying on where the last one stopped
+        let mut reel_position = 0;
+        for piece in spots.iter().filter(|segment| segment.original_id == "ad-reel") {
+            assert_eq!(piece.offset_seconds, reel_position);
+            reel_position = (piece.offset_seconds + piece.length_seconds) % 600;
+        }
+    }
+
+    #[test]
+    fn signs_off_overnight_with_clips_around_the_pattern() {
+        let mut programs = library();
+        programs.push(test_program("anthem", SIGN_OFF_CATEGORY, 3, "2:00"));
+        programs.push(test_program("morning", SIGN_ON_CATEGORY, 3, "1:30"));
+        let channels = ChannelMap {
+            channels: vec![channel(3, Vec::new(), Some(off_air_hours("6:00 AM", "1:00 AM", OffAirPattern::Bars)))],
+            ..Default::default()
+        };
+        let schedule = build_weekly_schedule(&programs, &ScheduleConfig::default(), &channels);
+        let slots = &schedule.channels[&3];
+        assert_covers_week(slots);
+
+        for day in 0..7 {
+            let night = slots_between(slots, day * SECONDS_PER_DAY + HOUR, day * SECONDS_PER_DAY + 6 * HOUR);
+            assert_eq!(night.len(), 1, "day {}", day);
+            let slot = night[0];
+            assert_eq!((slot.kind, slot.title.as_str()), (SlotKind::OffAir, OFF_AIR_TITLE));
+            assert_eq!((slot.start_second, slot.end_second()), (day * SECONDS_PER_DAY + HOUR, day * SECONDS_PER_DAY + 6 * HOUR));
+            assert_eq!(segment_ids(slot), vec!["anthem", "SMPTE Color Bars", "morning"]);
+            assert!(slot.segments.iter().all(|segment| segment.kind == SlotKind::OffAir));
+        }
+        // The clips air only in the off-air slots, and every program still airs
+        assert!(slots.iter().filter(|slot| slot.kind != SlotKind::OffAir).all(|slot| slot.original_id != "anthem" && slot.original_id != "morning"));
+        assert!(schedule.unscheduled.is_empty());
+    }
+
+    #[test]
+    fn sunday_night_sign_off_wraps_to_monday_morning() {
+        let mut programs = library();
+        programs.push(test_program("anthem", SIGN_OFF_CATEGORY, 3, "2:00"));
+        programs.push(test_program("morning", SIGN_ON_CATEGORY, 3, "1:30"));
+        let channels = ChannelMap {
+            channels: vec![channel(3, Vec::new(), Some(off_air_hours("6:00 AM", "11:30 PM", OffAirPattern::Static)))],
+            ..Default::default()
+        };
+        let schedule = build_weekly_schedule(&programs, &ScheduleConfig::default(), &channels);
+        let slots = &schedule.channels[&3];
+        assert_covers_week(slots);
+
+        // Monday morning is the end of Sunday night: pattern then sign-on
+        assert_eq!((slots[0].kind, slots[0].end_second()), (SlotKind::OffAir, 6 * HOUR));
+        assert_eq!(segment_ids(&slots[0]), vec!["Static", "morning"]);
+        // Sunday night signs off and runs the pattern to the end of the week
+        let last = slots.last().unwrap();
+        assert_eq!((last.kind, last.start_second), (SlotKind::OffAir, 6 * SECONDS_PER_DAY + 23 * HOUR + 30 * 60));
+        assert_eq!(segment_ids(last), vec!["anthem", "Static"]);
+    }
+
+    #[test]
+    fn clips_longer_than_the_off_air_time_are_left_out() {
+        let sign_off = test_program("anthem", SIGN_OFF_CATEGORY, 3, "10:00");
+        let sign_on = test_program("morning", SIGN_ON_CATEGORY, 3, "10:00");
+        let window = Window { start: 0, end: 15 * 60, kind: WindowKind::OffAir { sign_off: true, sign_on: true } };
+        let slot = fill_off_air(&window, Some((&sign_off, 600)), Some((&sign_on, 600)), OffAirPattern::Bars);
+        assert_eq!(segment_ids(&slot), vec!["anthem", "SMPTE Color Bars"]);
+        assert_eq!(slot.segments[1].length_seconds, 5 * 60);
+
+        let slot = fill_off_air(&window, None, None, OffAirPattern::Bars);
+        assert_eq!(segment_ids(&slot), vec!["SMPTE Color Bars"]);
+        assert_eq!(slot.segments[0].length_seconds, 15 * 60);
+    }
+
+    #[test]
+    fn oversized_breaks_and_blocks_stay_inside_the_week() {
+        let programs = vec![test_program("sitcom", "Comedy", 3, "1:00:00"), test_program("ad", "Commercial", 0, "0:30")];
+        let channels = ChannelMap {
+            interstitials: InterstitialConfig { break_points_minutes: vec![10, 20, u32::MAX], break_seconds: u32::MAX, ..Default::default() },
+            ..Default::default()
+        };
+        let schedule = build_weekly_schedule(&programs, &ScheduleConfig::default(), &channels);
+        let slots = &schedule.channels[&3];
+        assert_covers_week(slots);
+        // The breaks can't fit, so the program airs in one piece
+        assert_eq!(slots[0].segments[0].length_seconds, HOUR);
+        assert_eq!(slots[0].length_seconds, HOUR);
+
+        let config = ScheduleConfig { block_minutes: u32::MAX, ..Default::default() };
+        let schedule = build_weekly_schedule(&programs, &config, &channels);
+        assert_eq!(schedule.channels[&3].len(), 1);
+        assert_eq!(schedule.channels[&3][0].length_seconds, SECONDS_PER_WEEK);
+        assert_covers_week(&schedule.channels[&3]);
+    }
+}
@@ -0,0 +1,353 @@
+use chrono::{DateTime, FixedOffset, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::parental::Rating;
+use crate::schedule;
+
+// Current version of the sidecar format written next to each downloaded video
+pub const SCHEMA_VERSION: u32 = 3;
+
+// JSON Schema describing the current sidecar format, for tools that want to write compatible metadata
+pub const JSON_SCHEMA: &str = include_str!("../schema/tv_guide_metadata.schema.json");
+
+// Structure for TV Guide metadata
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TvGuideMetadata {
+    pub schema_version: u32,
+    pub title: String,
+    pub station: String,
+    pub description: String,
+    pub year: String,
+    pub duration: String,
+    pub category: String,
+    pub channel_number: u8,
+    // Next airing on the schedule, in the guide's timezone; None when unscheduled
+    pub start_time: Option<DateTime<FixedOffset>>,
+    pub end_time: Option<DateTime<FixedOffset>>,
+    pub thumbnail_url: String,
+    pub tags: Vec<String>,
+    pub original_id: String,
+    pub download_date: DateTime<Utc>,
+    pub station_callsign: String,
+    pub is_featured: bool,
+    // Content rating, set by hand or guessed from the subjects at download; None when unrated
+    #[serde(default)]
+    pub rating: Option<Rating>,
+    // SHA-1 of the video file, recorded at download or by the duplicate check; None until then
+    #[serde(default)]
+    pub content_sha1: Option<String>,
+}
+
+// Reasons a sidecar could not be loaded
+#[derive(Debug)]
+pub enum SidecarError {
+    Io(std::io::Error),
+    Parse(serde_json::Error),
+    UnsupportedVersion(u32),
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for SidecarError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SidecarError::Io(e) => write!(f, "could not read sidecar: {}", e),
+            SidecarError::Parse(e) => write!(f, "malformed JSON: {}", e),
+            SidecarError::UnsupportedVersion(v) => write!(
+                f,
+                "schema version {} is newer than supported version {}",
+                v, SCHEMA_VERSION
+            ),
+            SidecarError::Invalid(problems) => write!(f, "invalid metadata: {}", problems.join("; ")),
+        }
+    }
+}
+
+impl std::error::Error for SidecarError {}
+
+impl From<std::io::Error> for SidecarError {
+    fn from(e: std::io::Error) -> Self {
+        SidecarError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SidecarError {
+    fn from(e: serde_json::Error) -> Self {
+        SidecarError::Parse(e)
+    }
+}
+
+// A successfully loaded sidecar, with the version it was stored as on disk
+pub struct LoadedSidecar {
+    pub metadata: TvGuideMetadata,
+    pub migrated_from: Option<u32>,
+}
+
+// Path of the sidecar that belongs to a video file
+pub fn sidecar_path(video_path: &Path) -> PathBuf {
+    video_path.with_extension("json")
+}
+
+impl TvGuideMetadata {
+    // Check the fields that the guide and scheduler rely on
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut problems = Vec::new();
+
+        if self.schema_version != SCHEMA_VERSION {
+            problems.push(format!("schema_version must be {}", SCHEMA_VERSION));
+        }
+        if self.title.trim().is_empty() {
+            problems.push("title is empty".to_string());
+        }
+        if self.original_id.trim().is_empty() {
+            problems.push("original_id is empty".to_string());
+        }
+        if self.channel_number == 0 {
+            problems.push("channel_number must be between 1 and 255".to_string());
+        }
+        if self.station_callsign.trim().is_empty() {
+            problems.push("station_callsign is empty".to_string());
+        }
+        if self.duration.trim().is_empty() {
+            problems.push("duration is empty".to_string());
+        } else if schedule::parse_duration_seconds(&self.duration).is_none() {
+            problems.push(format!("duration '{}' is not a length of at most a week", self.duration));
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+
+    // Write the sidecar, replacing any existing file atomically
+    pub fn save(&self, path: &Path) -> Result<(), SidecarError> {
+        let json = serde_json::to_string_pretty(self)?;
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, json)?;
+        fs::rename(&tmp_path, path)?;
+        Ok(())
+    }
+}
+
+// Load a sidecar from disk, upgrading older versions in place
+pub fn load(path: &Path) -> Result<LoadedSidecar, SidecarError> {
+    let content = fs::read_to_string(path)?;
+    let value: Value = serde_json::from_str(&content)?;
+
+    let (value, migrated_from) = migrate(value)?;
+    let metadata: TvGuideMetadata = serde_json::from_value(value)?;
+    metadata.validate().map_err(SidecarError::Invalid)?;
+
+    if migrated_from.is_some() {
+        metadata.save(path)?;
+    }
+
+    Ok(LoadedSidecar { metadata, migrated_from })
+}
+
+// Version of a raw sidecar document; files written before versioning count as version 1
+fn stored_version(value: &Value) -> Result<u32, SidecarError> {
+    let Some(version) = value.get("schema_version") else {
+        return Ok(1);
+    };
+    match version.as_u64() {
+        Some(0) | None => Err(SidecarError::Invalid(vec![format!("schema_version {} is not a version number", version)])),
+        Some(v) => u32::try_from(v).map_err(|_| SidecarError::Invalid(vec![format!("schema_version {} is out of range", v)])),
+    }
+}
+
+// Apply each migration step until the document reaches the current version
+pub fn migrate(mut value: Value) -> Result<(Value, Option<u32>), SidecarError> {
+    if !value.is_object() {
+        return Err(SidecarError::Invalid(vec!["sidecar is not a JSON object".to_string()]));
+    }
+    let original = stored_version(&value)?;
+    if original > SCHEMA_VERSION {
+        return Err(SidecarError::UnsupportedVersion(original));
+    }
+
+    let mut version = original;
+    while version < SCHEMA_VERSION {
+        value = match version {
+            1 => migrate_v1_to_v2(value)?,
+            2 => migrate_v2_to_v3(value)?,
+            _ => return Err(SidecarError::UnsupportedVersion(version)),
+        };
+        version += 1;
+        if let Some(object) = value.as_object_mut() {
+            object.insert("schema_version".to_string(), Value::from(version));
+        }
+    }
+
+    let migrated_from = if original < SCHEMA_VERSION { Some(original) } else { None };
+    Ok((value, migrated_from))
+}
+
+// v1 stored download_date as a stringified epoch
+fn migrate_v1_to_v2(mut value: Value) -> Result<Value, SidecarError> {
+    let download_date = match value.get("download_date") {
+        Some(Value::String(s)) => s.trim().parse::<u64>().map_err(|_| {
+            SidecarError::Invalid(vec![format!("download_date '{}' is not an epoch timestamp", s)])
+        })?,
+        Some(Value::Number(n)) => n.as_u64().unwrap_or(0),
+        _ => 0,
+    };
+    if let Some(object) = value.as_object_mut() {
+        object.insert("download_date".to_string(), Value::from(download_date));
+    }
+    Ok(value)
+}
+
+// v3 replaced the display strings for the time slot with real timestamps, which
+// are filled in again the next time the library is scheduled, and stores the
+// download date as an RFC 3339 timestamp
+fn migrate_v2_to_v3(mut value: Value) -> Result<Value, SidecarError> {
+    let epoch = value.get("download_date").and_then(Value::as_i64).unwrap_or(0);
+    let download_date = Utc.timestamp_opt(epoch, 0).single().ok_or_else(|| {
+        SidecarError::Invalid(vec![format!("download_date {} is out of range", epoch)])
+    })?;
+
+    if let Some(object) = value.as_object_mut() {
+        object.remove("timeslot");
+        object.remove("day_of_week");
+        object.insert("start_time".to_string(), Value::Null);
+        object.insert("end_time".to_string(), Value::Null);
+        object.insert("download_date".to_string(), Value::from(download_date.to_rfc3339()));
+    }
+    Ok(value)
+}
+
+// A valid current-version sidecar for tests elsewhere in the crate
+#[cfg(test)]
+pub(crate) fn test_program(original_id: &str, category: &str, channel_number: u8, duration: &str) -> TvGuideMetadata {
+    TvGuideMetadata {
+        schema_version: SCHEMA_VERSION,
+        title: original_id.to_string(),
+        station: "WEWS".to_string(),
+        description: String::new(),
+        year: "1994".to_string(),
+        duration: duration.to_string(),
+        category: category.to_string(),
+        channel_number,
+        start_time: None,
+        end_time: None,
+        thumbnail_url: String::new(),
+        tags: Vec::new(),
+        original_id: original_id.to_string(),
+        download_date: Utc.timestamp_opt(779_999_999, 0).unwrap(),
+        station_callsign: "WEWS".to_string(),
+        is_featured: false,
+        rating: None,
+        content_sha1: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // A sidecar as the first downloader wrote it: no version, a stringified
+    // epoch and display strings for the time slot
+    fn v1_sidecar() -> Value {
+        json!({
+            "title": "Show 1",
+            "station": "WEWS",
+            "description": "",
+            "year": "1994",
+            "duration": "30:00",
+            "category": "Comedy",
+            "channel_number": 5,
+            "timeslot": "8:00 PM",
+            "day_of_week": "Monday",
+            "thumbnail_url": "",
+            "tags": [],
+            "original_id": "show1",
+            "download_date": "779999999",
+            "station_callsign": "WEWS",
+            "is_featured": false
+        })
+    }
+
+    #[test]
+    fn migrates_v1_to_current() {
+        let (value, migrated_from) = migrate(v1_sidecar()).unwrap();
+        assert_eq!(migrated_from, Some(1));
+        assert_eq!(value["schema_version"], json!(SCHEMA_VERSION));
+        assert!(value.get("timeslot").is_none());
+        assert!(value.get("day_of_week").is_none());
+
+        let metadata: TvGuideMetadata = serde_json::from_value(value).unwrap();
+        assert_eq!(metadata.download_date, Utc.timestamp_opt(779_999_999, 0).unwrap());
+        assert_eq!(metadata.start_time, None);
+        assert!(metadata.validate().is_ok());
+    }
+
+    #[test]
+    fn current_version_is_not_migrated() {
+        let (value, _) = migrate(v1_sidecar()).unwrap();
+        let (again, migrated_from) = migrate(value.clone()).unwrap();
+        assert_eq!(migrated_from, None);
+        assert_eq!(again, value);
+    }
+
+    #[test]
+    fn rejects_durations_that_are_not_a_real_length() {
+        for duration in ["inf", "NaN", "-30", "0:00", "1e10", "99999999:00:00", "half an hour"] {
+            let metadata = test_program("show", "Comedy", 3, duration);
+            let problems = metadata.validate().unwrap_err();
+            assert!(problems.iter().any(|problem| problem.contains("duration")), "{}", duration);
+        }
+        assert!(test_program("show", "Comedy", 3, "1:30:00").validate().is_ok());
+    }
+
+    #[test]
+    fn rejects_non_object_root() {
+        for value in [json!([1, 2]), json!("sidecar"), json!(3), Value::Null] {
+            assert!(matches!(migrate(value), Err(SidecarError::Invalid(_))));
+        }
+    }
+
+    #[test]
+    fn rejects_future_version() {
+        let mut value = v1_sidecar();
+        value["schema_version"] = json!(SCHEMA_VERSION + 1);
+        assert!(matches!(migrate(value), Err(SidecarError::UnsupportedVersion(v)) if v == SCHEMA_VERSION + 1));
+    }
+
+    #[test]
+    fn rejects_versions_that_are_not_a_whole_number_in_range() {
+        for version in [json!("2"), json!(2.5), json!(-1), json!(0), Value::Null, json!(u64::from(u32::MAX) + 2)] {
+            let mut value = v1_sidecar();
+            value["schema_version"] = version.clone();
+            assert!(matches!(migrate(value), Err(SidecarError::Invalid(_))), "{}", version);
+        }
+        let mut value = v1_sidecar();
+        value["schema_version"] = json!(u32::MAX);
+        assert!(matches!(migrate(value), Err(SidecarError::UnsupportedVersion(u32::MAX))));
+    }
+
+    #[test]
+    fn load_upgrades_in_place_and_reports_bad_files() {
+        let dir = std::env::temp_dir().join(format!("sidecar-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let path = dir.join("show1.json");
+        fs::write(&path, v1_sidecar().to_string()).unwrap();
+        let loaded = load(&path).unwrap();
+        assert_eq!(loaded.migrated_from, Some(1));
+        let stored: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(stored["schema_version"], json!(SCHEMA_VERSION));
+
+        let bad = dir.join("bad.json");
+        fs::write(&bad, "[1,2]").unwrap();
+        assert!(matches!(load(&bad), Err(SidecarError::Invalid(_))));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,401 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::ffi::CString;
+use std::fmt;
+use std::fs;
+use std::mem::MaybeUninit;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+use crate::history::WatchHistory;
+use crate::library::{self, LibraryItem};
+use crate::local_time::TimeSettings;
+use crate::schedule::{WeeklySchedule, SECONDS_PER_WEEK};
+use crate::trash::DEFAULT_TRASH_DAYS;
+
+// A size limit on the download directory and how programs are chosen for
+// removal when a download would go over it. With no quota nothing is removed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct StorageSettings {
+    // Most the download directory may hold, videos and sidecars together
+    pub quota_mb: Option<u64>,
+    pub eviction: EvictionPolicy,
+    // original_ids that are never removed to make room
+    pub pinned: BTreeSet<String>,
+    // Where deleted programs go; .trash in the download directory when unset
+    pub trash_directory: Option<PathBuf>,
+    // Days deleted programs can be restored for
+    pub trash_days: u32,
+}
+
+impl Default for StorageSettings {
+    fn default() -> Self {
+        Self {
+            quota_mb: None,
+            eviction: EvictionPolicy::default(),
+            pinned: BTreeSet::new(),
+            trash_directory: None,
+            trash_days: DEFAULT_TRASH_DAYS,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum EvictionPolicy {
+    // Programs that don't air at all go first, then the ones that aired longest ago
+    #[default]
+    LeastRecentlyAired,
+    // Programs nobody has watched go first, then the ones watched longest ago
+    LeastRecentlyWatched,
+    OldestDownload,
+    LargestFirst,
+    // Remove nothing; downloads that would go over the quota are refused
+    Never,
+}
+
+impl fmt::Display for EvictionPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            EvictionPolicy::LeastRecentlyAired => "least recently aired",
+            EvictionPolicy::LeastRecentlyWatched => "least recently watched",
+            EvictionPolicy::OldestDownload => "oldest download",
+            EvictionPolicy::LargestFirst => "largest first",
+            EvictionPolicy::Never => "never",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// A downloaded program, with what the eviction policies go by
+#[derive(Serialize, Debug, Clone)]
+pub struct StoredProgram {
+    pub original_id: String,
+    pub title: String,
+    pub video_path: PathBuf,
+    pub sidecar_path: PathBuf,
+    // Video and sidecar together
+    pub bytes: u64,
+    pub downloaded: DateTime<Utc>,
+    pub last_aired: Option<DateTime<Utc>>,
+    pub last_watched: Option<DateTime<Utc>>,
+    pub pinned: bool,
+}
+
+// What has to go for a download to fit under the quota
+#[derive(Serialize, Debug, Clone)]
+pub struct EvictionPlan {
+    pub policy: EvictionPolicy,
+    pub quota_bytes: u64,
+    // Programs and the trash together
+    pub used_bytes: u64,
+    // Deleted programs in a trash inside the download directory, which still take up its space
+    pub trash_bytes: u64,
+    // Size of the download being made room for
+    pub incoming_bytes: u64,
+    // Whether the trash is emptied first; it goes before any program does
+    pub empty_trash: bool,
+    // In the order they would be removed
+    pub evict: Vec<StoredProgram>,
+    // How far over the quota it would still be with everything the policy allows removed
+    pub shortfall_bytes: u64,
+}
+
+impl EvictionPlan {
+    pub fn freed_bytes(&self) -> u64 {
+        let trash = if self.empty_trash { self.trash_bytes } else { 0 };
+        self.evict.iter().fold(trash, |freed, program| freed.saturating_add(program.bytes))
+    }
+}
+
+fn file_size(path: &Path) -> u64 {
+    fs::metadata(path).map(|metadata| metadata.len()).unwrap_or(0)
+}
+
+// A directory's real location, so paths spelled differently, through a
+// symlink or relative to the working directory compare equal
+pub fn resolve_dir(dir: &Path) -> PathBuf {
+    fs::canonicalize(dir).unwrap_or_else(|_| dir.to_path_buf())
+}
+
+// When a program last started airing before `now`, going by the weekly grid
+fn last_aired(weekly: &WeeklySchedule, original_id: &str, time: &TimeSettings, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let second = time.second_of_week(now);
+    weekly
+        .airings(original_id)
+        .map(|slot| (second + SECONDS_PER_WEEK - slot.start_second % SECONDS_PER_WEEK) % SECONDS_PER_WEEK)
+        .min()
+        .map(|ago| now - Duration::seconds(ago as i64))
+}
+
+impl StorageSettings {
+    pub fn quota_bytes(&self) -> Option<u64> {
+        self.quota_mb.map(|mb| mb.saturating_mul(1024 * 1024))
+    }
+
+    // The library's programs that were downloaded into `dir`
+    pub fn stored_programs(&self, items: &[LibraryItem], dir: &Path, weekly: &WeeklySchedule, history: &WatchHistory, time: &TimeSettings, now: DateTime<Utc>) -> Vec<StoredProgram> {
+        let watched = history.last_watched();
+        let dir = resolve_dir(dir);
+        items
+            .iter()
+            .filter(|item| item.video_path.parent().is_some_and(|parent| resolve_dir(parent) == dir))
+            .map(|item| {
+                let id = &item.metadata.original_id;
+                StoredProgram {
+                    original_id: id.clone(),
+                    title: item.metadata.title.clone(),
+                    video_path: item.video_path.clone(),
+                    sidecar_path: item.sidecar_path.clone(),
+                    bytes: file_size(&item.video_path).saturating_add(file_size(&item.sidecar_path)),
+                    downloaded: item.metadata.download_date,
+                    last_aired: last_aired(weekly, id, time, now),
+                    last_watched: watched.get(id).copied(),
+                    pinned: self.pinned.contains(id),
+                }
+            })
+            .collect()
+    }
+
+    // Choose what to remove so `incoming_bytes` more fit under the quota,
+    // emptying the trash before removing any program; None when there is no quota
+    pub fn plan(&self, mut programs: Vec<StoredProgram>, trash_bytes: u64, incoming_bytes: u64) -> Option<EvictionPlan> {
+        let quota_bytes = self.quota_bytes()?;
+        let used_bytes = programs.iter().fold(trash_bytes, |used, program| used.saturating_add(program.bytes));
+        let mut over = used_bytes.saturating_add(incoming_bytes).saturating_sub(quota_bytes);
+        let empty_trash = over > 0 && trash_bytes > 0 && self.eviction != EvictionPolicy::Never;
+        if empty_trash {
+            over = over.saturating_sub(trash_bytes);
+        }
+
+        programs.retain(|program| !program.pinned && self.eviction != EvictionPolicy::Never);
+        match self.eviction {
+            EvictionPolicy::LeastRecentlyAired => programs.sort_by_key(|program| (program.last_aired, program.downloaded)),
+            EvictionPolicy::LeastRecentlyWatched => programs.sort_by_key(|program| (program.last_watched, program.downloaded)),
+            EvictionPolicy::OldestDownload => programs.sort_by_key(|program| program.downloaded),
+            EvictionPolicy::LargestFirst => programs.sort_by_key(|program| std::cmp::Reverse(program.bytes)),
+            EvictionPolicy::Never => {}
+        }
+
+        let mut evict = Vec::new();
+        for program in programs {
+            if over == 0 {
+                break;
+            }
+            over = over.saturating_sub(program.bytes);
+            evict.push(program);
+        }
+        Some(EvictionPlan {
+            policy: self.eviction,
+            quota_bytes,
+            used_bytes,
+            trash_bytes,
+            incoming_bytes,
+            empty_trash,
+            evict,
+            shortfall_bytes: over,
+        })
+    }
+}
+
+// Space to leave free on the disk after a download, so sidecars and
+// everything else on it can still be written
+pub const FREE_SPACE_RESERVE: u64 = 100 * 1024 * 1024;
+
+// Bytes available to this user on the filesystem holding `path`
+pub fn free_space(path: &Path) -> Result<u64> {
+    let c_path = CString::new(path.as_os_str().as_bytes()).context("path contains a NUL byte")?;
+    let mut stats = MaybeUninit::<libc::statvfs>::uninit();
+    // SAFETY: c_path is NUL-terminated and statvfs only writes to stats
+    if unsafe { libc::statvfs(c_path.as_ptr(), stats.as_mut_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error()).with_context(|| format!("Failed to check free space on {}", path.display()));
+    }
+    // SAFETY: statvfs succeeded, so it filled in stats
+    let stats = unsafe { stats.assume_init() };
+    Ok(stats.f_bavail * stats.f_frsize)
+}
+
+// Delete a program's video, sidecar and thumbnails for good. Eviction skips
+// the trash, which would free nothing.
+pub fn remove(program: &StoredProgram) -> Result<()> {
+    let companions = library::companion_files(&program.video_path);
+    for path in std::iter::once(&program.video_path).chain(&companions) {
+        fs::remove_file(path).with_context(|| format!("Failed to remove {}", path.display()))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::{WatchEvent, WatchKind, DEFAULT_VIEWER};
+    use crate::local_time::ClockFormat;
+    use crate::schedule::{Slot, SlotKind};
+    use crate::sidecar;
+    use chrono::TimeZone;
+
+    const MB: u64 = 1024 * 1024;
+
+    fn day(day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(1994, 10, day, 12, 0, 0).unwrap()
+    }
+
+    fn program(original_id: &str, megabytes: u64, downloaded: u32, aired: Option<u32>, watched: Option<u32>) -> StoredProgram {
+        StoredProgram {
+            original_id: original_id.to_string(),
+            title: original_id.to_string(),
+            video_path: PathBuf::from(format!("/videos/{}.mp4", original_id)),
+            sidecar_path: PathBuf::from(format!("/videos/{}.json", original_id)),
+            bytes: megabytes * MB,
+            downloaded: day(downloaded),
+            last_aired: aired.map(day),
+            last_watched: watched.map(day),
+            pinned: false,
+        }
+    }
+
+    // 400 MB of programs, under a 500 MB quota
+    fn library() -> Vec<StoredProgram> {
+        vec![
+            program("aired-old", 100, 3, Some(10), Some(14)),
+            program("never-aired", 100, 4, None, Some(12)),
+            program("big", 150, 5, Some(16), None),
+            program("oldest", 50, 1, Some(15), Some(13)),
+        ]
+    }
+
+    fn settings(eviction: EvictionPolicy) -> StorageSettings {
+        StorageSettings { quota_mb: Some(500), eviction, ..Default::default() }
+    }
+
+    fn evicted(plan: &EvictionPlan) -> Vec<&str> {
+        plan.evict.iter().map(|program| program.original_id.as_str()).collect()
+    }
+
+    #[test]
+    fn quotas_are_whole_megabytes_without_overflowing() {
+        assert_eq!(StorageSettings::default().quota_bytes(), None);
+        assert_eq!(StorageSettings { quota_mb: Some(2), ..Default::default() }.quota_bytes(), Some(2 * MB));
+        assert_eq!(StorageSettings { quota_mb: Some(u64::MAX), ..Default::default() }.quota_bytes(), Some(u64::MAX));
+        assert!(StorageSettings::default().plan(library(), 0, u64::MAX).is_none());
+
+        let plan = settings(EvictionPolicy::LargestFirst).plan(library(), 0, u64::MAX).unwrap();
+        assert_eq!(evicted(&plan).len(), 4);
+        assert_eq!(plan.freed_bytes(), 400 * MB);
+        assert_eq!(plan.shortfall_bytes, u64::MAX - 900 * MB);
+
+        let mut huge = library();
+        huge[0].bytes = u64::MAX;
+        let plan = settings(EvictionPolicy::LargestFirst).plan(huge, u64::MAX, 0).unwrap();
+        assert_eq!(plan.used_bytes, u64::MAX);
+        assert_eq!(plan.freed_bytes(), u64::MAX);
+    }
+
+    #[test]
+    fn each_policy_removes_programs_in_its_own_order() {
+        // 400 MB used; a 250 MB download needs 150 MB freed
+        for (policy, expected) in [
+            (EvictionPolicy::LeastRecentlyAired, vec!["never-aired", "aired-old"]),
+            (EvictionPolicy::LeastRecentlyWatched, vec!["big"]),
+            (EvictionPolicy::OldestDownload, vec!["oldest", "aired-old"]),
+            (EvictionPolicy::LargestFirst, vec!["big"]),
+        ] {
+            let plan = settings(policy).plan(library(), 0, 250 * MB).unwrap();
+            assert_eq!(evicted(&plan), expected, "{}", policy);
+            assert_eq!(plan.shortfall_bytes, 0, "{}", policy);
+            assert!(plan.freed_bytes() >= 150 * MB, "{}", policy);
+        }
+
+        // Nothing goes when the download already fits
+        let plan = settings(EvictionPolicy::LargestFirst).plan(library(), 0, 100 * MB).unwrap();
+        assert!(evicted(&plan).is_empty());
+        assert!(!plan.empty_trash);
+    }
+
+    #[test]
+    fn pinned_programs_and_the_never_policy_keep_everything() {
+        let mut pinned = settings(EvictionPolicy::LargestFirst);
+        pinned.pinned.insert("big".to_string());
+        let mut programs = library();
+        programs[2].pinned = true;
+        let plan = pinned.plan(programs, 0, 250 * MB).unwrap();
+        assert_eq!(evicted(&plan), vec!["aired-old", "never-aired"]);
+
+        // Everything removable still isn't enough
+        let mut programs = library();
+        programs.iter_mut().for_each(|program| program.pinned = true);
+        let plan = settings(EvictionPolicy::LargestFirst).plan(programs, 0, 250 * MB).unwrap();
+        assert!(evicted(&plan).is_empty());
+        assert_eq!(plan.shortfall_bytes, 150 * MB);
+
+        let plan = settings(EvictionPolicy::Never).plan(library(), 80 * MB, 250 * MB).unwrap();
+        assert!(evicted(&plan).is_empty());
+        assert!(!plan.empty_trash);
+        assert_eq!(plan.shortfall_bytes, 230 * MB);
+    }
+
+    #[test]
+    fn the_trash_is_emptied_before_any_program_goes() {
+        // 80 MB of trash brings the 400 MB library to 480 MB
+        let plan = settings(EvictionPolicy::OldestDownload).plan(library(), 80 * MB, 80 * MB).unwrap();
+        assert!(plan.empty_trash);
+        assert!(evicted(&plan).is_empty());
+        assert_eq!(plan.freed_bytes(), 80 * MB);
+
+        let plan = settings(EvictionPolicy::OldestDownload).plan(library(), 80 * MB, 200 * MB).unwrap();
+        assert!(plan.empty_trash);
+        assert_eq!(evicted(&plan), vec!["oldest", "aired-old"]);
+        assert_eq!(plan.used_bytes, 480 * MB);
+    }
+
+    #[test]
+    fn stored_programs_are_the_ones_in_the_download_directory() {
+        let dir = std::env::temp_dir().join(format!("stored-programs-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("downloads")).unwrap();
+        let item = |path: PathBuf, original_id: &str| {
+            fs::write(&path, vec![0u8; 1000]).unwrap();
+            let sidecar_path = sidecar::sidecar_path(&path);
+            fs::write(&sidecar_path, vec![0u8; 24]).unwrap();
+            LibraryItem { video_path: path, sidecar_path, metadata: sidecar::test_program(original_id, "News", 5, "30:00") }
+        };
+        let items = vec![
+            item(dir.join("downloads/news.mp4"), "news"),
+            item(dir.join("downloads/film.mp4"), "film"),
+            item(dir.join("elsewhere.mp4"), "elsewhere"),
+        ];
+
+        // "news" aired at 10:00 on Monday 17 October and was watched the day before
+        let mut weekly = WeeklySchedule::default();
+        let slot = Slot {
+            start_second: 10 * 3600,
+            length_seconds: 1800,
+            kind: SlotKind::Program,
+            original_id: "news".to_string(),
+            title: "News".to_string(),
+            segments: Vec::new(),
+        };
+        weekly.channels.insert(5, vec![slot]);
+        let mut history = WatchHistory::default();
+        let event = WatchEvent { kind: WatchKind::Play, at: day(16), channel: None, video_id: None, original_id: Some("news".to_string()), title: "News".to_string() };
+        history.record(DEFAULT_VIEWER, event);
+        let time = TimeSettings { timezone: chrono_tz::UTC, clock_format: ClockFormat::default() };
+        let mut settings = settings(EvictionPolicy::LeastRecentlyAired);
+        settings.pinned.insert("film".to_string());
+
+        // The directory matches however its path is spelled
+        let stored = settings.stored_programs(&items, &dir.join("downloads/../downloads"), &weekly, &history, &time, day(17));
+        let ids: Vec<&str> = stored.iter().map(|program| program.original_id.as_str()).collect();
+        assert_eq!(ids, vec!["news", "film"]);
+        assert_eq!(stored[0].bytes, 1024);
+        assert_eq!(stored[0].last_aired, Some(day(17) - Duration::hours(2)));
+        assert_eq!(stored[0].last_watched, Some(day(16)));
+        assert!(!stored[0].pinned);
+        assert_eq!((stored[1].last_aired, stored[1].last_watched, stored[1].pinned), (None, None, true));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,263 @@
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::path::Path;
+
+// Default location of the saved searches, relative to the working directory
+pub const DEFAULT_SUBSCRIPTIONS_FILE: &str = "./subscriptions.json";
+
+pub const DEFAULT_MAX_ITEMS: usize = 5;
+pub const DEFAULT_INTERVAL_HOURS: u32 = 24;
+
+fn default_max_items() -> usize {
+    DEFAULT_MAX_ITEMS
+}
+
+fn default_interval_hours() -> u32 {
+    DEFAULT_INTERVAL_HOURS
+}
+
+// A saved Internet Archive search, rerun every so often so new items are
+// downloaded as they appear
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Subscription {
+    pub name: String,
+    // Search keywords, as typed into the menu's search
+    pub query: String,
+    #[serde(default)]
+    pub filters: SearchFilters,
+    // Channel new downloads are filed under instead of the one guessed from their category
+    #[serde(default)]
+    pub channel: Option<u8>,
+    // New items downloaded per run; the rest wait for the next one
+    #[serde(default = "default_max_items")]
+    pub max_items: usize,
+    // Items larger than this are skipped; items of unknown size are not
+    #[serde(default)]
+    pub max_item_mb: Option<u64>,
+    #[serde(default = "default_interval_hours")]
+    pub interval_hours: u32,
+    #[serde(default)]
+    pub last_run: Option<DateTime<Utc>>,
+    // What the last run queued, or why it failed
+    #[serde(default)]
+    pub last_result: Option<String>,
+    // Identifiers queued by earlier runs, so videos deleted from the library stay deleted
+    #[serde(default)]
+    pub seen: BTreeSet<String>,
+}
+
+// Narrows a search beyond its keywords; unset fields match everything
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct SearchFilters {
+    // Internet Archive collection identifier, e.g. "classic_tv_commercials"
+    pub collection: Option<String>,
+    pub creator: Option<String>,
+    pub year_from: Option<i32>,
+    pub year_to: Option<i32>,
+}
+
+impl Subscription {
+    // The keywords plus filters in Internet Archive search syntax
+    pub fn search_query(&self) -> String {
+        let mut clauses = vec![format!("({})", self.query.trim())];
+        if let Some(collection) = &self.filters.collection {
+            clauses.push(format!("collection:({})", collection));
+        }
+        if let Some(creator) = &self.filters.creator {
+            clauses.push(format!("creator:({})", creator));
+        }
+        match (self.filters.year_from, self.filters.year_to) {
+            (None, None) => {}
+            (from, to) => clauses.push(format!(
+                "year:[{} TO {}]",
+                from.map(|year| year.to_string()).unwrap_or_else(|| "*".to_string()),
+                to.map(|year| year.to_string()).unwrap_or_else(|| "*".to_string())
+            )),
+        }
+        clauses.join(" AND ")
+    }
+
+    pub fn is_due(&self, now: DateTime<Utc>) -> bool {
+        self.last_run.is_none_or(|last_run| now >= last_run + Duration::hours(self.interval_hours as i64))
+    }
+
+    // Search results worth downloading, best first: not in the library, not
+    // queued before and not over the size limit, up to max_items of them
+    pub fn pick_new<'a>(&self, results: impl IntoIterator<Item = (&'a str, Option<u64>)>, library: &HashSet<String>) -> Vec<String> {
+        let max_bytes = self.max_item_mb.map(|mb| mb.saturating_mul(1024 * 1024));
+        results
+            .into_iter()
+            .filter(|(identifier, _)| !library.contains(*identifier) && !self.seen.contains(*identifier))
+            .filter(|(_, size)| !matches!((size, max_bytes), (Some(size), Some(max)) if *size > max))
+            .map(|(identifier, _)| identifier.to_string())
+            .take(self.max_items)
+            .collect()
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.name.trim().is_empty() || self.name.contains('/') {
+            return Err("name must not be empty or contain '/'".to_string());
+        }
+        if self.query.trim().is_empty() {
+            return Err("query must not be empty".to_string());
+        }
+        if self.max_items == 0 {
+            return Err("max_items must be at least 1".to_string());
+        }
+        if self.interval_hours == 0 {
+            return Err("interval_hours must be at least 1".to_string());
+        }
+        if let (Some(from), Some(to)) = (self.filters.year_from, self.filters.year_to) {
+            if from > to {
+                return Err(format!("year_from {} is after year_to {}", from, to));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Every saved search, stored as one JSON file shared by the menu and the server
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SubscriptionList {
+    #[serde(default)]
+    pub subscriptions: Vec<Subscription>,
+}
+
+impl SubscriptionList {
+    // Load the list, treating a missing file as having no subscriptions
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read subscriptions {}", path.display()))?;
+        serde_json::from_str(&content).with_context(|| format!("Failed to parse subscriptions {}", path.display()))
+    }
+
+    // Write the list, replacing the existing file atomically
+    pub fn save(&self, path: &Path) -> Result<()> {
+        for subscription in &self.subscriptions {
+            subscription.validate().map_err(|e| anyhow!("subscription '{}': {}", subscription.name, e))?;
+        }
+        let json = serde_json::to_string_pretty(self)?;
+        let tmp_path = path.with_extension("json.tmp");
+        fs::write(&tmp_path, json).with_context(|| format!("Failed to write subscriptions {}", path.display()))?;
+        fs::rename(&tmp_path, path).with_context(|| format!("Failed to write subscriptions {}", path.display()))?;
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Subscription> {
+        self.subscriptions.iter().find(|subscription| subscription.name == name)
+    }
+
+    pub fn add(&mut self, subscription: Subscription) -> Result<()> {
+        subscription.validate().map_err(|e| anyhow!(e))?;
+        if self.get(&subscription.name).is_some() {
+            bail!("a subscription named '{}' already exists", subscription.name);
+        }
+        self.subscriptions.push(subscription);
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<Subscription> {
+        let position = self.subscriptions.iter().position(|subscription| subscription.name == name)?;
+        Some(self.subscriptions.remove(position))
+    }
+
+    // Note a finished run. Runs take a while, so callers reload the list first
+    // rather than saving over edits made in the meantime.
+    pub fn record_run(&mut self, name: &str, at: DateTime<Utc>, queued: &[String]) {
+        if let Some(subscription) = self.subscriptions.iter_mut().find(|subscription| subscription.name == name) {
+            subscription.last_run = Some(at);
+            subscription.last_result = Some(match queued.len() {
+                0 => "nothing new".to_string(),
+                count => format!("queued {} new item(s)", count),
+            });
+            subscription.seen.extend(queued.iter().cloned());
+        }
+    }
+
+    // A failed run leaves last_run alone, so it is retried at the next check
+    pub fn record_failure(&mut self, name: &str, error: &str) {
+        if let Some(subscription) = self.subscriptions.iter_mut().find(|subscription| subscription.name == name) {
+            subscription.last_result = Some(format!("failed: {}", error));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const MB: u64 = 1024 * 1024;
+
+    fn subscription(name: &str) -> Subscription {
+        serde_json::from_value(serde_json::json!({"name": name, "query": "cleveland news"})).unwrap()
+    }
+
+    #[test]
+    fn queries_combine_keywords_and_filters() {
+        let mut news = subscription("news");
+        assert_eq!(news.search_query(), "(cleveland news)");
+        news.filters = SearchFilters {
+            collection: Some("tvarchive".to_string()),
+            creator: Some("WEWS".to_string()),
+            year_from: Some(1980),
+            year_to: None,
+        };
+        assert_eq!(news.search_query(), "(cleveland news) AND collection:(tvarchive) AND creator:(WEWS) AND year:[1980 TO *]");
+    }
+
+    #[test]
+    fn picks_only_new_items_that_fit_up_to_the_limit() {
+        let mut news = subscription("news");
+        news.max_items = 2;
+        news.max_item_mb = Some(500);
+        news.seen.insert("seen-before".to_string());
+        let library: HashSet<String> = HashSet::from(["in-library".to_string()]);
+        let results = [
+            ("in-library", Some(MB)),
+            ("seen-before", Some(MB)),
+            ("too-big", Some(501 * MB)),
+            ("unknown-size", None),
+            ("fits", Some(500 * MB)),
+            ("one-too-many", Some(MB)),
+        ];
+        assert_eq!(news.pick_new(results, &library), vec!["unknown-size", "fits"]);
+
+        news.max_item_mb = Some(u64::MAX);
+        assert_eq!(news.pick_new([("huge", Some(u64::MAX))], &library), vec!["huge"]);
+    }
+
+    #[test]
+    fn runs_remember_what_they_queued_so_it_is_never_picked_again() {
+        let mut list = SubscriptionList::default();
+        list.add(subscription("news")).unwrap();
+        assert!(list.add(subscription("news")).is_err());
+        assert!(list.add(subscription("a/b")).is_err());
+
+        let at = Utc.with_ymd_and_hms(1994, 10, 17, 6, 0, 0).unwrap();
+        assert!(list.get("news").unwrap().is_due(at));
+        list.record_run("news", at, &["first".to_string(), "second".to_string()]);
+        list.record_run("news", at, &["first".to_string()]);
+        let news = list.get("news").unwrap();
+        assert_eq!(news.seen, BTreeSet::from(["first".to_string(), "second".to_string()]));
+        assert_eq!(news.last_result.as_deref(), Some("queued 1 new item(s)"));
+        assert!(!news.is_due(at + Duration::hours(23)));
+        assert!(news.is_due(at + Duration::hours(24)));
+        assert_eq!(news.pick_new([("first", None), ("third", None)], &HashSet::new()), vec!["third"]);
+
+        // A failure keeps the last run time, so the search is retried at the next check
+        list.record_failure("news", "timed out");
+        let news = list.get("news").unwrap();
+        assert_eq!((news.last_run, news.last_result.as_deref()), (Some(at), Some("failed: timed out")));
+
+        assert!(list.remove("news").is_some());
+        assert!(list.remove("news").is_none());
+    }
+}
@@ -0,0 +1,336 @@
+use chrono::{DateTime, Duration, Utc};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use std::io;
+use std::time::Instant;
+use tui::layout::{Alignment, Constraint, Direction, Layout};
+use tui::style::{Modifier, Style};
+use tui::text::Line;
+use tui::widgets::{Block, Borders, Paragraph};
+use tui::Frame;
+
+use crate::channels::OffAirPattern;
+use crate::clock::Clock;
+use crate::guide::{self, CHANNEL_STYLE, GUIDE_STYLE};
+use crate::player::Player;
+use crate::tuner::{Source, Tuner, Tuning};
+
+// Typed channel numbers are committed after this pause, like a cable box
+const DIGIT_TIMEOUT: std::time::Duration = std::time::Duration::from_millis(1500);
+const BANNER_DURATION: std::time::Duration = std::time::Duration::from_secs(4);
+
+// What a key press asks the surfing loop to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Nothing,
+    Tune(u8),
+    Quit,
+}
+
+// Channel-flipping state: the channel being watched, the one before it and
+// any digits typed so far
+pub struct Surf<'a> {
+    tuner: &'a Tuner,
+    channels: Vec<u8>,
+    current: Option<u8>,
+    previous: Option<u8>,
+    digits: String,
+    last_digit_at: Option<Instant>,
+    tuning: Option<Tuning>,
+    // When what is playing runs out and the channel has to be tuned again
+    retune_at: Option<DateTime<Utc>>,
+}
+
+impl<'a> Surf<'a> {
+    pub fn new(tuner: &'a Tuner) -> Self {
+        Self {
+            tuner,
+            channels: tuner.channels(),
+            current: None,
+            previous: None,
+            digits: String::new(),
+            last_digit_at: None,
+            tuning: None,
+            retune_at: None,
+        }
+    }
+
+    pub fn current(&self) -> Option<u8> {
+        self.current
+    }
+
+    // Next or previous channel with programming, wrapping around the dial
+    fn step(&self, forward: bool) -> Option<u8> {
+        let current = match self.current {
+            Some(current) => current,
+            None => return self.channels.first().copied(),
+        };
+        if forward {
+            self.channels.iter().find(|&&c| c > current).or(self.channels.first()).copied()
+        } else {
+            self.channels.iter().rev().find(|&&c| c < current).or(self.channels.last()).copied()
+        }
+    }
+
+    pub fn handle_key(&mut self, key: KeyCode) -> Action {
+        match key {
+            KeyCode::Char('q') | KeyCode::Esc => Action::Quit,
+            KeyCode::Up | KeyCode::Char('+') => self.step(true).map(Action::Tune).unwrap_or(Action::Nothing),
+            KeyCode::Down | KeyCode::Char('-') => self.step(false).map(Action::Tune).unwrap_or(Action::Nothing),
+            KeyCode::Backspace | KeyCode::Char('l') => self.previous.map(Action::Tune).unwrap_or(Action::Nothing),
+            KeyCode::Char(digit) if digit.is_ascii_digit() => {
+                self.digits.push(digit);
+                self.last_digit_at = Some(Instant::now());
+                if self.digits.len() == 3 {
+                    self.commit_digits()
+                } else {
+                    Action::Nothing
+                }
+            }
+            KeyCode::Enter => self.commit_digits(),
+            _ => Action::Nothing,
+        }
+    }
+
+    // Tune to the typed number once the viewer stops typing
+    pub fn tick(&mut self) -> Action {
+        match self.last_digit_at {
+            Some(at) if at.elapsed() >= DIGIT_TIMEOUT => self.commit_digits(),
+            _ => Action::Nothing,
+        }
+    }
+
+    fn commit_digits(&mut self) -> Action {
+        let number = self.digits.parse::<u8>().ok().filter(|&n| n > 0);
+        self.digits.clear();
+        self.last_digit_at = None;
+        number.map(Action::Tune).unwrap_or(Action::Nothing)
+    }
+
+    // Switch to a channel and load what it is airing right now into the player
+    pub fn tune(&mut self, player: &mut Player, channel: u8, now: DateTime<Utc>) -> io::Result<()> {
+        self.switch_to(channel, now);
+
+        match &self.tuning {
+            Some(tuning) => {
+                player.play(&tuning.source, tuning.offset_seconds)?;
+                self.retune_at = Some(now + Duration::seconds(tuning.remaining_seconds as i64));
+            }
+            // Nothing on this channel: static, like an unused cable channel
+            None => {
+                player.play(&Source::Pattern(OffAirPattern::Static), 0)?;
+                self.retune_at = None;
+            }
+        }
+        player.show_text(&self.banner(), BANNER_DURATION)
+    }
+
+    // Make `channel` the one being watched, remembering the last one for Backspace
+    fn switch_to(&mut self, channel: u8, now: DateTime<Utc>) {
+        if self.current != Some(channel) {
+            self.previous = self.current;
+            self.current = Some(channel);
+        }
+        self.tuning = self.tuner.tune(channel, now);
+    }
+
+    // Move on to the next segment when the current one finishes
+    pub fn needs_retune(&self, now: DateTime<Utc>) -> Option<u8> {
+        match self.retune_at {
+            Some(at) if now >= at => self.current,
+            _ => None,
+        }
+    }
+
+    // Channel number, callsign and title, shown over the video
+    fn banner(&self) -> String {
+        let channel = self.current.map(|c| c.to_string()).unwrap_or_default();
+        match &self.tuning {
+            Some(tuning) => format!("{}  {}\n{}", channel, tuning.callsign, tuning.title),
+            None => channel,
+        }
+    }
+
+    pub fn draw(&self, frame: &mut Frame, now: DateTime<Utc>) {
+        let time = self.tuner.time();
+        let areas = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Min(7), Constraint::Length(1)])
+            .split(frame.size());
+
+        let clock = time.format_time(&time.local(now));
+        let header = format!(" CHANNEL SURFING{:>width$} ", clock, width = (areas[0].width as usize).saturating_sub(17));
+        frame.render_widget(Paragraph::new(header).style(GUIDE_STYLE.add_modifier(Modifier::BOLD)), areas[0]);
+
+        let number = if self.digits.is_empty() {
+            self.current.map(|c| c.to_string()).unwrap_or_else(|| "--".to_string())
+        } else {
+            format!("{}_", self.digits)
+        };
+        let mut lines = vec![
+            Line::from(""),
+            Line::styled(format!("  CH {}  ", number), CHANNEL_STYLE.add_modifier(Modifier::BOLD)),
+        ];
+        match &self.tuning {
+            Some(tuning) => {
+                lines.push(Line::styled(format!("  {}  ", tuning.callsign), CHANNEL_STYLE));
+                lines.push(Line::from(""));
+                lines.push(Line::styled(tuning.title.clone(), Style::new().add_modifier(Modifier::BOLD)));
+                if tuning.segment_title != tuning.title {
+                    lines.push(Line::from(tuning.segment_title.clone()));
+                }
+                lines.push(Line::from(format!(
+                    "{}  {} - {}",
+                    if tuning.off_air { "OFF AIR" } else { "ON AIR" },
+                    time.format_time(&time.local(tuning.starts_at)),
+                    time.format_time(&time.local(tuning.ends_at)),
+                )));
+            }
+            None if self.current.is_some() => {
+                lines.push(Line::from(""));
+                lines.push(Line::from("No signal"));
+            }
+            None => {}
+        }
+        if let Some(previous) = self.previous {
+            lines.push(Line::from(""));
+            lines.push(Line::from(format!("Last channel: {}", previous)));
+        }
+
+        let screen = Paragraph::new(lines)
+            .alignment(Alignment::Center)
+            .block(Block::default().borders(Borders::ALL))
+            .style(GUIDE_STYLE);
+        frame.render_widget(screen, areas[1]);
+
+        let help = "↑/↓ channel  0-9 enter number  Backspace last channel  q back";
+        frame.render_widget(Paragraph::new(help), areas[2]);
+    }
+}
+
+// Flip through channels in the player, starting on `start` or the lowest
+// channel, until the viewer quits or closes the player window
+pub fn run(tuner: &Tuner, clock: &dyn Clock, player_command: &str, start: Option<u8>) -> io::Result<()> {
+    let mut player = Player::launch(player_command)?;
+    let mut surf = Surf::new(tuner);
+    let (mut terminal, _guard) = guide::enter_terminal()?;
+
+    if let Some(channel) = start.or_else(|| surf.step(true)) {
+        surf.tune(&mut player, channel, clock.now())?;
+    }
+
+    while player.is_running() {
+        let now = clock.now();
+        if let Some(channel) = surf.needs_retune(now) {
+            surf.tune(&mut player, channel, now)?;
+        }
+        terminal.draw(|frame| surf.draw(frame, now))?;
+
+        // A typed number that timed out is tuned before the next key is read,
+        // so a key pressed in the same pass can't drop it
+        let timed_out = surf.tick();
+        if !apply(&mut surf, &mut player, clock, timed_out)? {
+            break;
+        }
+        if event::poll(std::time::Duration::from_millis(250))? {
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    let pressed = surf.handle_key(key.code);
+                    if !apply(&mut surf, &mut player, clock, pressed)? {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Carry out an action; false when the viewer asked to quit
+fn apply(surf: &mut Surf, player: &mut Player, clock: &dyn Clock, action: Action) -> io::Result<bool> {
+    match action {
+        Action::Quit => return Ok(false),
+        Action::Tune(channel) => surf.tune(player, channel, clock.now())?,
+        Action::Nothing => {}
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channels::ChannelMap;
+    use crate::local_time::TimeSettings;
+    use crate::schedule::WeeklySchedule;
+    use chrono::TimeZone;
+
+    fn tuner() -> Tuner {
+        let mut schedule = WeeklySchedule::default();
+        for channel in [3, 5, 12] {
+            schedule.channels.insert(channel, Vec::new());
+        }
+        Tuner::new(schedule, &[], &ChannelMap::default(), TimeSettings::default())
+    }
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(1994, 10, 17, 23, 30, 0).unwrap()
+    }
+
+    #[test]
+    fn arrows_flip_through_channels_and_wrap() {
+        let tuner = tuner();
+        let mut surf = Surf::new(&tuner);
+        assert_eq!(surf.handle_key(KeyCode::Up), Action::Tune(3));
+        surf.switch_to(12, now());
+        assert_eq!(surf.handle_key(KeyCode::Up), Action::Tune(3));
+        assert_eq!(surf.handle_key(KeyCode::Char('-')), Action::Tune(5));
+        surf.switch_to(3, now());
+        assert_eq!(surf.handle_key(KeyCode::Down), Action::Tune(12));
+        assert_eq!(surf.handle_key(KeyCode::Char('q')), Action::Quit);
+        assert_eq!(surf.handle_key(KeyCode::Esc), Action::Quit);
+        assert_eq!(surf.handle_key(KeyCode::Char('x')), Action::Nothing);
+    }
+
+    #[test]
+    fn backspace_returns_to_the_last_channel() {
+        let tuner = tuner();
+        let mut surf = Surf::new(&tuner);
+        assert_eq!(surf.handle_key(KeyCode::Backspace), Action::Nothing);
+        surf.switch_to(3, now());
+        surf.switch_to(12, now());
+        // Retuning the same channel keeps the last one
+        surf.switch_to(12, now());
+        assert_eq!(surf.current(), Some(12));
+        assert_eq!(surf.handle_key(KeyCode::Backspace), Action::Tune(3));
+        surf.switch_to(3, now());
+        assert_eq!(surf.handle_key(KeyCode::Char('l')), Action::Tune(12));
+    }
+
+    #[test]
+    fn typed_numbers_tune_on_enter_a_third_digit_or_a_pause() {
+        let tuner = tuner();
+        let mut surf = Surf::new(&tuner);
+        assert_eq!(surf.handle_key(KeyCode::Char('1')), Action::Nothing);
+        assert_eq!(surf.handle_key(KeyCode::Char('2')), Action::Nothing);
+        assert_eq!(surf.tick(), Action::Nothing);
+        assert_eq!(surf.handle_key(KeyCode::Enter), Action::Tune(12));
+
+        let actions: Vec<Action> = ['0', '0', '5'].into_iter().map(|digit| surf.handle_key(KeyCode::Char(digit))).collect();
+        assert_eq!(actions, vec![Action::Nothing, Action::Nothing, Action::Tune(5)]);
+        assert_eq!(surf.tick(), Action::Nothing);
+
+        surf.handle_key(KeyCode::Char('7'));
+        surf.last_digit_at = Some(Instant::now() - DIGIT_TIMEOUT);
+        assert_eq!(surf.tick(), Action::Tune(7));
+        assert_eq!(surf.tick(), Action::Nothing);
+
+        // Numbers that aren't a channel number do nothing
+        for digits in ["0", "300"] {
+            for digit in digits.chars() {
+                surf.handle_key(KeyCode::Char(digit));
+            }
+            assert_eq!(surf.handle_key(KeyCode::Enter), Action::Nothing, "{}", digits);
+        }
+        assert_eq!(surf.handle_key(KeyCode::Enter), Action::Nothing);
+    }
+}
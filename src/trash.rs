@@ -0,0 +1,321 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::library::{self, LibraryItem};
+
+// Days deleted programs stay in the trash before they are gone for good
+pub const DEFAULT_TRASH_DAYS: u32 = 30;
+
+// The trash folder inside the download directory, when no other is configured
+pub const TRASH_DIR_NAME: &str = ".trash";
+
+// Each entry's folder holds its files and this manifest
+const MANIFEST_FILE: &str = "trashed.json";
+
+// A deleted video and its companion files, kept so they can be restored
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TrashEntry {
+    // Name of the entry's folder, used to restore it
+    pub id: String,
+    pub title: String,
+    // Empty for videos that had no sidecar
+    #[serde(default)]
+    pub original_id: String,
+    pub trashed_at: DateTime<Utc>,
+    // Where each file was; in the entry's folder it keeps its file name
+    pub files: Vec<PathBuf>,
+}
+
+// A folder of deleted programs, one subfolder per program. Library scans only
+// look at files directly inside each root, so nothing in it is ever scheduled.
+pub struct Trash {
+    dir: PathBuf,
+}
+
+fn file_name(path: &Path) -> Result<&std::ffi::OsStr> {
+    path.file_name().with_context(|| format!("{} is not a file", path.display()))
+}
+
+// Rename, falling back to copying when the trash is on another filesystem
+fn move_file(from: &Path, to: &Path) -> Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to).with_context(|| format!("Failed to move {} to {}", from.display(), to.display()))?;
+    fs::remove_file(from).with_context(|| format!("Failed to remove {}", from.display()))
+}
+
+impl Trash {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // Everything in the trash, oldest first
+    pub fn entries(&self) -> Result<Vec<TrashEntry>> {
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut entries: Vec<TrashEntry> = Vec::new();
+        for folder in fs::read_dir(&self.dir).with_context(|| format!("Failed to read trash {}", self.dir.display()))? {
+            let manifest = folder?.path().join(MANIFEST_FILE);
+            if !manifest.exists() {
+                continue;
+            }
+            let content = fs::read_to_string(&manifest)
+                .with_context(|| format!("Failed to read {}", manifest.display()))?;
+            entries.push(serde_json::from_str(&content).with_context(|| format!("Failed to parse {}", manifest.display()))?);
+        }
+        entries.sort_by(|a, b| (a.trashed_at, &a.id).cmp(&(b.trashed_at, &b.id)));
+        Ok(entries)
+    }
+
+    // Bytes the trash takes up on disk
+    pub fn size(&self) -> u64 {
+        let Ok(folders) = fs::read_dir(&self.dir) else { return 0 };
+        folders
+            .filter_map(|folder| fs::read_dir(folder.ok()?.path()).ok())
+            .flatten()
+            .filter_map(|file| file.ok()?.metadata().ok())
+            .filter(|metadata| metadata.is_file())
+            .map(|metadata| metadata.len())
+            .sum()
+    }
+
+    // The entry with this ID, or the most recently trashed copy of this original_id
+    pub fn find(&self, key: &str) -> Result<Option<TrashEntry>> {
+        let entries = self.entries()?;
+        Ok(entries.iter().find(|entry| entry.id == key).or_else(|| entries.iter().rev().find(|entry| entry.original_id == key)).cloned())
+    }
+
+    // Move a video and its sidecar and thumbnails into a new entry
+    pub fn put(&self, video_path: &Path, title: &str, original_id: &str, now: DateTime<Utc>) -> Result<TrashEntry> {
+        let stem = video_path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default();
+        let base = format!("{}-{}", now.format("%Y%m%dT%H%M%S"), stem);
+        let mut id = base.clone();
+        for n in 2.. {
+            if !self.dir.join(&id).exists() {
+                break;
+            }
+            id = format!("{}-{}", base, n);
+        }
+        let folder = self.dir.join(&id);
+        fs::create_dir_all(&folder).with_context(|| format!("Failed to create {}", folder.display()))?;
+
+        let mut files = vec![video_path.to_path_buf()];
+        files.extend(library::companion_files(video_path));
+        let entry = TrashEntry {
+            id,
+            title: title.to_string(),
+            original_id: original_id.to_string(),
+            trashed_at: now,
+            files,
+        };
+        // Manifest first, so a move that fails part way still leaves something to restore
+        let manifest = folder.join(MANIFEST_FILE);
+        fs::write(&manifest, serde_json::to_string_pretty(&entry)?).with_context(|| format!("Failed to write {}", manifest.display()))?;
+        for file in &entry.files {
+            move_file(file, &folder.join(file_name(file)?))?;
+        }
+        Ok(entry)
+    }
+
+    // Put an entry's files back where they were, refusing to overwrite anything
+    pub fn restore(&self, entry: &TrashEntry) -> Result<()> {
+        let folder = self.dir.join(&entry.id);
+        let mut moves = Vec::new();
+        for file in &entry.files {
+            let trashed = folder.join(file_name(file)?);
+            if !trashed.exists() {
+                continue;
+            }
+            if file.exists() {
+                bail!("{} already exists; move it out of the way to restore {}", file.display(), entry.title);
+            }
+            moves.push((trashed, file));
+        }
+        for (trashed, file) in moves {
+            if let Some(parent) = file.parent() {
+                fs::create_dir_all(parent).with_context(|| format!("Failed to create {}", parent.display()))?;
+            }
+            move_file(&trashed, file)?;
+        }
+        fs::remove_dir_all(&folder).with_context(|| format!("Failed to remove {}", folder.display()))
+    }
+
+    // Delete an entry for good
+    pub fn purge(&self, entry: &TrashEntry) -> Result<()> {
+        let folder = self.dir.join(&entry.id);
+        fs::remove_dir_all(&folder).with_context(|| format!("Failed to remove {}", folder.display()))
+    }
+
+    // Delete for good whatever was trashed more than `days` ago, returning what went
+    pub fn purge_expired(&self, days: u32, now: DateTime<Utc>) -> Result<Vec<TrashEntry>> {
+        let cutoff = now - Duration::days(days as i64);
+        let expired: Vec<TrashEntry> = self.entries()?.into_iter().filter(|entry| entry.trashed_at < cutoff).collect();
+        for entry in &expired {
+            self.purge(entry)?;
+        }
+        Ok(expired)
+    }
+}
+
+// Which programs a selective delete picks. Every condition given must hold,
+// so an empty filter picks everything.
+#[derive(Debug, Clone, Default)]
+pub struct DeleteFilter {
+    pub channel: Option<u8>,
+    // Compared ignoring case
+    pub category: Option<String>,
+    // Downloaded more than this many days ago
+    pub older_than_days: Option<u32>,
+    // Shell-style pattern, with * and ?, matched against the file name or the title, ignoring case
+    pub pattern: Option<String>,
+}
+
+// A shell-style pattern as a regex matching the whole text
+fn glob_regex(pattern: &str) -> Result<Regex> {
+    let mut regex = String::from("(?i)^");
+    for c in pattern.chars() {
+        match c {
+            '*' => regex.push_str(".*"),
+            '?' => regex.push('.'),
+            _ => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    Regex::new(&regex).with_context(|| format!("invalid pattern '{}'", pattern))
+}
+
+impl DeleteFilter {
+    pub fn is_empty(&self) -> bool {
+        self.channel.is_none() && self.category.is_none() && self.older_than_days.is_none() && self.pattern.is_none()
+    }
+
+    // The items the filter picks
+    pub fn select<'a>(&self, items: &'a [LibraryItem], now: DateTime<Utc>) -> Result<Vec<&'a LibraryItem>> {
+        let pattern = self.pattern.as_deref().map(glob_regex).transpose()?;
+        let cutoff = self.older_than_days.map(|days| now - Duration::days(days as i64));
+        Ok(items
+            .iter()
+            .filter(|item| {
+                let metadata = &item.metadata;
+                let file = item.video_path.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+                self.channel.is_none_or(|channel| metadata.channel_number == channel)
+                    && self.category.as_ref().is_none_or(|category| metadata.category.eq_ignore_ascii_case(category))
+                    && cutoff.is_none_or(|cutoff| metadata.download_date < cutoff)
+                    && pattern.as_ref().is_none_or(|pattern| pattern.is_match(&file) || pattern.is_match(&metadata.title))
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sidecar;
+    use chrono::TimeZone;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn at(day: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(1994, 10, day, 12, 0, 0).unwrap()
+    }
+
+    fn item(file: &str, title: &str, category: &str, channel: u8, downloaded: DateTime<Utc>) -> LibraryItem {
+        let video_path = PathBuf::from("/videos").join(file);
+        let mut metadata = sidecar::test_program(file, category, channel, "30:00");
+        metadata.title = title.to_string();
+        metadata.download_date = downloaded;
+        LibraryItem { sidecar_path: sidecar::sidecar_path(&video_path), video_path, metadata }
+    }
+
+    #[test]
+    fn trashed_programs_can_be_restored_or_purged() {
+        let dir = temp_dir("trash-round-trip");
+        let videos = dir.join("videos");
+        fs::create_dir_all(&videos).unwrap();
+        let trash = Trash::new(dir.join(".trash"));
+        for name in ["show.mp4", "show.json", "show.jpg", "other.mp4"] {
+            fs::write(videos.join(name), name).unwrap();
+        }
+
+        let first = trash.put(&videos.join("show.mp4"), "Show", "show-1994", at(1)).unwrap();
+        assert!(!videos.join("show.mp4").exists());
+        assert!(!videos.join("show.json").exists());
+        assert!(!videos.join("show.jpg").exists());
+        assert_eq!(first.files.len(), 3);
+
+        // A second copy with the same name gets its own folder
+        fs::write(videos.join("show.mp4"), "again").unwrap();
+        let second = trash.put(&videos.join("show.mp4"), "Show", "show-1994", at(1)).unwrap();
+        assert_ne!(first.id, second.id);
+        let other = trash.put(&videos.join("other.mp4"), "Other", "", at(20)).unwrap();
+        assert!(trash.size() > 0);
+
+        assert_eq!(trash.find("show-1994").unwrap().unwrap().id, second.id);
+        assert_eq!(trash.find(&first.id).unwrap().unwrap().id, first.id);
+        assert!(trash.find("missing").unwrap().is_none());
+
+        trash.restore(&second).unwrap();
+        assert_eq!(fs::read_to_string(videos.join("show.mp4")).unwrap(), "again");
+        // Restoring the older copy would overwrite the restored one
+        assert!(trash.restore(&first).is_err());
+        assert!(!videos.join("show.json").exists());
+
+        let purged = trash.purge_expired(10, at(15)).unwrap();
+        assert_eq!(purged.iter().map(|entry| entry.id.as_str()).collect::<Vec<_>>(), vec![first.id.as_str()]);
+        let left: Vec<String> = trash.entries().unwrap().into_iter().map(|entry| entry.id).collect();
+        assert_eq!(left, vec![other.id]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn patterns_match_the_whole_text_ignoring_case() {
+        let pattern = glob_regex("*news?.mp4").unwrap();
+        assert!(pattern.is_match("Evening-NEWS1.mp4"));
+        assert!(!pattern.is_match("news.mp4"));
+        assert!(!pattern.is_match("news1.mp4.part"));
+        // Regex characters are literal
+        let pattern = glob_regex("a+b (1).*").unwrap();
+        assert!(pattern.is_match("a+b (1).mkv"));
+        assert!(!pattern.is_match("aab (1).mkv"));
+    }
+
+    #[test]
+    fn delete_filters_pick_items_matching_every_condition() {
+        let items = vec![
+            item("news-1.mp4", "Evening News", "News", 5, at(1)),
+            item("news-2.mp4", "Morning News", "news", 3, at(16)),
+            item("film.mp4", "Night of the Living Dead", "Movie", 5, at(1)),
+        ];
+        let picked = |filter: DeleteFilter| -> Vec<String> {
+            filter.select(&items, at(17)).unwrap().iter().map(|item| item.metadata.original_id.clone()).collect()
+        };
+
+        assert!(DeleteFilter::default().is_empty());
+        assert_eq!(picked(DeleteFilter::default()).len(), 3);
+        assert_eq!(picked(DeleteFilter { channel: Some(5), ..Default::default() }), vec!["news-1.mp4", "film.mp4"]);
+        assert_eq!(picked(DeleteFilter { category: Some("NEWS".to_string()), ..Default::default() }), vec!["news-1.mp4", "news-2.mp4"]);
+        assert_eq!(picked(DeleteFilter { older_than_days: Some(7), ..Default::default() }), vec!["news-1.mp4", "film.mp4"]);
+        assert_eq!(picked(DeleteFilter { pattern: Some("*living*".to_string()), ..Default::default() }), vec!["film.mp4"]);
+        assert_eq!(
+            picked(DeleteFilter { channel: Some(5), category: Some("news".to_string()), older_than_days: Some(7), pattern: Some("news-*".to_string()) }),
+            vec!["news-1.mp4"]
+        );
+        assert!(picked(DeleteFilter { channel: Some(3), older_than_days: Some(7), ..Default::default() }).is_empty());
+    }
+}
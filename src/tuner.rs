@@ -0,0 +1,213 @@
+use chrono::{DateTime, Duration, Utc};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+
+use crate::channels::{ChannelMap, OffAirPattern};
+use crate::library::{self, LibraryItem};
+use crate::local_time::TimeSettings;
+use crate::schedule::{SlotKind, WeeklySchedule};
+
+// What the player should show for a channel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    File(PathBuf),
+    // Generated test pattern, or static when there is nothing to play
+    Pattern(OffAirPattern),
+}
+
+// What a channel is airing at a given moment, and where to pick it up
+#[derive(Debug, Clone)]
+pub struct Tuning {
+    pub channel: u8,
+    pub callsign: String,
+    // True between sign-off and sign-on
+    pub off_air: bool,
+    pub title: String,
+    pub segment_title: String,
+    // Kind of the segment playing right now
+    pub kind: SlotKind,
+    pub source: Source,
+    pub offset_seconds: u32,
+    pub remaining_seconds: u32,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+}
+
+// Resolves channels to what they are airing right now, from the weekly grid
+pub struct Tuner {
+    schedule: WeeklySchedule,
+    files: HashMap<String, PathBuf>,
+    callsigns: BTreeMap<u8, String>,
+    patterns: HashMap<u8, OffAirPattern>,
+    time: TimeSettings,
+}
+
+impl Tuner {
+    pub fn new(schedule: WeeklySchedule, items: &[LibraryItem], channel_map: &ChannelMap, time: TimeSettings) -> Self {
+        let files = items
+            .iter()
+            .map(|item| (item.metadata.original_id.clone(), item.video_path.clone()))
+            .collect();
+        let patterns = channel_map
+            .channels
+            .iter()
+            .filter_map(|channel| channel.broadcast_hours.as_ref().map(|hours| (channel.number, hours.pattern)))
+            .collect();
+        Self {
+            callsigns: library::callsigns(items, channel_map),
+            schedule,
+            files,
+            patterns,
+            time,
+        }
+    }
+
+    pub fn schedule(&self) -> &WeeklySchedule {
+        &self.schedule
+    }
+
+    pub fn time(&self) -> &TimeSettings {
+        &self.time
+    }
+
+    // Channels with programming, in order
+    pub fn channels(&self) -> Vec<u8> {
+        self.schedule.channels.keys().copied().collect()
+    }
+
+    pub fn callsign(&self, channel: u8) -> &str {
+        self.callsigns.get(&channel).map(String::as_str).unwrap_or("")
+    }
+
+    pub fn tune(&self, channel: u8, now: DateTime<Utc>) -> Option<Tuning> {
+        let second_of_week = self.time.second_of_week(now);
+        let slot = self.schedule.slot_at(channel, second_of_week)?;
+        let starts_at = self.time.airing_start(slot.start_second, slot.length_seconds, now).with_timezone(&Utc);
+
+        let (segment_title, kind, source, offset_seconds, remaining_seconds) = match slot.segment_at(second_of_week) {
+            Some(segment) => {
+                let into_segment = second_of_week - segment.start_second;
+                (
+                    segment.title.clone(),
+                    segment.kind,
+                    self.source(channel, segment.kind, &segment.original_id),
+                    segment.offset_seconds + into_segment,
+                    segment.length_seconds - into_segment,
+                )
+            }
+            None => (
+                slot.title.clone(),
+                slot.kind,
+                self.source(channel, slot.kind, &slot.original_id),
+                0,
+                slot.end_second() - second_of_week,
+            ),
+        };
+
+        Some(Tuning {
+            channel,
+            callsign: self.callsign(channel).to_string(),
+            off_air: slot.kind == SlotKind::OffAir,
+            title: slot.title.clone(),
+            segment_title,
+            kind,
+            source,
+            offset_seconds,
+            remaining_seconds,
+            starts_at,
+            ends_at: starts_at + Duration::seconds(slot.length_seconds as i64),
+        })
+    }
+
+    // Clips come from the library; the test pattern between them is generated,
+    // and anything without a video file shows static
+    fn source(&self, channel: u8, kind: SlotKind, original_id: &str) -> Source {
+        match self.files.get(original_id) {
+            Some(path) => Source::File(path.clone()),
+            None if kind == SlotKind::OffAir => Source::Pattern(self.patterns.get(&channel).copied().unwrap_or_default()),
+            None => Source::Pattern(OffAirPattern::Static),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::channels::{BroadcastHours, ChannelConfig};
+    use crate::clock::{Clock, SimulatedClock};
+    use crate::local_time::ClockFormat;
+    use crate::schedule::{self, ScheduleConfig};
+    use crate::sidecar::test_program;
+
+    fn new_york() -> TimeSettings {
+        TimeSettings { timezone: chrono_tz::America::New_York, clock_format: ClockFormat::TwelveHour }
+    }
+
+    fn tuner(channel_map: &ChannelMap) -> Tuner {
+        let items = vec![LibraryItem {
+            video_path: PathBuf::from("/videos/show.ia.mp4"),
+            sidecar_path: PathBuf::from("/videos/show.ia.json"),
+            metadata: test_program("show", "Comedy", 3, "30:00"),
+        }];
+        let programs: Vec<_> = items.iter().map(|item| item.metadata.clone()).collect();
+        let weekly = schedule::build_weekly_schedule(&programs, &ScheduleConfig::default(), channel_map);
+        Tuner::new(weekly, &items, channel_map, new_york())
+    }
+
+    #[test]
+    fn tunes_at_a_simulated_moment() {
+        let tuner = tuner(&ChannelMap::default());
+        let time = new_york();
+        // A Monday evening
+        let clock = SimulatedClock::frozen(time.parse_local("1994-10-17T19:40").unwrap());
+
+        let tuning = tuner.tune(3, clock.now()).unwrap();
+        assert_eq!(tuning.source, Source::File(PathBuf::from("/videos/show.ia.mp4")));
+        assert!(!tuning.off_air);
+        assert_eq!(tuning.offset_seconds, 10 * 60);
+        assert_eq!(tuning.remaining_seconds, 20 * 60);
+        assert_eq!(tuning.starts_at, time.parse_local("1994-10-17T19:30").unwrap());
+        assert_eq!(tuning.ends_at, time.parse_local("1994-10-17T20:00").unwrap());
+
+        clock.advance(Duration::minutes(25));
+        let tuning = tuner.tune(3, clock.now()).unwrap();
+        assert_eq!(tuning.offset_seconds, 5 * 60);
+        assert_eq!(tuning.starts_at, time.parse_local("1994-10-17T20:00").unwrap());
+
+        assert!(tuner.tune(4, clock.now()).is_none());
+    }
+
+    #[test]
+    fn off_air_between_sign_off_and_sign_on() {
+        let channel_map = ChannelMap {
+            channels: vec![ChannelConfig {
+                number: 3,
+                callsign: Some("WEWS".to_string()),
+                dayparts: Vec::new(),
+                broadcast_hours: Some(BroadcastHours {
+                    sign_on: "6:00 AM".to_string(),
+                    sign_off: "1:00 AM".to_string(),
+                    sign_on_clip: None,
+                    sign_off_clip: None,
+                    pattern: OffAirPattern::Bars,
+                }),
+                name: None,
+                selection: None,
+                owner: None,
+            }],
+            ..Default::default()
+        };
+        let tuner = tuner(&channel_map);
+        let time = new_york();
+        let clock = SimulatedClock::frozen(time.parse_local("1994-10-18T03:00").unwrap());
+
+        let tuning = tuner.tune(3, clock.now()).unwrap();
+        assert!(tuning.off_air);
+        assert_eq!(tuning.source, Source::Pattern(OffAirPattern::Bars));
+        assert_eq!(tuning.callsign, "WEWS");
+        assert_eq!(tuning.ends_at, time.parse_local("1994-10-18T06:00").unwrap());
+
+        clock.set(time.parse_local("1994-10-18T06:00").unwrap());
+        assert!(!tuner.tune(3, clock.now()).unwrap().off_air);
+    }
+}